@@ -22,6 +22,12 @@ fn main() {
         file_content.push_str(&this_line);
         println!("cargo::rerun-if-env-changed={}", var_name);
     }
+    // unlike the others the seed has no default, leaving it to be random
+    let seed = env::var("SEED")
+        .map(|val_str| format!("Some({})", val_str.parse::<u64>().unwrap()))
+        .unwrap_or(String::from("None"));
+    file_content.push_str(&format!("const SEED: Option<u64> = {};\n", seed));
+    println!("cargo::rerun-if-env-changed=SEED");
 
     fs::write(&dest_path, &file_content).unwrap();
     println!("cargo::rerun-if-changed=build.rs");
@@ -0,0 +1,135 @@
+#![allow(clippy::needless_return)]
+
+//! Times the road's cell map with the standard library's SipHash and with
+//! Fx, replaying the cells the default 200 bike, 200 car road vacates,
+//! fills and looks ahead through over a run.
+//!
+//! `cargo run --release --example cell_hasher`
+
+use std::{
+    collections::HashMap,
+    hash::{BuildHasher, RandomState},
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use lovrle_rust_v2::{
+    population::{evenly_spaced_bikes, evenly_spaced_cars},
+    road::{Coord, Road, RoadOccupier, Vehicle},
+};
+use rustc_hash::FxBuildHasher;
+
+const UPDATES: usize = 200;
+// how far ahead of each vehicle's front the replay looks, as a gap search
+// does
+const LOOK_AHEAD: isize = 20;
+const ROUNDS: usize = 5;
+
+/// A vehicle and the cells it is in, wrapped onto the road as the road's
+/// own map keys them, with those of its front first.
+type VehicleCells = (Vehicle, Vec<Coord>);
+
+/// The cells each vehicle left and took in one update.
+struct Step {
+    moves: Vec<(Vehicle, Vec<Coord>, Vec<Coord>)>,
+}
+
+fn wrapped_cells(occupied_cells: impl Iterator<Item = Coord>, front: isize) -> Vec<Coord> {
+    let mut cells: Vec<Coord> = occupied_cells
+        .map(|coord| Coord {
+            lat: coord.lat,
+            long: coord.long.rem_euclid(2000),
+        })
+        .collect();
+    let front = front.rem_euclid(2000);
+    cells.sort_by_key(|coord| coord.long != front);
+    return cells;
+}
+
+fn vehicle_cells(road: &Road<200, 200, 2000, 7, 7>) -> Vec<VehicleCells> {
+    let bikes = road.bikes().iter().enumerate().map(|(bike_id, bike)| {
+        (
+            Vehicle::Bike(bike_id),
+            wrapped_cells(bike.occupied_cells(), bike.front()),
+        )
+    });
+    let cars = road.cars().iter().enumerate().map(|(car_id, car)| {
+        (
+            Vehicle::Car(car_id),
+            wrapped_cells(car.occupied_cells(), car.front()),
+        )
+    });
+    return bikes.chain(cars).collect();
+}
+
+fn record_steps() -> (Vec<VehicleCells>, Vec<Step>) {
+    let bikes = evenly_spaced_bikes::<200, 2000>(13).map(|builder| builder.build().unwrap());
+    let cars = evenly_spaced_cars::<200, 2000>().map(|builder| builder.build().unwrap());
+    let mut road = Road::<200, 200, 2000, 7, 7>::new(bikes, cars).unwrap();
+    road.reseed(1);
+    let start = vehicle_cells(&road);
+    let mut before = start.clone();
+    let steps = (0..UPDATES)
+        .map(|_| {
+            road.update().unwrap();
+            let after = vehicle_cells(&road);
+            let moves = before
+                .iter()
+                .zip(&after)
+                .map(|((vehicle, left), (_, taken))| (*vehicle, left.clone(), taken.clone()))
+                .collect();
+            before = after;
+            return Step { moves };
+        })
+        .collect();
+    return (start, steps);
+}
+
+fn replay<S: BuildHasher + Default>(start: &[VehicleCells], steps: &[Step]) -> Duration {
+    let mut cells: HashMap<Coord, Vehicle, S> = HashMap::with_hasher(S::default());
+    let timer = Instant::now();
+    for (vehicle, taken) in start {
+        for coord in taken {
+            cells.insert(*coord, *vehicle);
+        }
+    }
+    for step in steps {
+        for (_, left, taken) in &step.moves {
+            let front = taken[0].long;
+            for coord in taken.iter().take_while(|coord| coord.long == front) {
+                for d_long in 1..=LOOK_AHEAD {
+                    let ahead = Coord {
+                        lat: coord.lat,
+                        long: (coord.long + d_long).rem_euclid(2000),
+                    };
+                    black_box(cells.get(&ahead));
+                }
+            }
+            for coord in left {
+                cells.remove(coord);
+            }
+        }
+        for (vehicle, _, taken) in &step.moves {
+            for coord in taken {
+                cells.insert(*coord, *vehicle);
+            }
+        }
+    }
+    black_box(&cells);
+    return timer.elapsed();
+}
+
+fn main() {
+    let (start, steps) = record_steps();
+    for round in 0..ROUNDS {
+        let sip = replay::<RandomState>(&start, &steps);
+        let fx = replay::<FxBuildHasher>(&start, &steps);
+        println!(
+            "round {}: SipHash {:>9.2?} Fx {:>9.2?} per update, {:.1}x",
+            round,
+            sip / UPDATES as u32,
+            fx / UPDATES as u32,
+            sip.as_secs_f64() / fx.as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,45 @@
+#![allow(clippy::needless_return)]
+
+//! Times updating a road with only a couple of vehicles with and without
+//! rayon, where the threading overhead outweighs the work being split up.
+//!
+//! `cargo run --release --example parallel_overhead`
+
+use std::time::{Duration, Instant};
+
+use lovrle_rust_v2::{bike::BikeBuilder, car::CarBuilder, road::Road};
+
+const ITERATIONS: usize = 20_000;
+
+fn time_updates(parallel: bool) -> Duration {
+    let bike = BikeBuilder::default()
+        .with_front_at(0)
+        .with_right_at(15)
+        .build()
+        .expect("bike should be valid");
+    let car = CarBuilder::default()
+        .with_front_at(20)
+        .build()
+        .expect("car should be valid");
+    let mut road = Road::<1, 1, 100, 2, 14>::new([bike], [car]).expect("road should be valid");
+    road.reseed(0);
+    road.set_parallel(parallel);
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        road.update().expect("update should succeed");
+    }
+    return start.elapsed();
+}
+
+fn main() {
+    for parallel in [true, false] {
+        let elapsed = time_updates(parallel);
+        println!(
+            "parallel: {:5} {:>10.2?} total {:>8.2?} per update",
+            parallel,
+            elapsed,
+            elapsed / ITERATIONS as u32
+        );
+    }
+}
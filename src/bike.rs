@@ -1,30 +1,64 @@
-use std::cmp::{max, Ordering};
+use std::cmp::{max, min, Ordering};
 
 use anyhow::{anyhow, Ok, Result};
-use rand::{
-    distributions::Bernoulli,
-    prelude::{Distribution, IteratorRandom},
-};
-use serde::Serialize;
+use rand::distributions::Bernoulli;
+use serde::{Deserialize, Serialize};
 
-use crate::road::{Coord, RectangleOccupier, Road, RoadOccupier, Vehicle};
+use crate::{
+    probability::Probability,
+    rand_source::RandSource,
+    road::{Coord, Lane, RectangleOccupier, Road, RoadOccupier, Vehicle},
+};
 
-#[derive(Debug, Copy, Clone, Serialize)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub enum YStarSelectionStrategy {
     Rightmost,
     UniformRandom,
 }
 
+/// How quickly a bike speeds up: by `slow_acceleration` while at or below
+/// `max_slow_speed` and by `fast_acceleration` above it, as for cars.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpeedProfile {
+    pub slow_acceleration: isize,
+    pub fast_acceleration: isize,
+    pub max_slow_speed: isize,
+}
+
+impl SpeedProfile {
+    /// The same acceleration at every speed.
+    pub const fn constant(acceleration: isize) -> Self {
+        return Self {
+            slow_acceleration: acceleration,
+            fast_acceleration: acceleration,
+            max_slow_speed: 0,
+        };
+    }
+
+    pub const fn acceleration_at(&self, speed: isize) -> isize {
+        return match speed <= self.max_slow_speed {
+            true => self.slow_acceleration,
+            false => self.fast_acceleration,
+        };
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 pub struct Bike {
     occupation: RectangleOccupier,
     forward_speed_max: isize,
     pub forward_speed: isize,
-    forward_acceleration: isize,
+    speed_profile: SpeedProfile,
     rightward_speed_max: isize,
     ignore_lateral_distribution: Bernoulli,
+    // kept as the distributions don't expose them, for turning back into a
+    // builder
+    lateral_ignorance: Probability,
     decelerate_distribution: Bernoulli,
+    deceleration_prob: Probability,
     y_star_selection_strategy: YStarSelectionStrategy,
+    bike_lane_bias: usize,
+    group: u8,
 }
 
 #[allow(dead_code)]
@@ -33,10 +67,56 @@ impl Bike {
         return self.occupation.front;
     }
 
+    /// The tag the bike was built with, for telling cohorts of bikes apart.
+    pub const fn group(&self) -> u8 {
+        return self.group;
+    }
+
+    /// The same bike `d_long` further along the road.
+    pub(crate) const fn shifted(&self, d_long: isize) -> Self {
+        return Self {
+            occupation: RectangleOccupier {
+                front: self.occupation.front + d_long,
+                ..self.occupation
+            },
+            ..*self
+        };
+    }
+
     pub const fn length(&self) -> usize {
         return self.occupation.length;
     }
 
+    pub const fn forward_speed_max(&self) -> isize {
+        return self.forward_speed_max;
+    }
+
+    /// The bike's max speed under the road's conditions and on the grade its
+    /// front is currently on, reduced to the limit of any speed zone its
+    /// front is in and of the lanes it is in. Never negative.
+    pub fn effective_speed_max<
+        const B: usize,
+        const C: usize,
+        const L: usize,
+        const BLW: usize,
+        const MLW: usize,
+    >(
+        &self,
+        road: &Road<B, C, L, BLW, MLW>,
+    ) -> isize {
+        return [
+            road.speed_limit_at(self.front()),
+            road.lane_speed_limit_for(&self.occupation),
+        ]
+        .into_iter()
+        .flatten()
+        .fold(
+            road.graded_speed_max(self.forward_speed_max, self.front()),
+            min,
+        )
+        .max(0);
+    }
+
     /// Returns the positions that the bike could move to laterally
     pub const fn potential_lateral_positions(&self) -> impl Iterator<Item = isize> {
         // could add something to do with the width of the bike here,
@@ -48,14 +128,22 @@ impl Bike {
             ..(self.occupation.right + self.rightward_speed_max + 1);
     }
 
-    fn should_ignore_lateral_movement(&self) -> bool {
-        return self
-            .ignore_lateral_distribution
-            .sample(&mut rand::thread_rng());
+    pub fn should_ignore_lateral_movement(&self, source: &mut impl RandSource) -> bool {
+        return source.decide(&self.ignore_lateral_distribution);
+    }
+
+    pub fn should_decelerate(&self, source: &mut impl RandSource) -> bool {
+        return source.decide(&self.decelerate_distribution);
+    }
+
+    /// Changes the probability of the random deceleration mid-run.
+    pub fn set_deceleration_prob(&mut self, deceleration_prob: Probability) {
+        self.rebuild_decel(deceleration_prob);
     }
 
-    fn should_decelerate(&self) -> bool {
-        return self.decelerate_distribution.sample(&mut rand::thread_rng());
+    fn rebuild_decel(&mut self, p: Probability) {
+        self.decelerate_distribution = p.bernoulli();
+        self.deceleration_prob = p;
     }
 
     fn y_j_t_plus_1(&self) -> impl Iterator<Item = isize> {
@@ -72,17 +160,65 @@ impl Bike {
         &self,
         self_id: usize,
         road: &Road<B, C, L, BLW, MLW>,
+        source: &mut impl RandSource,
     ) -> Self {
-        if self.should_ignore_lateral_movement() {
+        if self.should_ignore_lateral_movement(source) {
             return Self { ..*self };
         } else {
-            return Self {
-                occupation: self.select_y_star(road, self_id),
-                ..*self
-            };
+            return self.choose_lateral(self.lateral_options(road, self_id), source);
         }
     }
 
+    /// The positions the bike could choose between in a lateral update that
+    /// isn't ignored, i.e. the highest priority of `y_prime_prime_j_t_plus_1`
+    /// (see `generate_y_stars`). Split from the choice itself so the options
+    /// can be found in parallel while the random choice is made in a fixed
+    /// order.
+    pub(crate) fn lateral_options<
+        const B: usize,
+        const C: usize,
+        const L: usize,
+        const BLW: usize,
+        const MLW: usize,
+    >(
+        &self,
+        road: &Road<B, C, L, BLW, MLW>,
+        self_id: usize,
+    ) -> Vec<RectangleOccupier> {
+        return self.generate_y_stars(road, self_id);
+    }
+
+    /// Whether the bike is on the motor lane with none of its lateral options
+    /// taking it onto the bike lane.
+    pub fn is_stuck_in_motor_lane<
+        const B: usize,
+        const C: usize,
+        const L: usize,
+        const BLW: usize,
+        const MLW: usize,
+    >(
+        &self,
+        road: &Road<B, C, L, BLW, MLW>,
+        self_id: usize,
+    ) -> bool {
+        return is_on_motor_lane(road, &self.occupation)
+            && self
+                .y_prime_prime_j_t_plus_1(road, self_id)
+                .iter()
+                .all(|option| is_on_motor_lane(road, option));
+    }
+
+    pub(crate) fn choose_lateral(
+        &self,
+        options: Vec<RectangleOccupier>,
+        source: &mut impl RandSource,
+    ) -> Self {
+        return Self {
+            occupation: self.choose_y_star(options, source),
+            ..*self
+        };
+    }
+
     fn y_prime_j_t_plus_1<
         'a,
         const B: usize,
@@ -94,7 +230,7 @@ impl Bike {
         &'a self,
         road: &'a Road<B, C, L, BLW, MLW>,
         self_id: &'a usize,
-    ) -> impl Iterator<Item = RectangleOccupier> + '_ {
+    ) -> impl Iterator<Item = RectangleOccupier> + 'a {
         return self
             .y_j_t_plus_1()
             // Step 1: check the availability of possible lateral positions
@@ -105,9 +241,35 @@ impl Bike {
             // check that the occupation is on the road
             .filter(|occupation| road.road_contains_occupier(occupation))
             // check that the spaces are free
-            .filter(|occupation| !road.is_collision_for(occupation, Vehicle::Bike(*self_id)));
+            .filter(|occupation| !road.is_collision_for(occupation, Vehicle::Bike(*self_id)))
+            // keep clear of cars that may widen into the space as they speed
+            // up, though staying still must always remain an option
+            .filter(|occupation| {
+                *occupation == self.occupation || !road.is_in_widening_car_path(occupation)
+            });
+    }
+
+    /// The front gap of a candidate position, less the bike lane bias if the
+    /// position is (even partly) in the motor lane.
+    fn biased_front_gap<
+        const B: usize,
+        const C: usize,
+        const L: usize,
+        const BLW: usize,
+        const MLW: usize,
+    >(
+        &self,
+        road: &Road<B, C, L, BLW, MLW>,
+        occupation: &RectangleOccupier,
+    ) -> Option<usize> {
+        let front_gap = road.front_gap(occupation)?;
+        return match road.motor_lane_contains_occupier(occupation) {
+            true => Some(front_gap.saturating_sub(self.bike_lane_bias)),
+            false => Some(front_gap),
+        };
     }
 
+    /// Orders candidate positions so that the more preferable one is greater.
     fn y_star_cmp_priority<
         const B: usize,
         const C: usize,
@@ -115,11 +277,15 @@ impl Bike {
         const BLW: usize,
         const MLW: usize,
     >(
+        &self,
         road: &Road<B, C, L, BLW, MLW>,
         lhs: &RectangleOccupier,
         rhs: &RectangleOccupier,
     ) -> Ordering {
-        match road.front_gap(lhs).cmp(&road.front_gap(rhs)) {
+        match self
+            .biased_front_gap(road, lhs)
+            .cmp(&self.biased_front_gap(road, rhs))
+        {
             Ordering::Less => Ordering::Less,
             Ordering::Equal => match (
                 road.motor_lane_contains_occupier(lhs),
@@ -172,7 +338,7 @@ impl Bike {
         self_id: usize,
     ) -> Vec<RectangleOccupier> {
         return y_prime_prime_j_t_plus_1(
-            &road,
+            road,
             self.rectangle_occupation(),
             self.y_prime_j_t_plus_1(road, &self_id),
         )
@@ -192,17 +358,18 @@ impl Bike {
         self_id: usize,
     ) -> Vec<RectangleOccupier> {
         let mut y_prime_prime = self.y_prime_prime_j_t_plus_1(road, self_id);
-        y_prime_prime.sort_by(|lhs, rhs| Bike::y_star_cmp_priority(road, lhs, rhs));
+        // highest priority first
+        y_prime_prime.sort_by(|lhs, rhs| self.y_star_cmp_priority(road, rhs, lhs));
         let best_choice_example = match y_prime_prime.first() {
-            Some(choice) => choice,
+            Some(choice) => *choice,
             None => return Vec::new(), // nothing to choose y_stars from so just return nothing
-        }
-        .clone();
+        };
         let best_choices = y_prime_prime
             .into_iter()
             // keep the ones that have priority equal with the first element
             .take_while(|choice| {
-                Bike::y_star_cmp_priority(road, &best_choice_example, choice).is_eq()
+                self.y_star_cmp_priority(road, &best_choice_example, choice)
+                    .is_eq()
             });
         return best_choices.collect();
     }
@@ -217,16 +384,28 @@ impl Bike {
         &self,
         road: &Road<B, C, L, BLW, MLW>,
         self_id: usize,
+        source: &mut impl RandSource,
+    ) -> RectangleOccupier {
+        // the strategy only breaks ties between the highest priority options,
+        // so `Rightmost` can't pick a lower priority option further right
+        return self.choose_y_star(self.generate_y_stars(road, self_id), source);
+    }
+
+    fn choose_y_star(
+        &self,
+        y_prime_prime: Vec<RectangleOccupier>,
+        source: &mut impl RandSource,
     ) -> RectangleOccupier {
-        let y_prime_prime = self.y_prime_prime_j_t_plus_1(road, self_id);
         return match self.y_star_selection_strategy {
             YStarSelectionStrategy::Rightmost => rightmost_y_star_selector(y_prime_prime),
-            YStarSelectionStrategy::UniformRandom => uniform_y_star_selector(y_prime_prime),
+            YStarSelectionStrategy::UniformRandom => uniform_y_star_selector(y_prime_prime, source),
         }
         // staying still is valid if nothing else is found to be
         .unwrap_or(self.occupation);
     }
 
+    /// The bike after moving forward, with `decelerate` deciding whether the
+    /// random deceleration (see `should_decelerate`) is applied.
     pub fn forward_update<
         const B: usize,
         const C: usize,
@@ -236,26 +415,33 @@ impl Bike {
     >(
         &self,
         road: &Road<B, C, L, BLW, MLW>,
+        decelerate: bool,
     ) -> Self {
-        let next_speed = [
-            // try and accelerate
-            self.forward_speed + self.forward_acceleration,
-            // unless that is too fast
-            self.forward_speed_max,
-            // unless you'd crash by going that fast
-            road.front_gap(&self.rectangle_occupation())
-                .expect("bike should have width")
-                .try_into()
-                .expect("shouldn't be too large"),
-        ]
-        .into_iter()
-        .min()
-        .expect("iterator should have 3 values");
-
-        let next_speed = match self.should_decelerate() {
+        let speed_max = self.effective_speed_max(road);
+        let front_gap: isize = road
+            .front_gap(&self.rectangle_occupation())
+            .expect("bike should have width")
+            .try_into()
+            .expect("shouldn't be too large");
+        // try and accelerate, unless that is too fast or you'd crash by going
+        // that fast
+        let next_speed = (self.forward_speed
+            + self.speed_profile.acceleration_at(self.forward_speed))
+        .clamp(0, speed_max)
+        .min(front_gap);
+
+        let next_speed = match decelerate {
             false => next_speed,
             true => max(next_speed - 1, 0),
         };
+        assert!(
+            (0..=min(speed_max, front_gap)).contains(&next_speed),
+            "bike went from {} to {}, outside of 0 to its max speed {} and front gap {}",
+            self.forward_speed,
+            next_speed,
+            speed_max,
+            front_gap
+        );
 
         let next_occupation = RectangleOccupier {
             front: (self.occupation.front + next_speed).rem_euclid(L as isize),
@@ -280,8 +466,10 @@ fn rightmost_y_star_selector(
 
 fn uniform_y_star_selector(
     options: impl IntoIterator<Item = RectangleOccupier>,
+    source: &mut impl RandSource,
 ) -> Option<RectangleOccupier> {
-    return options.into_iter().choose(&mut rand::thread_rng());
+    let options: Vec<RectangleOccupier> = options.into_iter().collect();
+    return source.pick(options.len()).map(|index| options[index]);
     // let selected_index = (0..options.len())
     //     .choose(&mut rand::thread_rng())?
     // return options
@@ -334,7 +522,7 @@ fn determine_y_prime_prime_j_t_plus_1_filter<
     road: &Road<B, C, L, BLW, MLW>,
     current_occupation: RectangleOccupier,
 ) -> YPrimePrimeFilter {
-    return match road.motor_lane_contains_occupier(&current_occupation) {
+    return match is_on_motor_lane(road, &current_occupation) {
         true => match road.is_blocking(&current_occupation.back_left(), None) {
             true => YPrimePrimeFilter::MotorLaneBlocking,
             false => YPrimePrimeFilter::MotorLaneNonBlocking,
@@ -343,6 +531,25 @@ fn determine_y_prime_prime_j_t_plus_1_filter<
     };
 }
 
+/// Whether a bike occupying `occupation` is on the motor lane. A bike wider
+/// than the bike lane has to overhang the motor lane, so it is only treated as
+/// being on the motor lane if it is further into it than it has to be, or if
+/// there is no bike lane for it to be in at all.
+fn is_on_motor_lane<
+    const B: usize,
+    const C: usize,
+    const L: usize,
+    const BLW: usize,
+    const MLW: usize,
+>(
+    road: &Road<B, C, L, BLW, MLW>,
+    occupation: &RectangleOccupier,
+) -> bool {
+    let (bike_lane_cells, motor_lane_cells) = road.lane_distribution(occupation);
+    let overhang_cells = occupation.width.saturating_sub(BLW) * occupation.length;
+    return 0 < motor_lane_cells && (bike_lane_cells == 0 || overhang_cells < motor_lane_cells);
+}
+
 fn y_prime_prime_motor_lane_blocking<
     const B: usize,
     const C: usize,
@@ -386,7 +593,7 @@ fn avoid_blocking_ypp_filter<
     yp: impl Iterator<Item = RectangleOccupier> + 'a,
     road: &'a Road<B, C, L, BLW, MLW>,
     boundary: isize,
-) -> impl Iterator<Item = RectangleOccupier> + '_ {
+) -> impl Iterator<Item = RectangleOccupier> + 'a {
     yp.filter(
         move |occupation| match occupation.occupier_is_within(boundary) {
             true => !road.is_blocking(&occupation.back_left(), None),
@@ -431,7 +638,7 @@ impl Default for Bike {
     }
 }
 
-#[derive(Debug, Serialize, Copy, Clone)]
+#[derive(Debug, Serialize, Deserialize, Copy, Clone)]
 pub struct BikeBuilder {
     front: isize,
     right: isize,
@@ -439,18 +646,20 @@ pub struct BikeBuilder {
     width: isize,
     forward_speed_max: isize,
     forward_speed: isize,
-    forward_acceleration: isize,
+    speed_profile: SpeedProfile,
     rightward_speed_max: isize,
-    lateral_ignorance: f64,
-    deceleration_prob: f64,
+    lateral_ignorance: Probability,
+    deceleration_prob: Probability,
     y_star_selection_strategy: YStarSelectionStrategy,
+    bike_lane_bias: isize,
+    group: u8,
 }
 
 impl BikeBuilder {
     pub fn deterministic_default() -> Self {
         Self {
-            lateral_ignorance: 0.0,
-            deceleration_prob: 0.0,
+            lateral_ignorance: Probability::ZERO,
+            deceleration_prob: Probability::ZERO,
             y_star_selection_strategy: YStarSelectionStrategy::Rightmost,
             ..Default::default()
         }
@@ -464,6 +673,39 @@ impl BikeBuilder {
         return Self { right, ..*self };
     }
 
+    /// Puts the bike's right edge on the right edge of the lane, on a road
+    /// with the lane widths `bike_lane_width` (`BLW`) and `motor_lane_width`
+    /// (`MLW`): against the kerb in the bike lane or against the bike lane in
+    /// the motor lane. Errors if the bike is too wide for the lane.
+    pub fn with_lane(
+        &self,
+        bike_lane_width: usize,
+        motor_lane_width: usize,
+        lane: Lane,
+    ) -> Result<Self> {
+        let (lane_width, right) = match lane {
+            Lane::Motor => (motor_lane_width, motor_lane_width),
+            Lane::Bike => (bike_lane_width, motor_lane_width + bike_lane_width),
+        };
+        if lane_width < self.width as usize {
+            return Err(anyhow!(
+                "bike {} lats wide doesn't fit in the {:?} lane {} lats wide",
+                self.width,
+                lane,
+                lane_width
+            ));
+        }
+        return Ok(self.with_right_at(right as isize - 1));
+    }
+
+    pub fn in_bike_lane(&self, bike_lane_width: usize, motor_lane_width: usize) -> Result<Self> {
+        return self.with_lane(bike_lane_width, motor_lane_width, Lane::Bike);
+    }
+
+    pub fn in_motor_lane(&self, bike_lane_width: usize, motor_lane_width: usize) -> Result<Self> {
+        return self.with_lane(bike_lane_width, motor_lane_width, Lane::Motor);
+    }
+
     pub const fn with_front_right_at(&self, front_right: Coord) -> Self {
         let Coord {
             lat: right,
@@ -536,42 +778,47 @@ impl BikeBuilder {
         };
     }
 
+    /// Accelerate by `forward_acceleration` at every speed.
     pub fn with_forward_acceleration(&self, forward_acceleration: isize) -> Result<Self> {
-        return match forward_acceleration < 1 {
-            true => Err(anyhow!(
-                "forward acceleration must be strictly positive, instead {}",
-                forward_acceleration
-            )),
-            false => Ok(Self {
-                forward_acceleration,
-                ..*self
-            }),
-        };
+        return self.with_speed_profile(SpeedProfile::constant(forward_acceleration));
     }
 
-    pub fn with_lateral_ignorance(&self, lateral_ignorance: f64) -> Result<Self> {
-        return match lateral_ignorance <= 0.0 && 1.0 <= lateral_ignorance {
-            true => Err(anyhow!(
-                "lateral ignorance must be between 0 and 1, instead {}",
-                lateral_ignorance
-            )),
-            false => Ok(Self {
-                lateral_ignorance,
-                ..*self
-            }),
+    pub fn with_speed_profile(&self, speed_profile: SpeedProfile) -> Result<Self> {
+        let SpeedProfile {
+            slow_acceleration,
+            fast_acceleration,
+            max_slow_speed,
+        } = speed_profile;
+        if slow_acceleration < 1 || fast_acceleration < 1 {
+            return Err(anyhow!(
+                "forward accelerations must be strictly positive, instead {} and {}",
+                slow_acceleration,
+                fast_acceleration
+            ));
+        }
+        if max_slow_speed.is_negative() {
+            return Err(anyhow!(
+                "cannot have negative max slow speed, instead {}",
+                max_slow_speed
+            ));
+        }
+        return Ok(Self {
+            speed_profile,
+            ..*self
+        });
+    }
+
+    pub const fn with_lateral_ignorance(&self, lateral_ignorance: Probability) -> Self {
+        return Self {
+            lateral_ignorance,
+            ..*self
         };
     }
 
-    pub fn with_deceleration_prob(&self, deceleration_prob: f64) -> Result<Self> {
-        return match deceleration_prob <= 0.0 && 1.0 <= deceleration_prob {
-            true => Err(anyhow!(
-                "deceleration_prob must be between 0 and 1, instead {}",
-                deceleration_prob
-            )),
-            false => Ok(Self {
-                deceleration_prob,
-                ..*self
-            }),
+    pub const fn with_deceleration_prob(&self, deceleration_prob: Probability) -> Self {
+        return Self {
+            deceleration_prob,
+            ..*self
         };
     }
 
@@ -585,9 +832,55 @@ impl BikeBuilder {
         };
     }
 
+    /// Tags the bike as part of a group, such as e-bikes, 0 unless set. The
+    /// group doesn't change how the bike rides, it is only carried through
+    /// to the output.
+    pub const fn with_group(&self, group: u8) -> Self {
+        return Self { group, ..*self };
+    }
+
+    /// Number of cells of front gap a position in the motor lane must beat a
+    /// bike lane position by before the bike will prefer it.
+    pub fn with_bike_lane_bias(&self, bike_lane_bias: isize) -> Result<Self> {
+        return match bike_lane_bias.is_negative() {
+            true => Err(anyhow!(
+                "cannot have negative bike lane bias, instead {}",
+                bike_lane_bias
+            )),
+            false => Ok(Self {
+                bike_lane_bias,
+                ..*self
+            }),
+        };
+    }
+
     pub fn build(&self) -> Result<Bike> {
         return self.try_into();
     }
+
+    /// Builds the bike without checking its speed against its max speed, for
+    /// rebuilding a bike that a downhill grade took past its max speed.
+    pub(crate) fn build_coasting(&self) -> Result<Bike> {
+        return Ok(Bike {
+            occupation: RectangleOccupier {
+                front: self.front,
+                right: self.right,
+                length: self.length.try_into()?,
+                width: self.width.try_into()?,
+            },
+            forward_speed_max: self.forward_speed_max,
+            forward_speed: self.forward_speed,
+            speed_profile: self.speed_profile,
+            rightward_speed_max: self.rightward_speed_max,
+            ignore_lateral_distribution: self.lateral_ignorance.bernoulli(),
+            lateral_ignorance: self.lateral_ignorance,
+            decelerate_distribution: self.deceleration_prob.bernoulli(),
+            deceleration_prob: self.deceleration_prob,
+            y_star_selection_strategy: self.y_star_selection_strategy,
+            bike_lane_bias: self.bike_lane_bias.try_into()?,
+            group: self.group,
+        });
+    }
 }
 
 impl Default for BikeBuilder {
@@ -599,11 +892,13 @@ impl Default for BikeBuilder {
             width: 2,
             forward_speed_max: 6,
             forward_speed: 0,
-            forward_acceleration: 1,
+            speed_profile: SpeedProfile::constant(1),
             rightward_speed_max: 2,
-            lateral_ignorance: 0.2,
-            deceleration_prob: 0.2,
+            lateral_ignorance: Probability::new(0.2).expect("0.2 is a probability"),
+            deceleration_prob: Probability::new(0.2).expect("0.2 is a probability"),
             y_star_selection_strategy: YStarSelectionStrategy::UniformRandom,
+            bike_lane_bias: 0,
+            group: 0,
         }
     }
 }
@@ -612,28 +907,14 @@ impl TryInto<Bike> for &BikeBuilder {
     type Error = anyhow::Error;
 
     fn try_into(self) -> Result<Bike> {
-        return match self.forward_speed_max < self.forward_speed {
-            true => Err(anyhow!(
+        if self.forward_speed_max < self.forward_speed {
+            return Err(anyhow!(
                 "forward speed ({}) cannot be greater than max ({})",
-                self.forward_speed_max,
-                self.forward_speed
-            )),
-            false => Ok(Bike {
-                occupation: RectangleOccupier {
-                    front: self.front,
-                    right: self.right,
-                    length: self.length.try_into()?,
-                    width: self.width.try_into()?,
-                },
-                forward_speed_max: self.forward_speed_max,
-                forward_speed: self.forward_speed,
-                forward_acceleration: self.forward_acceleration,
-                rightward_speed_max: self.rightward_speed_max,
-                ignore_lateral_distribution: Bernoulli::new(self.lateral_ignorance)?,
-                decelerate_distribution: Bernoulli::new(self.deceleration_prob)?,
-                y_star_selection_strategy: self.y_star_selection_strategy,
-            }),
-        };
+                self.forward_speed,
+                self.forward_speed_max
+            ));
+        }
+        return self.build_coasting();
     }
 }
 
@@ -645,16 +926,122 @@ impl TryInto<Bike> for BikeBuilder {
     }
 }
 
+/// A builder that builds the bike as it currently is.
+impl From<&Bike> for BikeBuilder {
+    fn from(value: &Bike) -> Self {
+        return Self {
+            front: value.occupation.front,
+            right: value.occupation.right,
+            length: value.occupation.length as isize,
+            width: value.occupation.width as isize,
+            forward_speed_max: value.forward_speed_max,
+            forward_speed: value.forward_speed,
+            speed_profile: value.speed_profile,
+            rightward_speed_max: value.rightward_speed_max,
+            lateral_ignorance: value.lateral_ignorance,
+            deceleration_prob: value.deceleration_prob,
+            y_star_selection_strategy: value.y_star_selection_strategy,
+            bike_lane_bias: value.bike_lane_bias as isize,
+            group: value.group,
+        };
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
+    use rand::thread_rng;
+
     use crate::{
         bike::{
             determine_y_prime_prime_j_t_plus_1_filter, y_prime_prime_j_t_plus_1, Bike, BikeBuilder,
-            YPrimePrimeFilter, YStarSelectionStrategy,
+            SpeedProfile, YPrimePrimeFilter, YStarSelectionStrategy,
         },
-        road::{RectangleOccupier, Road, Vehicle},
+        car::CarBuilder,
+        probability::Probability,
+        road::{Conditions, Coord, Grade, Lane, RectangleOccupier, Road, SpeedZone, Vehicle},
     };
+    use proptest::{prop_assert, proptest};
+
+    proptest! {
+        #[test]
+        fn bike_speed_stays_within_effective_max_proptest(
+            seed: u64,
+            forward_speed_max in 0..=50isize,
+            acceleration in 1..=1_000isize,
+            deceleration_prob in 0.0..=1.0f64,
+            delta_speed_max in -1_000..=1_000isize,
+            zone_max_speed in 1..=1_000isize,
+            lane_speed_limit in 1..=1_000isize,
+            speed_factor in 0.01..=100.0f32,
+        ) {
+            let bikes = [10, 60].map(|front| {
+                BikeBuilder::default()
+                    .with_front_at(front)
+                    .with_forward_max_speed(forward_speed_max)
+                    .unwrap()
+                    .with_forward_speed(forward_speed_max)
+                    .unwrap()
+                    .with_forward_acceleration(acceleration)
+                    .unwrap()
+                    .with_deceleration_prob(Probability::new(deceleration_prob).unwrap())
+                    .build()
+                    .unwrap()
+            });
+            let mut road = Road::<2, 0, 100, 4, 4>::new(bikes, []).unwrap();
+            road.reseed(seed);
+            road.add_grade(Grade {
+                long_range: 0..=49,
+                delta_speed_max,
+            })
+            .unwrap();
+            road.add_speed_zone(SpeedZone {
+                long_range: 30..=79,
+                max_speed: zone_max_speed,
+            })
+            .unwrap();
+            // the same in both lanes so moving laterally doesn't change it
+            road.set_bike_lane_speed_limit(Some(lane_speed_limit)).unwrap();
+            road.set_motor_lane_speed_limit(Some(lane_speed_limit)).unwrap();
+            road.set_conditions(Conditions {
+                speed_factor,
+                extra_decel_prob: Probability::ZERO,
+            })
+            .unwrap();
+
+            for _ in 0..10 {
+                let speed_maxes: Vec<isize> = road
+                    .bikes()
+                    .iter()
+                    .map(|bike| bike.effective_speed_max(&road))
+                    .collect();
+                road.update().unwrap();
+                for (bike, speed_max) in road.bikes().iter().zip(speed_maxes) {
+                    prop_assert!((0..=speed_max).contains(&bike.forward_speed));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn with_lane_puts_bike_on_lane_edge() {
+        let builder = BikeBuilder::default();
+        let in_bike_lane = builder.with_lane(7, 7, Lane::Bike).unwrap();
+        let in_motor_lane = builder.in_motor_lane(7, 7).unwrap();
+        assert_eq!(in_bike_lane.right, 13);
+        assert_eq!(in_motor_lane.right, 6);
+        assert_eq!(builder.in_bike_lane(7, 7).unwrap().right, 13);
+
+        let bikes =
+            [in_bike_lane, in_motor_lane.with_front_at(10)].map(|bike| bike.build().unwrap());
+        let road = Road::<2, 0, 20, 7, 7>::new(bikes, []).unwrap();
+        assert!(road.road_contains_occupier(road.get_bike(0)));
+        assert!(road.road_contains_occupier(road.get_bike(1)));
+        assert!(road.motor_lane_contains_occupier(road.get_bike(1)));
+        assert!(!road.motor_lane_contains_occupier(road.get_bike(0)));
+
+        assert!(builder.with_lane(1, 7, Lane::Bike).is_err());
+    }
 
     #[test]
     fn bike_can_move_laterally() {
@@ -665,9 +1052,9 @@ mod tests {
             width: 2,
             forward_speed_max: 5,
             forward_speed: 0,
-            forward_acceleration: 1,
+            speed_profile: SpeedProfile::constant(1),
             rightward_speed_max: 2,
-            lateral_ignorance: 0.0,
+            lateral_ignorance: Probability::ZERO,
             ..Default::default()
         }
         .build()
@@ -687,9 +1074,9 @@ mod tests {
             width: 2,
             forward_speed_max: 5,
             forward_speed: 0,
-            forward_acceleration: 1,
+            speed_profile: SpeedProfile::constant(1),
             rightward_speed_max: 2,
-            lateral_ignorance: 0.0,
+            lateral_ignorance: Probability::ZERO,
             ..Default::default()
         }
         .build()
@@ -714,9 +1101,9 @@ mod tests {
             width: 2,
             forward_speed_max: 5,
             forward_speed: 0,
-            forward_acceleration: 1,
+            speed_profile: SpeedProfile::constant(1),
             rightward_speed_max: 2,
-            lateral_ignorance: 0.0,
+            lateral_ignorance: Probability::ZERO,
             ..Default::default()
         }
         .build()
@@ -739,9 +1126,9 @@ mod tests {
             width: 2,
             forward_speed_max: 5,
             forward_speed: 0,
-            forward_acceleration: 1,
+            speed_profile: SpeedProfile::constant(1),
             rightward_speed_max: 2,
-            lateral_ignorance: 0.0,
+            lateral_ignorance: Probability::ZERO,
             ..Default::default()
         }
         .build()
@@ -752,10 +1139,38 @@ mod tests {
     }
 
     #[test]
-    fn bike_is_ml_non_blocking_empty_road_no_bl() {
+    fn bike_wider_than_bike_lane_overhanging_is_on_bike_lane() {
         let bikes = [BikeBuilder::default()
-            .with_lateral_ignorance(0.0)
+            .with_dimensions((3, 2))
             .unwrap()
+            .with_right_at(5)
+            .build()
+            .unwrap()];
+        let road = Road::<1, 0, 20, 2, 4>::new(bikes, []).unwrap();
+        let occupation = road.get_bike(0).rectangle_occupation();
+
+        assert_eq!(road.lane_distribution(&occupation), (4, 2));
+        assert!(road.motor_lane_contains_occupier(&occupation));
+        assert_eq!(
+            determine_y_prime_prime_j_t_plus_1_filter(&road, occupation),
+            YPrimePrimeFilter::BikeLane
+        );
+
+        let further_left = RectangleOccupier {
+            right: 4,
+            ..occupation
+        };
+        assert_eq!(road.lane_distribution(&further_left), (2, 4));
+        assert_ne!(
+            determine_y_prime_prime_j_t_plus_1_filter(&road, further_left),
+            YPrimePrimeFilter::BikeLane
+        );
+    }
+
+    #[test]
+    fn bike_is_ml_non_blocking_empty_road_no_bl() {
+        let bikes = [BikeBuilder::default()
+            .with_lateral_ignorance(Probability::ZERO)
             .build()
             .unwrap()];
         let road = Road::<1, 0, 20, 0, 6>::new(bikes, []).unwrap();
@@ -775,10 +1190,10 @@ mod tests {
             width: 2,
             forward_speed_max: 5,
             forward_speed: 0,
-            forward_acceleration: 1,
+            speed_profile: SpeedProfile::constant(1),
             // high enough to move anywhere on the road
             rightward_speed_max: 20,
-            lateral_ignorance: 0.0,
+            lateral_ignorance: Probability::ZERO,
             ..Default::default()
         }
         .build()
@@ -803,10 +1218,10 @@ mod tests {
             width: 2,
             forward_speed_max: 5,
             forward_speed: 0,
-            forward_acceleration: 1,
+            speed_profile: SpeedProfile::constant(1),
             // high enough to move anywhere on the road
             rightward_speed_max: 20,
-            lateral_ignorance: 0.0,
+            lateral_ignorance: Probability::ZERO,
             y_star_selection_strategy: YStarSelectionStrategy::Rightmost,
             ..Default::default()
         }
@@ -814,52 +1229,60 @@ mod tests {
         .unwrap()];
         let road = Road::<1, 0, 20, 10, 10>::new(bikes, []).unwrap();
         let bike = road.get_bike(0);
-        let y_star_right = bike.select_y_star(&road, 0).right;
+        let y_star_right = bike.select_y_star(&road, 0, &mut thread_rng()).right;
         assert_eq!(y_star_right, road.self_total_width() - 1);
     }
 
     #[test]
     fn zero_ignorance_never_ignores() {
         let bike = BikeBuilder::default()
-            .with_lateral_ignorance(0.0)
-            .unwrap()
+            .with_lateral_ignorance(Probability::ZERO)
             .build()
             .unwrap();
 
-        assert!(!bike.should_ignore_lateral_movement())
+        assert!(!bike.should_ignore_lateral_movement(&mut thread_rng()))
     }
 
     #[test]
     fn one_ignorance_always_ignores() {
         let bike = BikeBuilder::default()
-            .with_lateral_ignorance(1.0)
-            .unwrap()
+            .with_lateral_ignorance(Probability::ONE)
             .build()
             .unwrap();
 
-        assert!(bike.should_ignore_lateral_movement())
+        assert!(bike.should_ignore_lateral_movement(&mut thread_rng()))
     }
 
     #[test]
     fn zero_deceleration_prob_never_decelerates() {
         let bike = BikeBuilder::default()
-            .with_deceleration_prob(0.0)
-            .unwrap()
+            .with_deceleration_prob(Probability::ZERO)
             .build()
             .unwrap();
 
-        assert!(!bike.should_decelerate())
+        assert!(!bike.should_decelerate(&mut thread_rng()))
     }
 
     #[test]
     fn one_deceleration_prob_always_decelerates() {
         let bike = BikeBuilder::default()
-            .with_deceleration_prob(1.0)
-            .unwrap()
+            .with_deceleration_prob(Probability::ONE)
+            .build()
+            .unwrap();
+
+        assert!(bike.should_decelerate(&mut thread_rng()))
+    }
+
+    #[test]
+    fn deceleration_prob_can_change_mid_run() {
+        let mut bike = BikeBuilder::default()
+            .with_deceleration_prob(Probability::ONE)
             .build()
             .unwrap();
+        assert!(bike.should_decelerate(&mut thread_rng()));
 
-        assert!(bike.should_decelerate())
+        bike.set_deceleration_prob(Probability::ZERO);
+        assert!(!bike.should_decelerate(&mut thread_rng()));
     }
 
     #[test]
@@ -871,9 +1294,9 @@ mod tests {
             width: 2,
             forward_speed_max: 5,
             forward_speed: 0,
-            forward_acceleration: 1,
+            speed_profile: SpeedProfile::constant(1),
             rightward_speed_max: 5,
-            lateral_ignorance: 0.0,
+            lateral_ignorance: Probability::ZERO,
             ..Default::default()
         }
         .build()
@@ -892,9 +1315,9 @@ mod tests {
             width: 2,
             forward_speed_max: 5,
             forward_speed: 0,
-            forward_acceleration: 1,
+            speed_profile: SpeedProfile::constant(1),
             rightward_speed_max: 5,
-            lateral_ignorance: 0.0,
+            lateral_ignorance: Probability::ZERO,
             ..Default::default()
         }
         .build()
@@ -922,9 +1345,9 @@ mod tests {
             width: 2,
             forward_speed_max: 5,
             forward_speed: 0,
-            forward_acceleration: 1,
+            speed_profile: SpeedProfile::constant(1),
             rightward_speed_max: 5,
-            lateral_ignorance: 0.0,
+            lateral_ignorance: Probability::ZERO,
             ..Default::default()
         }
         .build()
@@ -956,9 +1379,9 @@ mod tests {
             width: 2,
             forward_speed_max: 5,
             forward_speed: 0,
-            forward_acceleration: 1,
+            speed_profile: SpeedProfile::constant(1),
             rightward_speed_max: 5,
-            lateral_ignorance: 0.0,
+            lateral_ignorance: Probability::ZERO,
             ..Default::default()
         }
         .build()
@@ -971,4 +1394,183 @@ mod tests {
 
         assert_eq!(y_prime_prime_type, YPrimePrimeFilter::MotorLaneNonBlocking);
     }
+
+    fn bike_lane_bias_road(bike_lane_bias: isize) -> Road<4, 0, 30, 4, 4> {
+        /*
+        lat: 0 1 2 3|4 5 6 7
+        12       3 3
+        11       3 3
+        10           2 2 1 1
+         9           2 2 1 1
+         5           0 0
+         4           0 0
+        the bike lane ahead of bike 0 is 3 cells clear and the motor lane
+        at right = 3 is 5 cells clear
+        */
+        let bikes = [
+            BikeBuilder::deterministic_default()
+                .with_bike_lane_bias(bike_lane_bias)
+                .unwrap()
+                .with_front_right_at(Coord { lat: 5, long: 5 }),
+            BikeBuilder::deterministic_default().with_front_right_at(Coord { lat: 7, long: 10 }),
+            BikeBuilder::deterministic_default().with_front_right_at(Coord { lat: 5, long: 10 }),
+            BikeBuilder::deterministic_default().with_front_right_at(Coord { lat: 3, long: 12 }),
+        ]
+        .map(|builder| builder.build().unwrap());
+        return Road::new(bikes, []).unwrap();
+    }
+
+    #[test]
+    fn unbiased_bike_prefers_bigger_motor_lane_gap() {
+        let road = bike_lane_bias_road(0);
+        let bike = road.get_bike(0);
+
+        let y_stars: Vec<isize> = bike
+            .generate_y_stars(&road, 0)
+            .iter()
+            .map(|occupation| occupation.right)
+            .collect();
+
+        assert_eq!(y_stars, vec![3]);
+    }
+
+    #[test]
+    fn rightmost_strategy_only_breaks_ties_between_y_stars() {
+        let road = bike_lane_bias_road(0);
+        let bike = road.get_bike(0);
+
+        let rightmost_option = bike
+            .y_prime_prime_j_t_plus_1(&road, 0)
+            .iter()
+            .map(|occupation| occupation.right)
+            .max();
+        let y_star = bike.select_y_star(&road, 0, &mut thread_rng());
+
+        // picking the rightmost of all the options would have left the motor
+        // lane for the shorter gap in the bike lane
+        assert!(rightmost_option.is_some_and(|right| 3 < right));
+        assert_eq!(y_star.right, 3);
+    }
+
+    #[test]
+    fn biased_bike_stays_in_bike_lane() {
+        let road = bike_lane_bias_road(3);
+        let bike = road.get_bike(0);
+
+        let y_stars = bike.generate_y_stars(&road, 0);
+
+        assert!(!y_stars.is_empty());
+        assert!(y_stars
+            .iter()
+            .all(|occupation| !road.motor_lane_contains_occupier(occupation)));
+    }
+
+    #[test]
+    fn y_stars_are_the_highest_priority_options() {
+        let road = bike_lane_bias_road(0);
+        let bike = road.get_bike(0);
+
+        let options = bike.y_prime_prime_j_t_plus_1(&road, 0);
+        let y_stars = bike.generate_y_stars(&road, 0);
+
+        assert!(!y_stars.is_empty());
+        assert!(y_stars.len() < options.len());
+        for y_star in &y_stars {
+            for option in &options {
+                assert!(bike.y_star_cmp_priority(&road, y_star, option).is_ge());
+            }
+        }
+    }
+
+    #[test]
+    fn bike_faster_than_its_max_speed_fails_to_build() {
+        let builder = BikeBuilder::default().with_forward_max_speed(6).unwrap();
+        assert!(builder.with_forward_speed(7).unwrap().build().is_err());
+        assert!(builder.with_forward_speed(6).unwrap().build().is_ok());
+    }
+
+    #[test]
+    fn negative_bike_lane_bias_is_rejected() {
+        assert!(BikeBuilder::default().with_bike_lane_bias(-1).is_err());
+        // a saved builder doesn't go through with_bike_lane_bias
+        let mut saved = serde_json::to_value(BikeBuilder::default()).unwrap();
+        saved["bike_lane_bias"] = (-1).into();
+        let loaded: BikeBuilder = serde_json::from_value(saved).unwrap();
+        assert!(loaded.build().is_err());
+    }
+
+    #[test]
+    fn bike_accelerates_faster_when_slow() {
+        let profile = SpeedProfile {
+            slow_acceleration: 2,
+            fast_acceleration: 1,
+            max_slow_speed: 3,
+        };
+        let bikes = [(0, 10), (2, 20)].map(|(speed, front)| {
+            BikeBuilder::deterministic_default()
+                .with_front_at(front)
+                .with_forward_max_speed(10)
+                .unwrap()
+                .with_forward_speed(speed)
+                .unwrap()
+                .with_speed_profile(profile)
+                .unwrap()
+                .build()
+                .unwrap()
+        });
+        let mut road = Road::<2, 0, 40, 2, 2>::new(bikes, []).unwrap();
+
+        road.bikes_forward_update().unwrap();
+        assert_eq!(road.get_bike(0).forward_speed, 2);
+        assert_eq!(road.get_bike(1).forward_speed, 4);
+        road.bikes_forward_update().unwrap();
+        // both now above the slow speeds
+        assert_eq!(road.get_bike(0).forward_speed, 4);
+        assert_eq!(road.get_bike(1).forward_speed, 5);
+        assert!(BikeBuilder::default()
+            .with_speed_profile(SpeedProfile {
+                slow_acceleration: 0,
+                ..profile
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn bike_keeps_clear_of_widening_car() {
+        // at speed 5 the car covers lats 0 to 5, widening to lat 6 at the 7 it
+        // could reach next update
+        let cars = [CarBuilder::default()
+            .with_front_at(10)
+            .with_speed(5)
+            .build()
+            .unwrap()];
+        let bikes = [BikeBuilder::deterministic_default()
+            .with_front_right_at(Coord { long: 9, lat: 9 })
+            .build()
+            .unwrap()];
+        let road = Road::<1, 1, 30, 2, 14>::new(bikes, cars).unwrap();
+        let beside_car = RectangleOccupier {
+            right: 7,
+            ..road.get_bike(0).rectangle_occupation()
+        };
+        assert!(!road.is_collision_for(&beside_car, Vehicle::Bike(0)));
+        assert!(road.is_in_widening_car_path(&beside_car));
+        // as far ahead as the car could get to, but no further
+        let ahead_of_car = |front| RectangleOccupier {
+            front,
+            right: 6,
+            width: 1,
+            length: 1,
+        };
+        assert!(road.is_in_widening_car_path(&ahead_of_car(17)));
+        assert!(!road.is_in_widening_car_path(&ahead_of_car(18)));
+
+        let rights: Vec<isize> = road
+            .get_bike(0)
+            .y_prime_j_t_plus_1(&road, &0)
+            .map(|occupation| occupation.right)
+            .collect();
+
+        assert_eq!(rights, vec![8, 9, 10, 11]);
+    }
 }
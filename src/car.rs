@@ -1,30 +1,75 @@
-use crate::road::{rectangle_occupation, Road, Vehicle};
+use crate::road::{RectangleOccupier, Road, Vehicle};
 use std::cmp::{max, min};
 
 use anyhow::{anyhow, Result};
-use rand::{distributions::Bernoulli, prelude::Distribution};
-use serde::Serialize;
+use rand::distributions::Bernoulli;
+use serde::{Deserialize, Serialize};
 
-use crate::road::{Coord, RoadOccupier};
+use crate::{
+    probability::Probability,
+    rand_source::RandSource,
+    road::{Coord, RoadOccupier},
+};
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum LaneChangePolicy {
+    /// Changes either way need the same incentive.
+    #[default]
+    Symmetric,
+    /// Overtake on the left and move back right whenever it costs nothing.
+    KeepRight,
+}
+
+/// Which way along the road a car travels.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Direction {
+    /// Towards increasing long, like every bike.
+    #[default]
+    Forward,
+    /// Towards decreasing long, as oncoming traffic.
+    Backward,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum LaneChangeDirection {
+    Left,
+    Right,
+}
 
 #[derive(Copy, Clone, Debug)]
 pub struct Car {
-    front: isize,
-    pub length: usize,
+    // as wide as the car's speed makes it, widening to the right of its left
+    // edge, so it has to be replaced whenever the speed changes
+    occupation: RectangleOccupier,
     const_width: f32,
-    pub speed: isize,
+    speed: isize,
     fast_acceleration: isize,
     slow_acceleration: isize,
     max_slow_speed: isize,
     speed_max: isize,
     alpha: f32,
     deceleration_distribution: Bernoulli,
+    // kept as the distribution doesn't expose it, for turning back into a
+    // builder
+    deceleration_prob: Probability,
+    skip_acceleration_distribution: Bernoulli,
+    acceleration_prob: Probability,
+    politeness: f32,
+    lane_change_threshold: f32,
+    lane_change_policy: LaneChangePolicy,
+    brake_light: bool,
+    // buses stop for this many updates at each of the road's bus stops
+    bus_dwell: Option<usize>,
+    dwell_remaining: usize,
+    direction: Direction,
+    group: u8,
+    reaction_delay: usize,
+    min_gap: usize,
 }
 
 impl RoadOccupier for Car {
     fn occupied_cells(&self) -> impl Iterator<Item = Coord> {
-        let width = self.lateral_occupancy();
-        return rectangle_occupation(self.front, (width as isize) - 1, width, self.length);
+        return self.occupation.occupied_cells();
     }
 }
 
@@ -34,16 +79,257 @@ impl Car {
     //     return (self.const_width + additional_width).ceil() as usize;
     // }
 
-    pub fn next_iteration_potential_speed(&self) -> isize {
+    pub fn next_iteration_potential_speed<
+        const B: usize,
+        const C: usize,
+        const L: usize,
+        const BLW: usize,
+        const MLW: usize,
+    >(
+        &self,
+        road: &Road<B, C, L, BLW, MLW>,
+    ) -> isize {
         let acceleration = match self.speed <= self.max_slow_speed {
             true => self.slow_acceleration,
             false => self.fast_acceleration,
         };
-        return min(self.speed + acceleration, self.speed_max as isize);
+        return min(self.speed + acceleration, self.effective_speed_max(road));
+    }
+
+    /// The car's max speed under the road's conditions and on the grade its
+    /// front is currently on, reduced to the limit of any speed zone its
+    /// front is in and of the lanes it is in.
+    pub fn effective_speed_max<
+        const B: usize,
+        const C: usize,
+        const L: usize,
+        const BLW: usize,
+        const MLW: usize,
+    >(
+        &self,
+        road: &Road<B, C, L, BLW, MLW>,
+    ) -> isize {
+        return [
+            road.speed_limit_at(self.front()),
+            road.lane_speed_limit_for(&self.occupation),
+        ]
+        .into_iter()
+        .flatten()
+        .fold(road.graded_speed_max(self.speed_max, self.front()), min);
     }
 
     pub const fn front(&self) -> isize {
-        return self.front;
+        // a backward car's front is its lowest long
+        return match self.direction {
+            Direction::Forward => self.occupation.front,
+            Direction::Backward => self.occupation.back(),
+        };
+    }
+
+    pub const fn length(&self) -> usize {
+        return self.occupation.length;
+    }
+
+    pub const fn speed(&self) -> isize {
+        return self.speed;
+    }
+
+    pub const fn speed_max(&self) -> isize {
+        return self.speed_max;
+    }
+
+    pub const fn direction(&self) -> Direction {
+        return self.direction;
+    }
+
+    /// The tag the car was built with, for telling cohorts of cars apart.
+    pub const fn group(&self) -> u8 {
+        return self.group;
+    }
+
+    pub const fn reaction_delay(&self) -> usize {
+        return self.reaction_delay;
+    }
+
+    pub const fn min_gap(&self) -> usize {
+        return self.min_gap;
+    }
+
+    pub const fn left(&self) -> isize {
+        return self.occupation.left();
+    }
+
+    /// The car with its front on `front` going at `speed`, and as wide as
+    /// that speed makes it.
+    fn placed(&self, front: isize, speed: isize) -> Self {
+        return Self {
+            occupation: occupation_of(
+                front,
+                self.left(),
+                self.lateral_occupancy_at_speed(speed),
+                self.length(),
+                self.direction,
+            ),
+            speed,
+            ..*self
+        };
+    }
+
+    /// The long `distance` cells ahead of the car's front in its direction of
+    /// travel.
+    pub(crate) const fn long_ahead(&self, distance: isize) -> isize {
+        return match self.direction {
+            Direction::Forward => self.front() + distance,
+            Direction::Backward => self.front() - distance,
+        };
+    }
+
+    /// The car moved `distance` cells in its direction of travel, at that
+    /// speed.
+    fn moved(&self, distance: isize) -> Self {
+        return self.placed(self.long_ahead(distance), distance);
+    }
+
+    /// Every cell the car passes through moving `distance` along the road,
+    /// from its current back to its new front, and the min gap it keeps clear
+    /// beyond that, as wide as the wider of its current width and the width
+    /// at that speed.
+    fn swept(&self, distance: isize) -> RectangleOccupier {
+        let moved = self.moved(distance);
+        let reach = distance + self.min_gap as isize;
+        let reached_front = self.long_ahead(reach);
+        return occupation_of(
+            reached_front,
+            self.left(),
+            max(self.width(), moved.width()),
+            self.length() + reach as usize,
+            self.direction,
+        );
+    }
+
+    pub const fn is_bus(&self) -> bool {
+        return self.bus_dwell.is_some();
+    }
+
+    /// Whether the car is a bus held at a stop, not moving this update.
+    pub const fn is_dwelling(&self) -> bool {
+        return 0 < self.dwell_remaining;
+    }
+
+    /// Whether the car slowed down in its last update.
+    pub const fn brake_light(&self) -> bool {
+        return self.brake_light;
+    }
+
+    /// The number of lats the car takes up at its current speed, from its
+    /// left edge.
+    pub const fn width(&self) -> usize {
+        return self.occupation.width;
+    }
+
+    /// The rectangle the car currently takes up, as wide as its speed makes
+    /// it. The rectangle's front is its highest long, which for a backward car
+    /// is its back.
+    pub const fn rectangle_occupation(&self) -> RectangleOccupier {
+        return self.occupation;
+    }
+
+    /// The width the car would take up if it reached its potential speed next
+    /// update, see `next_iteration_potential_speed`.
+    pub fn potential_width<
+        const B: usize,
+        const C: usize,
+        const L: usize,
+        const BLW: usize,
+        const MLW: usize,
+    >(
+        &self,
+        road: &Road<B, C, L, BLW, MLW>,
+    ) -> usize {
+        return self.lateral_occupancy_at_speed(self.next_iteration_potential_speed(road));
+    }
+
+    /// How much faster the car could go next update than it is going now,
+    /// standing in for its acceleration in lane change decisions.
+    pub fn safe_acceleration<
+        const B: usize,
+        const C: usize,
+        const L: usize,
+        const BLW: usize,
+        const MLW: usize,
+    >(
+        &self,
+        road: &Road<B, C, L, BLW, MLW>,
+        self_id: usize,
+    ) -> isize {
+        return self.fastest_safe_speed(road, self_id) - self.speed;
+    }
+
+    /// The MOBIL incentive criterion for a lane change: the change in the
+    /// car's own acceleration, plus the changes in the accelerations of the
+    /// new and old followers weighted by the car's politeness, must exceed its
+    /// lane change threshold. A follower being cut off has a negative change.
+    ///
+    /// The accelerations come from `safe_acceleration` before and after the
+    /// change, see `Road::set_car_lane_width`.
+    pub fn accepts_lane_change(
+        &self,
+        own_gain: isize,
+        new_follower_gain: isize,
+        old_follower_gain: isize,
+    ) -> bool {
+        let followers_gain = (new_follower_gain + old_follower_gain) as f32;
+        return own_gain as f32 + self.politeness * followers_gain > self.lane_change_threshold;
+    }
+
+    /// `accepts_lane_change` for a change in `direction`, under the car's lane
+    /// change policy. Keeping right, a car moves back right as long as the
+    /// criterion isn't negative, rather than needing it to pass the
+    /// threshold.
+    pub fn accepts_lane_change_towards(
+        &self,
+        direction: LaneChangeDirection,
+        own_gain: isize,
+        new_follower_gain: isize,
+        old_follower_gain: isize,
+    ) -> bool {
+        return match (self.lane_change_policy, direction) {
+            (LaneChangePolicy::KeepRight, LaneChangeDirection::Right) => {
+                let followers_gain = (new_follower_gain + old_follower_gain) as f32;
+                own_gain as f32 + self.politeness * followers_gain >= 0.0
+            }
+            _ => self.accepts_lane_change(own_gain, new_follower_gain, old_follower_gain),
+        };
+    }
+
+    /// The same car `d_lat` lats further right, or left for a negative
+    /// `d_lat`.
+    pub(crate) const fn shifted_laterally(&self, d_lat: isize) -> Self {
+        return Self {
+            occupation: RectangleOccupier {
+                right: self.occupation.right + d_lat,
+                ..self.occupation
+            },
+            ..*self
+        };
+    }
+
+    /// The same car `d_long` further along the road.
+    pub(crate) const fn shifted(&self, d_long: isize) -> Self {
+        return Self {
+            occupation: RectangleOccupier {
+                front: self.occupation.front + d_long,
+                ..self.occupation
+            },
+            ..*self
+        };
+    }
+
+    /// Cells travelled before standing still if the car slows down by one
+    /// every update from now on, slowing before it moves each update.
+    pub const fn braking_distance(&self) -> usize {
+        let speed = self.speed.unsigned_abs();
+        return speed * speed.saturating_sub(1) / 2;
     }
 
     pub fn safe_speeds<
@@ -58,17 +344,17 @@ impl Car {
         road: &'a Road<B, C, L, BLW, MLW>,
         self_id: usize,
     ) -> impl Iterator<Item = isize> + 'a {
-        return (0..=self.next_iteration_potential_speed()).filter(move |speed| {
-            let potential_car = Self {
-                front: self.front + speed,
-                speed: *speed,
-                ..*self
-            };
+        return (0..=self.next_iteration_potential_speed(road)).filter(move |speed| {
+            let potential_car = self.moved(*speed);
 
-            !road.is_collision_for(&potential_car, Vehicle::Car(self_id))
+            road.road_contains_occupier(&potential_car)
+                && !road.is_collision_for(&potential_car, Vehicle::Car(self_id))
         });
     }
 
+    /// The car after one update, with `decelerate` deciding whether the random
+    /// deceleration (see `should_decelerate`) is applied and the speed limited
+    /// to `speed_cap` if there is one.
     pub(crate) fn update<
         const B: usize,
         const C: usize,
@@ -79,42 +365,107 @@ impl Car {
         &self,
         road: &Road<B, C, L, BLW, MLW>,
         self_id: usize,
+        decelerate: bool,
+        speed_cap: Option<isize>,
     ) -> Self {
+        if self.is_dwelling() {
+            return Car {
+                brake_light: 0 < self.speed,
+                dwell_remaining: self.dwell_remaining - 1,
+                ..self.placed(self.front(), 0)
+            };
+        }
+
         // this implementation is different from that described in the paper as
         // the paper implementation caused collisions between vehicles.
 
         // ..= as if your max_speed is 1 you'll want to be able to go 1 ahead.
-        debug_assert_ne!(self.next_iteration_potential_speed(), 0);
+        debug_assert_ne!(self.next_iteration_potential_speed(road), 0);
         let mut next_speed = self.fastest_safe_speed(road, self_id);
+        if let Some(speed_cap) = speed_cap {
+            next_speed = min(next_speed, speed_cap);
+        }
+        // buses don't overshoot the next stop, stops only serve forward buses
+        if let Some(stop_distance) = self
+            .bus_dwell
+            .filter(|_| self.direction == Direction::Forward)
+            .and_then(|_| road.distance_to_next_bus_stop(self.front()))
+        {
+            next_speed = min(next_speed, stop_distance as isize);
+        }
 
-        // cannot cause issues with the previous speed being unsafe as
-        next_speed = match self.should_decelerate() {
+        // every speed up to the fastest safe one was checked on the way to it,
+        // so slowing down stays safe as long as it never speeds the car up
+        let undecelerated_speed = next_speed;
+        next_speed = match decelerate {
             true => max(next_speed - 1, 0),
             false => next_speed,
         };
+        debug_assert!(
+            next_speed <= undecelerated_speed,
+            "deceleration sped car {} up from {} to {}",
+            self_id,
+            undecelerated_speed,
+            next_speed
+        );
 
+        let moved = self.moved(next_speed);
+        debug_assert!(
+            road.road_contains_occupier(&moved)
+                && !road.is_collision_for(&moved, Vehicle::Car(self_id)),
+            "car {} would collide at speed {}: {:?}",
+            self_id,
+            next_speed,
+            road.collisions_for(&moved)
+        );
+        let next_front = moved.front().rem_euclid(L as isize);
+        let dwell_remaining = match self.bus_dwell {
+            Some(dwell)
+                if self.direction == Direction::Forward
+                    && 0 < next_speed
+                    && road.is_bus_stop(next_front) =>
+            {
+                dwell
+            }
+            _ => 0,
+        };
         return Car {
-            front: (self.front + next_speed).rem_euclid(L as isize),
-            speed: next_speed,
-            ..*self
+            brake_light: next_speed < self.speed,
+            dwell_remaining,
+            ..moved.placed(next_front, next_speed)
         };
     }
 
-    fn should_decelerate(&self) -> bool {
-        return self
-            .deceleration_distribution
-            .sample(&mut rand::thread_rng());
+    pub fn should_decelerate(&self, source: &mut impl RandSource) -> bool {
+        return source.decide(&self.deceleration_distribution);
     }
 
-    fn lateral_occupancy_at_speed(&self, speed: isize) -> usize {
-        return lateral_occupancy(self.const_width, speed, self.alpha);
+    /// Changes the probability of the random deceleration mid-run.
+    pub fn set_deceleration_prob(&mut self, deceleration_prob: Probability) {
+        self.rebuild_decel(deceleration_prob);
+    }
+
+    fn rebuild_decel(&mut self, p: Probability) {
+        self.deceleration_distribution = p.bernoulli();
+        self.deceleration_prob = p;
+    }
+
+    /// Whether the car keeps its speed this update rather than accelerating,
+    /// with probability `acceleration_prob`. Nothing is drawn from `source`
+    /// when the probability is 0, so such cars update exactly as they did
+    /// before the option existed.
+    pub fn should_skip_acceleration(&self, source: &mut impl RandSource) -> bool {
+        return match self.acceleration_prob == Probability::ZERO {
+            true => false,
+            false => source.decide(&self.skip_acceleration_distribution),
+        };
     }
 
-    fn lateral_occupancy(&self) -> usize {
-        return self.lateral_occupancy_at_speed(self.speed);
+    fn lateral_occupancy_at_speed(&self, speed: isize) -> usize {
+        return lateral_occupancy(self.const_width, speed, self.alpha);
     }
 
-    fn fastest_safe_speed<
+    pub(crate) fn fastest_safe_speed<
         const B: usize,
         const C: usize,
         const L: usize,
@@ -125,14 +476,18 @@ impl Car {
         road: &Road<B, C, L, BLW, MLW>,
         self_id: usize,
     ) -> isize {
-        (1..=self.next_iteration_potential_speed())
+        (1..=self.next_iteration_potential_speed(road))
             .take_while(|speed| {
-                let potential_car = Self {
-                    front: self.front + speed,
-                    speed: *speed,
-                    ..*self
-                };
-                !road.is_collision_for(&potential_car, Vehicle::Car(self_id))
+                // where the car ends up alone misses anything it would pass
+                // at a width that no slower speed reached
+                let potential_car = self.moved(*speed);
+                let swept = self.swept(*speed);
+                // nor does the collision check see the car's own back, which
+                // is ahead of it on a road barely longer than it is
+                road.road_contains_occupier(&swept)
+                    && !road.is_collision_for(&swept, Vehicle::Car(self_id))
+                    && !road.could_stop_in_keep_clear(&potential_car, self_id)
+                    && speed.unsigned_abs() + self.length() <= L
             })
             .last()
             .unwrap_or(0)
@@ -144,7 +499,26 @@ fn lateral_occupancy(const_width: f32, speed: isize, alpha: f32) -> usize {
     return (const_width + additional_width).ceil() as usize;
 }
 
-#[derive(Debug, Serialize, Copy, Clone)]
+fn occupation_of(
+    front: isize,
+    left: isize,
+    width: usize,
+    length: usize,
+    direction: Direction,
+) -> RectangleOccupier {
+    let highest_long = match direction {
+        Direction::Forward => front,
+        Direction::Backward => front + length as isize - 1,
+    };
+    return RectangleOccupier {
+        front: highest_long,
+        right: left + width as isize - 1,
+        width,
+        length,
+    };
+}
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone)]
 pub struct CarBuilder {
     front: isize,
     length: usize,
@@ -153,18 +527,95 @@ pub struct CarBuilder {
     beta: f32,
     speed_max: isize,
     speed: isize,
-    deceleration_prob: f64,
+    deceleration_prob: Probability,
+    acceleration_prob: Probability,
     slow_acceleration: isize,
     fast_acceleration: isize,
     max_slow_speed: isize,
+    politeness: f32,
+    lane_change_threshold: f32,
+    lane_change_policy: LaneChangePolicy,
+    bus_dwell: Option<usize>,
+    direction: Direction,
+    left: isize,
+    group: u8,
+    // missing from cars saved before they existed
+    #[serde(default)]
+    reaction_delay: usize,
+    #[serde(default)]
+    min_gap: usize,
+    // the state of a car mid run, so saving and modifying it keeps a bus
+    // dwelling at its stop
+    #[serde(default)]
+    brake_light: bool,
+    #[serde(default)]
+    dwell_remaining: usize,
 }
 
 #[allow(dead_code)]
 impl CarBuilder {
+    /// A bus, longer than the default car, stopping for `dwell` updates at
+    /// each of the road's bus stops.
+    pub fn bus(dwell: usize) -> Result<Self> {
+        return Self::default().with_length(12)?.with_bus_dwell(dwell);
+    }
+
+    /// Makes the car a bus stopping for `dwell` updates at each of the road's
+    /// bus stops.
+    pub fn with_bus_dwell(&self, dwell: usize) -> Result<Self> {
+        return match dwell {
+            0 => Err(anyhow!("bus must dwell at stops for at least one update")),
+            _ => Ok(Self {
+                bus_dwell: Some(dwell),
+                ..*self
+            }),
+        };
+    }
+
     pub fn with_front_at(&self, front: isize) -> Self {
         return Self { front, ..*self };
     }
 
+    pub fn with_direction(&self, direction: Direction) -> Self {
+        return Self { direction, ..*self };
+    }
+
+    /// Tags the car as part of a group, 0 unless set. The group doesn't
+    /// change how the car drives, it is only carried through to the output.
+    pub fn with_group(&self, group: u8) -> Self {
+        return Self { group, ..*self };
+    }
+
+    /// How many updates late the car reacts to the gap in front of it, 0
+    /// unless set. A delayed car caps its speed at the gap it saw that many
+    /// updates ago, so it is slow to follow a leader pulling away, but it
+    /// still never drives into the current gap.
+    pub const fn with_reaction_delay(&self, reaction_delay: usize) -> Self {
+        return Self {
+            reaction_delay,
+            ..*self
+        };
+    }
+
+    /// The number of clear cells the car keeps in front of it after moving,
+    /// 0 unless set. A car already closer than that stays put until the gap
+    /// opens up again.
+    pub const fn with_min_gap(&self, min_gap: usize) -> Self {
+        return Self { min_gap, ..*self };
+    }
+
+    /// Puts the car's left edge at `left` rather than lat 0, for a second lane
+    /// of motor traffic such as oncoming cars.
+    pub fn with_left_at(&self, left: isize) -> Result<Self> {
+        return match left.is_negative() {
+            true => Err(anyhow!(
+                "car left edge cannot be negative, instead {}",
+                left
+            )),
+            false => Ok(Self { left, ..*self }),
+        };
+    }
+
     pub fn with_slow_acceleration(&self, slow_acceleration: isize) -> Self {
         return Self {
             slow_acceleration,
@@ -172,22 +623,126 @@ impl CarBuilder {
         };
     }
 
+    pub fn with_length(&self, length: usize) -> Result<Self> {
+        return match length {
+            0 => Err(anyhow!("car length must be at least one cell")),
+            _ => Ok(Self { length, ..*self }),
+        };
+    }
+
+    /// The fastest the car goes under the road's limits, which only a
+    /// downhill grade lets it go past. Checked against the speed when the car
+    /// is built.
+    pub fn with_speed_max(&self, speed_max: isize) -> Result<Self> {
+        return match speed_max < 1 {
+            true => Err(anyhow!(
+                "car max speed must be at least 1, instead {}",
+                speed_max
+            )),
+            false => Ok(Self { speed_max, ..*self }),
+        };
+    }
+
     pub fn build(&self) -> Result<Car> {
         return self.try_into();
     }
 
-    fn with_speed(&self, speed: isize) -> Self {
+    /// Builds the car without checking its speed against its max speed, for
+    /// rebuilding a car that a downhill grade took past its max speed. It
+    /// brakes back down on its next update.
+    pub(crate) fn build_coasting(&self) -> Result<Car> {
+        if 0 < self.dwell_remaining && self.bus_dwell.is_none() {
+            return Err(anyhow!(
+                "only a bus can dwell at a stop, instead {} updates left",
+                self.dwell_remaining
+            ));
+        }
+        if self.speed_max < 1 {
+            return Err(anyhow!(
+                "car max speed must be at least 1, instead {}",
+                self.speed_max
+            ));
+        }
+        return Ok(Car {
+            occupation: occupation_of(
+                self.front,
+                self.left,
+                lateral_occupancy(self.car_width + self.beta, self.speed, self.alpha),
+                self.length,
+                self.direction,
+            ),
+            const_width: self.car_width + self.beta,
+            speed_max: self.speed_max,
+            speed: self.speed,
+            fast_acceleration: self.fast_acceleration,
+            slow_acceleration: self.slow_acceleration,
+            max_slow_speed: self.max_slow_speed,
+            alpha: self.alpha,
+            deceleration_distribution: self.deceleration_prob.bernoulli(),
+            deceleration_prob: self.deceleration_prob,
+            skip_acceleration_distribution: self.acceleration_prob.bernoulli(),
+            acceleration_prob: self.acceleration_prob,
+            politeness: self.politeness,
+            lane_change_threshold: self.lane_change_threshold,
+            lane_change_policy: self.lane_change_policy,
+            brake_light: self.brake_light,
+            bus_dwell: self.bus_dwell,
+            dwell_remaining: self.dwell_remaining,
+            direction: self.direction,
+            group: self.group,
+            reaction_delay: self.reaction_delay,
+            min_gap: self.min_gap,
+        });
+    }
+
+    pub fn with_speed(&self, speed: isize) -> Self {
         return Self { speed, ..*self };
     }
 
-    fn with_deceleration_prob(&self, deceleration_prob: f64) -> Result<Self> {
-        return match deceleration_prob <= 0.0 && 1.0 <= deceleration_prob {
-            true => Err(anyhow!(
-                "deceleration_prob must be between 0 and 1, instead {}",
-                deceleration_prob
+    pub(crate) const fn with_deceleration_prob(&self, deceleration_prob: Probability) -> Self {
+        return Self {
+            deceleration_prob,
+            ..*self
+        };
+    }
+
+    /// The probability of a car skipping the acceleration in an update, so
+    /// that 0 always accelerates when possible and 1 never does.
+    pub const fn with_acceleration_prob(&self, acceleration_prob: Probability) -> Self {
+        return Self {
+            acceleration_prob,
+            ..*self
+        };
+    }
+
+    pub fn with_politeness(&self, politeness: f32) -> Result<Self> {
+        return match politeness.is_finite() && 0.0 <= politeness {
+            false => Err(anyhow!(
+                "politeness must be finite and not negative, instead {}",
+                politeness
+            )),
+            true => Ok(Self {
+                politeness,
+                ..*self
+            }),
+        };
+    }
+
+    pub fn with_lane_change_policy(&self, lane_change_policy: LaneChangePolicy) -> Self {
+        return Self {
+            lane_change_policy,
+            ..*self
+        };
+    }
+
+    pub fn with_lane_change_threshold(&self, lane_change_threshold: f32) -> Result<Self> {
+        return match lane_change_threshold.is_finite() && 0.0 <= lane_change_threshold {
+            false => Err(anyhow!(
+                "lane change threshold must be finite and not negative, instead {}",
+                lane_change_threshold
             )),
-            false => Ok(Self {
-                deceleration_prob,
+            true => Ok(Self {
+                lane_change_threshold,
                 ..*self
             }),
         };
@@ -207,7 +762,19 @@ impl Default for CarBuilder {
             slow_acceleration: 2,
             fast_acceleration: 1,
             max_slow_speed: 5,
-            deceleration_prob: 0.2,
+            deceleration_prob: Probability::new(0.2).expect("0.2 is a probability"),
+            acceleration_prob: Probability::ZERO,
+            politeness: 0.5,
+            lane_change_threshold: 1.0,
+            lane_change_policy: LaneChangePolicy::default(),
+            bus_dwell: None,
+            direction: Direction::default(),
+            left: 0,
+            group: 0,
+            reaction_delay: 0,
+            min_gap: 0,
+            brake_light: false,
+            dwell_remaining: 0,
         }
     }
 }
@@ -216,24 +783,46 @@ impl TryFrom<&CarBuilder> for Car {
     type Error = anyhow::Error;
 
     fn try_from(value: &CarBuilder) -> std::result::Result<Self, Self::Error> {
-        return match value.speed_max < value.speed {
-            true => Err(anyhow!(
+        if value.speed_max < value.speed {
+            return Err(anyhow!(
                 "speed ({}) cannot be greater than max ({})",
-                value.speed_max,
-                value.speed
-            )),
-            false => Ok(Self {
-                front: value.front,
-                length: value.length,
-                const_width: value.car_width + value.beta,
-                speed_max: value.speed_max,
-                speed: value.speed,
-                fast_acceleration: value.fast_acceleration,
-                slow_acceleration: value.slow_acceleration,
-                max_slow_speed: value.max_slow_speed,
-                alpha: value.alpha,
-                deceleration_distribution: Bernoulli::new(value.deceleration_prob)?,
-            }),
+                value.speed,
+                value.speed_max
+            ));
+        }
+        return value.build_coasting();
+    }
+}
+
+/// A builder that builds the car as it currently is. The car's width and
+/// buffer aren't stored separately, so the builder has all of it as width. A
+/// bus dwelling at a stop is built still dwelling.
+impl From<&Car> for CarBuilder {
+    fn from(value: &Car) -> Self {
+        return Self {
+            front: value.front(),
+            length: value.length(),
+            car_width: value.const_width,
+            alpha: value.alpha,
+            beta: 0.0,
+            speed_max: value.speed_max,
+            speed: value.speed,
+            deceleration_prob: value.deceleration_prob,
+            acceleration_prob: value.acceleration_prob,
+            slow_acceleration: value.slow_acceleration,
+            fast_acceleration: value.fast_acceleration,
+            max_slow_speed: value.max_slow_speed,
+            politeness: value.politeness,
+            lane_change_threshold: value.lane_change_threshold,
+            lane_change_policy: value.lane_change_policy,
+            bus_dwell: value.bus_dwell,
+            direction: value.direction,
+            left: value.left(),
+            group: value.group,
+            reaction_delay: value.reaction_delay,
+            min_gap: value.min_gap,
+            brake_light: value.brake_light,
+            dwell_remaining: value.dwell_remaining,
         };
     }
 }
@@ -248,9 +837,39 @@ impl TryFrom<CarBuilder> for Car {
 
 #[cfg(test)]
 mod tests {
-    use crate::road::Road;
+    use rand::thread_rng;
+
+    use crate::road::{Coord, Road, Vehicle};
+
+    use crate::bike::BikeBuilder;
+    use crate::car::{CarBuilder, Direction, LaneChangeDirection, LaneChangePolicy};
+    use crate::probability::Probability;
+
+    #[test]
+    fn fast_wide_car_does_not_tunnel_through_bike() {
+        // at speed 7 the car is 7 lats wide but at the slower speeds it
+        // checks first it is narrower, never reaching lat 6. Moving 8 puts its
+        // back beyond the bike, so only the swept path collides with it
+        let cars = [CarBuilder::default()
+            .with_front_at(10)
+            .with_speed(7)
+            .with_deceleration_prob(Probability::ZERO)
+            .build()
+            .unwrap()];
+        let bikes = [BikeBuilder::default()
+            .with_dimensions((1, 1))
+            .unwrap()
+            .with_front_right_at(Coord { lat: 6, long: 12 })
+            .build()
+            .unwrap()];
+        let road = Road::<1, 1, 50, 3, 7>::new(bikes, cars).unwrap();
+        let car = road.get_car(0);
+        assert_eq!(car.width(), 7);
+        assert_eq!(car.next_iteration_potential_speed(&road), 8);
 
-    use crate::car::CarBuilder;
+        assert_eq!(car.fastest_safe_speed(&road, 0), 1);
+        assert_eq!(road.car_front_gap(0), Some(1));
+    }
 
     #[test]
     fn car_update_works() {
@@ -260,6 +879,103 @@ mod tests {
         road.cars_update().unwrap();
     }
 
+    #[test]
+    fn deceleration_prob_can_change_mid_run() {
+        let mut car = CarBuilder::default()
+            .with_deceleration_prob(Probability::ONE)
+            .build()
+            .unwrap();
+        assert!(car.should_decelerate(&mut thread_rng()));
+        assert_eq!(
+            CarBuilder::from(&car).build().unwrap().deceleration_prob,
+            Probability::ONE
+        );
+
+        car.set_deceleration_prob(Probability::ZERO);
+        assert!(!car.should_decelerate(&mut thread_rng()));
+    }
+
+    #[test]
+    fn car_stops_before_full_keep_clear_zone() {
+        // the stopped car's back is at 23, inside the zone
+        let cars = [(15, 5), (27, 0)].map(|(front, speed)| {
+            CarBuilder::default()
+                .with_front_at(front)
+                .with_speed(speed)
+                .build()
+                .unwrap()
+        });
+        let mut road = Road::<0, 2, 60, 0, 14>::new([], cars).unwrap();
+        assert_eq!(road.get_car(0).fastest_safe_speed(&road, 0), 7);
+
+        road.add_keep_clear(20..=25).unwrap();
+
+        assert_eq!(road.get_car(0).fastest_safe_speed(&road, 0), 4);
+        #[allow(clippy::reversed_empty_ranges)]
+        let empty_range = 5..=4;
+        assert!(road.add_keep_clear(empty_range).is_err());
+    }
+
+    #[test]
+    fn car_stops_before_keep_clear_zone_with_queue_just_past_it() {
+        // the stopped car's back is at 27, just past the zone, leaving too
+        // little room for the approaching car to get all of itself out
+        let cars = [(14, 5), (31, 0)].map(|(front, speed)| {
+            CarBuilder::default()
+                .with_front_at(front)
+                .with_speed(speed)
+                .build()
+                .unwrap()
+        });
+        let mut road = Road::<0, 2, 60, 0, 14>::new([], cars).unwrap();
+        assert_eq!(road.get_car(0).fastest_safe_speed(&road, 0), 7);
+
+        road.add_keep_clear(20..=25).unwrap();
+
+        assert_eq!(road.get_car(0).fastest_safe_speed(&road, 0), 5);
+    }
+
+    #[test]
+    fn backward_car_stops_before_keep_clear_zone_with_queue_just_past_it() {
+        // the same as for a forward car, mirrored along the road
+        let cars = [(45, 5), (28, 0)].map(|(front, speed)| {
+            CarBuilder::default()
+                .with_front_at(front)
+                .with_speed(speed)
+                .with_direction(Direction::Backward)
+                .build()
+                .unwrap()
+        });
+        let mut road = Road::<0, 2, 60, 0, 14>::new([], cars).unwrap();
+        assert_eq!(road.get_car(0).fastest_safe_speed(&road, 0), 7);
+
+        road.add_keep_clear(34..=39).unwrap();
+
+        assert_eq!(road.get_car(0).fastest_safe_speed(&road, 0), 5);
+    }
+
+    #[test]
+    fn car_with_safe_speed_one_holds_position_when_decelerating() {
+        let cars = [(10, 0), (16, 1)].map(|(front, speed)| {
+            CarBuilder::default()
+                .with_front_at(front)
+                .with_speed(speed)
+                .build()
+                .unwrap()
+        });
+        let road = Road::<0, 2, 40, 0, 14>::new([], cars).unwrap();
+        let follower = road.get_car(0);
+        assert_eq!(follower.fastest_safe_speed(&road, 0), 1);
+
+        let decelerated = follower.update(&road, 0, true, None);
+        let not_decelerated = follower.update(&road, 0, false, None);
+
+        assert_eq!(decelerated.speed, 0);
+        assert_eq!(decelerated.front(), 10);
+        assert!(!road.is_collision_for(&decelerated, Vehicle::Car(0)));
+        assert_eq!(not_decelerated.front(), 11);
+    }
+
     #[test]
     fn car_update_works_as_expected() {
         let start_front = 10;
@@ -268,15 +984,108 @@ mod tests {
             .with_front_at(start_front)
             .with_slow_acceleration(slow_acc)
             .with_speed(0)
-            .with_deceleration_prob(0.0)
-            .unwrap()]
+            .with_deceleration_prob(Probability::ZERO)]
         .map(|builder| builder.try_into().unwrap());
         let mut road = Road::<0, 1, 20, 3, 3>::new([], cars).unwrap();
 
         road.cars_update().unwrap();
 
-        let end_front = road.get_car(0).front;
+        let end_front = road.get_car(0).front();
 
         assert_eq!(end_front - start_front, slow_acc);
     }
+
+    #[test]
+    fn braking_distance_sums_slowing_speeds() {
+        let braking_distances = [0, 1, 2, 5].map(|speed| {
+            CarBuilder::default()
+                .with_speed(speed)
+                .build()
+                .unwrap()
+                .braking_distance()
+        });
+
+        assert_eq!(braking_distances, [0, 0, 1, 10]);
+    }
+
+    #[test]
+    fn polite_car_declines_cutting_off_follower() {
+        let selfish = CarBuilder::default()
+            .with_politeness(0.0)
+            .unwrap()
+            .build()
+            .unwrap();
+        let polite = CarBuilder::default()
+            .with_politeness(0.8)
+            .unwrap()
+            .build()
+            .unwrap();
+        // the change gains the car 3 but the new follower has to brake by 5
+        let (own_gain, new_follower_gain, old_follower_gain) = (3, -5, 0);
+
+        assert!(selfish.accepts_lane_change(own_gain, new_follower_gain, old_follower_gain));
+        assert!(!polite.accepts_lane_change(own_gain, new_follower_gain, old_follower_gain));
+        assert!(polite.accepts_lane_change(own_gain, 0, 0));
+        assert!(CarBuilder::default().with_politeness(-0.1).is_err());
+        for not_finite in [f32::NAN, f32::INFINITY] {
+            assert!(CarBuilder::default().with_politeness(not_finite).is_err());
+            assert!(CarBuilder::default()
+                .with_lane_change_threshold(not_finite)
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn safe_acceleration_limited_by_gap() {
+        let cars = [(10, 0), (16, 0)].map(|(front, speed)| {
+            CarBuilder::default()
+                .with_front_at(front)
+                .with_speed(speed)
+                .build()
+                .unwrap()
+        });
+        let road = Road::<0, 2, 40, 0, 14>::new([], cars).unwrap();
+
+        assert_eq!(road.get_car(0).safe_acceleration(&road, 0), 1);
+        assert_eq!(road.get_car(1).safe_acceleration(&road, 1), 2);
+    }
+
+    #[test]
+    fn keep_right_policy_returns_right() {
+        let symmetric = CarBuilder::default().build().unwrap();
+        let keep_right = CarBuilder::default()
+            .with_lane_change_policy(LaneChangePolicy::KeepRight)
+            .build()
+            .unwrap();
+
+        for car in [symmetric, keep_right] {
+            // a fast car stuck behind a slow one overtakes on the left
+            assert!(car.accepts_lane_change_towards(LaneChangeDirection::Left, 3, 0, 0));
+            // but a slow car has nothing to gain from moving left
+            assert!(!car.accepts_lane_change_towards(LaneChangeDirection::Left, 0, 0, 0));
+        }
+        // moving back right gains nothing, so only keeping right does it
+        assert!(!symmetric.accepts_lane_change_towards(LaneChangeDirection::Right, 0, 0, 0));
+        assert!(keep_right.accepts_lane_change_towards(LaneChangeDirection::Right, 0, 0, 0));
+        // unless it would mean braking for the car ahead on the right
+        assert!(!keep_right.accepts_lane_change_towards(LaneChangeDirection::Right, -2, 0, 0));
+    }
+
+    #[test]
+    fn car_max_speed_must_be_at_least_one() {
+        assert!(CarBuilder::default().with_speed_max(0).is_err());
+        assert!(CarBuilder::default().with_speed_max(-1).is_err());
+        assert!(CarBuilder::default().with_speed_max(1).is_ok());
+        // a saved builder doesn't go through with_speed_max
+        let mut saved = serde_json::to_value(CarBuilder::default()).unwrap();
+        saved["speed_max"] = 0.into();
+        let loaded: CarBuilder = serde_json::from_value(saved).unwrap();
+        assert!(loaded.build().is_err());
+    }
+
+    #[test]
+    fn car_faster_than_its_max_speed_fails_to_build() {
+        assert!(CarBuilder::default().with_speed(40).build().is_err());
+        assert!(CarBuilder::default().with_speed(20).build().is_ok());
+    }
 }
@@ -0,0 +1,304 @@
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::road::{rectangle_occupation, Coord, RectangleOccupier, Road, RoadOccupier};
+
+/// A car with a real valued front and speed, for sub-cell resolution on the
+/// same road geometry as the cellular vehicles.
+///
+/// Added to a road with `Road::add_continuous_car`, it occupies the cells of
+/// its floored front, so the road's vehicles stop behind it as they would
+/// behind a car, and it keeps out of anything ahead of it in the same way. It
+/// has no random braking and keeps a constant width, accelerating steadily up
+/// to its max speed.
+///
+/// Speeds are in cells per time unit and accelerations in cells per time
+/// unit per time unit, with each update lasting `dt` time units, 1 unless
+/// set. Shorter steps move the car less per update, but it speeds up just as
+/// quickly in time.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ContinuousCar {
+    front: f64,
+    speed: f64,
+    left: isize,
+    width: usize,
+    length: usize,
+    speed_max: f64,
+    acceleration: f64,
+    dt: f64,
+}
+
+impl RoadOccupier for ContinuousCar {
+    fn occupied_cells(&self) -> impl Iterator<Item = Coord> {
+        let RectangleOccupier {
+            front,
+            right,
+            width,
+            length,
+        } = self.rectangle_occupation();
+        return rectangle_occupation(front, right, width, length);
+    }
+}
+
+impl ContinuousCar {
+    /// A stopped car with its front at `front` and its left edge on `left`,
+    /// with a max speed of 5 and an acceleration of 1.
+    pub fn new(front: f64, left: isize, width: usize, length: usize) -> Result<Self> {
+        if !front.is_finite() {
+            return Err(anyhow!("front must be finite, instead {}", front));
+        }
+        if left.is_negative() {
+            return Err(anyhow!(
+                "car left edge cannot be negative, instead {}",
+                left
+            ));
+        }
+        if width == 0 || length == 0 {
+            return Err(anyhow!(
+                "car must be at least one cell wide and long, instead {}x{}",
+                width,
+                length
+            ));
+        }
+        return Ok(Self {
+            front,
+            speed: 0.0,
+            left,
+            width,
+            length,
+            speed_max: 5.0,
+            acceleration: 1.0,
+            dt: 1.0,
+        });
+    }
+
+    pub fn with_speed(&self, speed: f64) -> Result<Self> {
+        return match (0.0..=self.speed_max).contains(&speed) {
+            true => Ok(Self { speed, ..*self }),
+            false => Err(anyhow!(
+                "speed must be between 0 and max ({}), instead {}",
+                self.speed_max,
+                speed
+            )),
+        };
+    }
+
+    pub fn with_speed_max(&self, speed_max: f64) -> Result<Self> {
+        return match speed_max.is_finite() && self.speed <= speed_max {
+            true => Ok(Self { speed_max, ..*self }),
+            false => Err(anyhow!(
+                "max speed must be finite and at least the speed ({}), instead {}",
+                self.speed,
+                speed_max
+            )),
+        };
+    }
+
+    pub fn with_acceleration(&self, acceleration: f64) -> Result<Self> {
+        return match acceleration.is_finite() && !acceleration.is_sign_negative() {
+            true => Ok(Self {
+                acceleration,
+                ..*self
+            }),
+            false => Err(anyhow!(
+                "acceleration must be finite and non-negative, instead {}",
+                acceleration
+            )),
+        };
+    }
+
+    /// How many time units each update lasts.
+    pub fn with_dt(&self, dt: f64) -> Result<Self> {
+        return match dt.is_finite() && 0.0 < dt {
+            true => Ok(Self { dt, ..*self }),
+            false => Err(anyhow!(
+                "time step must be finite and strictly positive, instead {}",
+                dt
+            )),
+        };
+    }
+
+    pub const fn dt(&self) -> f64 {
+        return self.dt;
+    }
+
+    pub const fn front(&self) -> f64 {
+        return self.front;
+    }
+
+    pub const fn speed(&self) -> f64 {
+        return self.speed;
+    }
+
+    /// The cells the car is in, as if it were a `Car` with its front floored.
+    pub fn rectangle_occupation(&self) -> RectangleOccupier {
+        return RectangleOccupier {
+            front: self.front.floor() as isize,
+            right: self.left + self.width as isize - 1,
+            width: self.width,
+            length: self.length,
+        };
+    }
+
+    /// The fastest speed the car can reach next update without its floored
+    /// front passing the clear cells ahead of it on the road.
+    pub fn fastest_safe_speed<
+        const B: usize,
+        const C: usize,
+        const L: usize,
+        const BLW: usize,
+        const MLW: usize,
+    >(
+        &self,
+        road: &Road<B, C, L, BLW, MLW>,
+    ) -> f64 {
+        let gap = road
+            .front_gap(&self.rectangle_occupation())
+            .expect("car should be at least one cell wide");
+        // moving by at most the gap from the floored front keeps the floored
+        // front within the gap
+        let safe_distance = gap as f64 - self.front.fract().abs();
+        return (self.speed + self.acceleration * self.dt)
+            .min(self.speed_max)
+            .min(safe_distance / self.dt)
+            .max(0.0);
+    }
+
+    /// The car after an update on the road, moved along by its new speed for
+    /// `dt` and wrapped around the road's length.
+    pub fn update<
+        const B: usize,
+        const C: usize,
+        const L: usize,
+        const BLW: usize,
+        const MLW: usize,
+    >(
+        &self,
+        road: &Road<B, C, L, BLW, MLW>,
+    ) -> Self {
+        let speed = self.fastest_safe_speed(road);
+        return Self {
+            front: (self.front + speed * self.dt).rem_euclid(L as f64),
+            speed,
+            ..*self
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        car::CarBuilder,
+        continuous_car::ContinuousCar,
+        probability::Probability,
+        road::{rectangle_occupation, Road, RoadOccupier, Vehicle},
+    };
+
+    #[test]
+    fn continuous_car_advances_by_fractional_speed() {
+        let road = Road::<0, 0, 30, 0, 7>::new([], []).unwrap();
+        let car = ContinuousCar::new(2.0, 0, 2, 3)
+            .unwrap()
+            .with_acceleration(0.4)
+            .unwrap();
+
+        let car = car.update(&road);
+        assert_eq!(car.speed(), 0.4);
+        assert_eq!(car.front(), 2.4);
+        assert!(car.occupied_cells().eq(rectangle_occupation(2, 1, 2, 3)));
+
+        let car = car.update(&road).update(&road);
+        assert!((car.front() - 4.4).abs() < 1e-9);
+        assert!(car.occupied_cells().eq(rectangle_occupation(4, 1, 2, 3)));
+    }
+
+    #[test]
+    fn halving_dt_halves_distance_per_step_but_not_acceleration_in_time() {
+        let road = Road::<0, 0, 100, 0, 7>::new([], []).unwrap();
+        let car = ContinuousCar::new(0.0, 0, 2, 3).unwrap();
+        let half_step_car = car.with_dt(0.5).unwrap();
+        assert!(car.with_dt(0.0).is_err());
+
+        // both take 5 time units to reach the max speed of 5
+        let car = (0..5).fold(car, |car, _| car.update(&road));
+        let half_step_car = (0..10).fold(half_step_car, |car, _| car.update(&road));
+        assert_eq!(car.speed(), 5.0);
+        assert_eq!(half_step_car.speed(), 5.0);
+        assert_eq!(car.front(), 15.0);
+        assert_eq!(half_step_car.front(), 13.75);
+
+        let distance = car.update(&road).front() - car.front();
+        let half_step_distance = half_step_car.update(&road).front() - half_step_car.front();
+        assert_eq!(distance, 5.0);
+        assert_eq!(half_step_distance, 2.5);
+    }
+
+    #[test]
+    fn continuous_car_stops_short_of_car_ahead() {
+        let car_ahead = CarBuilder::default()
+            .with_front_at(12)
+            .with_speed(0)
+            .build()
+            .unwrap();
+        let road = Road::<0, 1, 30, 0, 7>::new([], [car_ahead]).unwrap();
+        let mut car = ContinuousCar::new(3.5, 0, 2, 3)
+            .unwrap()
+            .with_speed(2.5)
+            .unwrap();
+
+        for _ in 0..10 {
+            car = car.update(&road);
+            assert!(road.collisions_for(&car).is_empty());
+        }
+        // the car ahead's back is at long 8
+        assert_eq!(car.rectangle_occupation().front, 7);
+        assert_eq!(car.speed(), 0.0);
+    }
+
+    #[test]
+    fn car_stops_behind_continuous_car_on_road() {
+        let car = CarBuilder::default()
+            .with_front_at(5)
+            .with_deceleration_prob(Probability::ZERO)
+            .build()
+            .unwrap();
+        let mut road = Road::<0, 1, 40, 0, 7>::new([], [car]).unwrap();
+        let stopped = ContinuousCar::new(20.5, 0, 2, 3)
+            .unwrap()
+            .with_speed_max(0.0)
+            .unwrap();
+        assert_eq!(road.add_continuous_car(stopped).unwrap(), 0);
+        assert!(road.add_continuous_car(stopped).is_err());
+        assert!(road.cells().to_string().contains("R0"));
+
+        for _ in 0..10 {
+            road.update().unwrap();
+        }
+        // the continuous car's back is at long 18
+        assert_eq!(road.cars()[0].front(), 17);
+        assert_eq!(road.cars()[0].speed(), 0);
+        assert!(road
+            .collisions_for(&road.continuous_cars()[0])
+            .into_iter()
+            .all(|vehicle| *vehicle == Vehicle::ContinuousCar(0)));
+    }
+
+    #[test]
+    fn continuous_car_on_road_moves_its_cells() {
+        let mut road = Road::<0, 0, 30, 0, 7>::new([], []).unwrap();
+        let car = ContinuousCar::new(2.5, 0, 2, 3)
+            .unwrap()
+            .with_speed(1.5)
+            .unwrap();
+        road.add_continuous_car(car).unwrap();
+
+        road.update().unwrap();
+        let moved = road.continuous_cars()[0];
+        assert_eq!(moved.front(), 5.0);
+        assert_eq!(
+            road.collisions_for(&moved).len(),
+            moved.occupied_cells().count()
+        );
+        assert!(road.collisions_for(&car).is_empty());
+    }
+}
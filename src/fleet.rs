@@ -0,0 +1,261 @@
+use std::ops::RangeInclusive;
+
+use anyhow::{anyhow, Result};
+use rand::{distributions::WeightedIndex, prelude::Distribution, Rng};
+
+use crate::{car::CarBuilder, probability::Probability};
+
+/// One kind of car in a fleet, making up `share` of it, with each parameter
+/// drawn uniformly from its range.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CarClass {
+    /// Relative to the shares of the other classes, so they don't have to
+    /// add up to one.
+    pub share: f64,
+    pub speed_max: RangeInclusive<isize>,
+    /// The acceleration from low speeds, see `CarBuilder::with_slow_acceleration`.
+    pub acceleration: RangeInclusive<isize>,
+    pub deceleration_prob: RangeInclusive<f64>,
+    pub length: RangeInclusive<usize>,
+}
+
+impl Default for CarClass {
+    /// The default car, with every range a single value.
+    fn default() -> Self {
+        let car = CarBuilder::default()
+            .build()
+            .expect("default car builder configuration should be valid");
+        return Self {
+            share: 1.0,
+            speed_max: car.speed_max()..=car.speed_max(),
+            acceleration: 2..=2,
+            deceleration_prob: 0.2..=0.2,
+            length: car.length()..=car.length(),
+        };
+    }
+}
+
+impl CarClass {
+    fn validate(&self) -> Result<()> {
+        if !(self.share.is_finite() && 0.0 < self.share) {
+            return Err(anyhow!(
+                "class share must be positive, instead {}",
+                self.share
+            ));
+        }
+        if self.speed_max.is_empty()
+            || self.acceleration.is_empty()
+            || self.deceleration_prob.is_empty()
+            || self.length.is_empty()
+        {
+            return Err(anyhow!("class ranges cannot be empty: {:?}", self));
+        }
+        if *self.speed_max.start() < 1 || *self.acceleration.start() < 1 {
+            return Err(anyhow!(
+                "class max speeds and accelerations must be at least 1: {:?}",
+                self
+            ));
+        }
+        if *self.deceleration_prob.start() < 0.0 || 1.0 < *self.deceleration_prob.end() {
+            return Err(anyhow!(
+                "class deceleration_prob must be between 0 and 1, instead {:?}",
+                self.deceleration_prob
+            ));
+        }
+        if *self.length.start() == 0 {
+            return Err(anyhow!("class length must be at least one cell"));
+        }
+        return Ok(());
+    }
+
+    fn sample(&self, rng: &mut impl Rng) -> Result<CarBuilder> {
+        let deceleration_prob = match self.deceleration_prob.start() == self.deceleration_prob.end()
+        {
+            true => *self.deceleration_prob.start(),
+            false => rng.gen_range(self.deceleration_prob.clone()),
+        };
+        return CarBuilder::default()
+            .with_speed_max(rng.gen_range(self.speed_max.clone()))?
+            .with_slow_acceleration(rng.gen_range(self.acceleration.clone()))
+            .with_deceleration_prob(Probability::new(deceleration_prob)?)
+            .with_length(rng.gen_range(self.length.clone()));
+    }
+}
+
+/// A mix of car classes to sample the cars of a road from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FleetSpec {
+    classes: Vec<CarClass>,
+    class_distribution: WeightedIndex<f64>,
+}
+
+impl FleetSpec {
+    pub fn new(classes: Vec<CarClass>) -> Result<Self> {
+        for class in &classes {
+            class.validate()?;
+        }
+        let class_distribution = WeightedIndex::new(classes.iter().map(|class| class.share))
+            .map_err(|error| anyhow!("invalid class shares: {}", error))?;
+        return Ok(Self {
+            classes,
+            class_distribution,
+        });
+    }
+
+    /// Default cars differing only in length, such as motorcycles, cars and
+    /// trucks, each with a share of the fleet and a range of lengths.
+    pub fn from_lengths(
+        lengths: impl IntoIterator<Item = (f64, RangeInclusive<usize>)>,
+    ) -> Result<Self> {
+        return Self::new(
+            lengths
+                .into_iter()
+                .map(|(share, length)| CarClass {
+                    share,
+                    length,
+                    ..CarClass::default()
+                })
+                .collect(),
+        );
+    }
+
+    pub fn classes(&self) -> &[CarClass] {
+        return &self.classes;
+    }
+
+    /// Samples a class for each car, then the car's parameters from that
+    /// class. Every builder is left at the default position, so the cars
+    /// still need placing with `CarBuilder::with_front_at`. Taking the `rng`
+    /// lets a seeded generator reproduce the fleet.
+    pub fn sample_cars<const C: usize>(&self, rng: &mut impl Rng) -> Result<[CarBuilder; C]> {
+        let mut builders = [CarBuilder::default(); C];
+        for builder in &mut builders {
+            *builder = self.classes[self.class_distribution.sample(rng)].sample(rng)?;
+        }
+        return Ok(builders);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::{
+        car::Car,
+        fleet::{CarClass, FleetSpec},
+    };
+
+    #[test]
+    fn sampled_fleet_matches_class_mix() {
+        let cars = CarClass {
+            share: 0.8,
+            speed_max: 18..=22,
+            ..CarClass::default()
+        };
+        let trucks = CarClass {
+            share: 0.2,
+            speed_max: 10..=12,
+            deceleration_prob: 0.1..=0.3,
+            length: 10..=12,
+            ..CarClass::default()
+        };
+        let fleet = FleetSpec::new(vec![cars, trucks]).unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let builders = fleet.sample_cars::<1000>(&mut rng).unwrap();
+        let built: Vec<Car> = builders
+            .iter()
+            .map(|builder| builder.build().unwrap())
+            .collect();
+
+        let (long, short): (Vec<&Car>, Vec<&Car>) =
+            built.iter().partition(|car| 10 <= car.length());
+        assert!(
+            (long.len() as isize - 200).abs() < 40,
+            "{} trucks",
+            long.len()
+        );
+        assert!(long
+            .iter()
+            .all(|truck| (10..=12).contains(&truck.speed_max()) && truck.length() <= 12));
+        assert!(short
+            .iter()
+            .all(|car| (18..=22).contains(&car.speed_max()) && car.length() == 5));
+        for speed_max in 18..=22 {
+            let count = short
+                .iter()
+                .filter(|car| car.speed_max() == speed_max)
+                .count();
+            assert!(
+                (count as isize - 160).abs() < 40,
+                "{} at {}",
+                count,
+                speed_max
+            );
+        }
+    }
+
+    #[test]
+    fn seeded_fleets_are_reproducible() {
+        let fleet = FleetSpec::new(vec![CarClass {
+            speed_max: 5..=30,
+            ..CarClass::default()
+        }])
+        .unwrap();
+        let speed_maxes = |seed| {
+            let builders = fleet
+                .sample_cars::<20>(&mut StdRng::seed_from_u64(seed))
+                .unwrap();
+            return builders.map(|builder| builder.build().unwrap().speed_max());
+        };
+
+        assert_eq!(speed_maxes(7), speed_maxes(7));
+    }
+
+    #[test]
+    fn length_fleet_samples_only_given_lengths() {
+        let fleet = FleetSpec::from_lengths([(0.1, 2..=2), (0.7, 5..=5), (0.2, 12..=18)]).unwrap();
+        let mut rng = StdRng::seed_from_u64(3);
+
+        let lengths = fleet
+            .sample_cars::<200>(&mut rng)
+            .unwrap()
+            .map(|builder| builder.build().unwrap().length());
+
+        assert!(lengths
+            .iter()
+            .all(|length| [2, 5].contains(length) || (12..=18).contains(length)));
+        assert!(lengths.contains(&2) && lengths.contains(&5));
+        assert!(FleetSpec::from_lengths([(1.0, 0..=4)]).is_err());
+    }
+
+    #[test]
+    fn fleet_rejects_invalid_classes() {
+        assert!(FleetSpec::new(vec![]).is_err());
+        assert!(FleetSpec::new(vec![CarClass {
+            share: 0.0,
+            ..CarClass::default()
+        }])
+        .is_err());
+        assert!(FleetSpec::new(vec![CarClass {
+            length: 0..=3,
+            ..CarClass::default()
+        }])
+        .is_err());
+        assert!(FleetSpec::new(vec![CarClass {
+            deceleration_prob: 0.5..=1.5,
+            ..CarClass::default()
+        }])
+        .is_err());
+        assert!(FleetSpec::new(vec![CarClass {
+            speed_max: 0..=3,
+            ..CarClass::default()
+        }])
+        .is_err());
+        assert!(FleetSpec::new(vec![CarClass {
+            acceleration: 0..=2,
+            ..CarClass::default()
+        }])
+        .is_err());
+    }
+}
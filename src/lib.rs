@@ -1,5 +1,19 @@
+#![allow(clippy::needless_return)]
+
 pub mod bike;
 pub mod car;
+pub mod continuous_car;
+pub mod fleet;
+pub mod pedestrian;
+pub mod population;
+#[cfg(feature = "ppm-export")]
+mod ppm;
+pub mod probability;
 #[cfg(test)]
 mod proptest_defs;
+pub mod rand_source;
 pub mod road;
+pub mod simulation;
+pub mod stats;
+#[cfg(feature = "svg-export")]
+mod svg;
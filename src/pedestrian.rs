@@ -0,0 +1,131 @@
+use std::iter::once;
+
+use serde::{Deserialize, Serialize};
+
+use crate::road::{Coord, Road, RoadOccupier, Vehicle};
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum WalkDirection {
+    /// Towards lat 0, the far side of the motor lane.
+    Left,
+    /// Towards the bike lane side of the road.
+    Right,
+}
+
+/// A pedestrian crossing the road at a single long, walking one lat a step
+/// and waiting whenever a vehicle is in the way. Once walked off the road it
+/// is finished and no longer occupies a cell.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Pedestrian {
+    long: isize,
+    lat: isize,
+    direction: WalkDirection,
+    finished: bool,
+}
+
+impl RoadOccupier for Pedestrian {
+    fn occupied_cells(&self) -> impl Iterator<Item = Coord> {
+        let cell = Coord {
+            lat: self.lat,
+            long: self.long,
+        };
+        return once(cell).filter(|_| !self.finished);
+    }
+}
+
+impl Pedestrian {
+    pub const fn new(long: isize, lat: isize, direction: WalkDirection) -> Self {
+        return Self {
+            long,
+            lat,
+            direction,
+            finished: false,
+        };
+    }
+
+    pub const fn long(&self) -> isize {
+        return self.long;
+    }
+
+    pub const fn lat(&self) -> isize {
+        return self.lat;
+    }
+
+    pub const fn direction(&self) -> WalkDirection {
+        return self.direction;
+    }
+
+    /// Whether the pedestrian has made it off the road.
+    pub const fn is_finished(&self) -> bool {
+        return self.finished;
+    }
+
+    pub(crate) fn update<
+        const B: usize,
+        const C: usize,
+        const L: usize,
+        const BLW: usize,
+        const MLW: usize,
+    >(
+        &self,
+        road: &Road<B, C, L, BLW, MLW>,
+        self_id: usize,
+    ) -> Self {
+        if self.finished {
+            return *self;
+        }
+        let next_lat = match self.direction {
+            WalkDirection::Left => self.lat - 1,
+            WalkDirection::Right => self.lat + 1,
+        };
+        if !(0..Road::<B, C, L, BLW, MLW>::total_width()).contains(&next_lat) {
+            return Self {
+                finished: true,
+                ..*self
+            };
+        }
+        let moved = Self {
+            lat: next_lat,
+            ..*self
+        };
+        return match road.is_collision_for(&moved, Vehicle::Pedestrian(self_id)) {
+            true => *self,
+            false => moved,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        car::CarBuilder,
+        pedestrian::{Pedestrian, WalkDirection},
+        probability::Probability,
+        road::Road,
+    };
+
+    #[test]
+    fn pedestrian_waits_for_car_then_walks_off_road() {
+        let car = CarBuilder::default()
+            .with_front_at(10)
+            .with_acceleration_prob(Probability::ONE)
+            .build()
+            .unwrap();
+        let mut road = Road::<0, 1, 20, 2, 4>::new([], [car]).unwrap();
+        road.add_pedestrian(Pedestrian::new(8, 5, WalkDirection::Left))
+            .unwrap();
+
+        for _ in 0..3 {
+            road.update().unwrap();
+        }
+        assert_eq!(road.pedestrians()[0].lat(), car.width() as isize);
+
+        road.modify_car(0, |builder| *builder = builder.with_front_at(16))
+            .unwrap();
+        for _ in 0..car.width() + 1 {
+            road.update().unwrap();
+        }
+        assert!(road.pedestrians()[0].is_finished());
+        assert_eq!(road.cells_in_range(8..=8, 0..=5).count(), 0);
+    }
+}
@@ -0,0 +1,173 @@
+use std::cmp::min;
+
+use crate::{
+    bike::{Bike, BikeBuilder},
+    car::CarBuilder,
+};
+
+fn default_bike_dimensions() -> (usize, usize) {
+    let occupation = Bike::default().rectangle_occupation();
+    return (occupation.width, occupation.length);
+}
+
+fn default_car_length() -> usize {
+    return CarBuilder::default()
+        .build()
+        .expect("default car builder configuration should be valid")
+        .length();
+}
+
+/// The largest number of vehicles of `vehicle_length` that can be placed one
+/// after another along `length` without exceeding `target_density`, where
+/// density is measured the same way as `Road::car_density`.
+fn count_for_density(length: usize, vehicle_length: usize, target_density: f64) -> usize {
+    let wanted = (target_density * length as f64 / vehicle_length as f64).floor() as usize;
+    return min(wanted, length / vehicle_length);
+}
+
+/// Suggests the `(NUM_BIKES, NUM_CARS)` of default sized vehicles that fill a
+/// road of the given `length` and total `width` to `target_density`.
+///
+/// Bikes are lined up along the right hand edge and cars along the left, as in
+/// `main.rs`, so no bikes are suggested if the road is narrower than a bike.
+pub fn suggest_counts(length: usize, width: usize, target_density: f64) -> (usize, usize) {
+    let (bike_width, bike_length) = default_bike_dimensions();
+    let num_bikes = match width < bike_width {
+        true => 0,
+        false => count_for_density(length, bike_length, target_density),
+    };
+    let num_cars = count_for_density(length, default_car_length(), target_density);
+    return (num_bikes, num_cars);
+}
+
+/// Default bikes with their right hand edges on `right`, spaced evenly along
+/// a road of length `L` with the first at its start.
+pub fn evenly_spaced_bikes<const B: usize, const L: usize>(right: isize) -> [BikeBuilder; B] {
+    // no bikes means the array will be empty so the zero spacing won't be a
+    // problem
+    let bike_spacing = L.checked_div(B).unwrap_or(0);
+    return std::array::from_fn(|bike_id| {
+        return BikeBuilder::default()
+            .with_front_at((bike_spacing * bike_id) as isize)
+            .with_right_at(right);
+    });
+}
+
+/// Default cars spaced evenly along a road of length `L` like
+/// `evenly_spaced_bikes`, on the left hand edge.
+pub fn evenly_spaced_cars<const C: usize, const L: usize>() -> [CarBuilder; C] {
+    let car_spacing = L.checked_div(C).unwrap_or(0);
+    return std::array::from_fn(|car_id| {
+        return CarBuilder::default().with_front_at((car_spacing * car_id) as isize);
+    });
+}
+
+/// Builders for the population suggested by `suggest_counts`, evenly spaced
+/// along the road. The vector lengths are the counts to use as the road's
+/// const generics.
+pub fn density_population(
+    length: usize,
+    width: usize,
+    target_density: f64,
+) -> (Vec<BikeBuilder>, Vec<CarBuilder>) {
+    let (num_bikes, num_cars) = suggest_counts(length, width, target_density);
+    // no bikes or cars mean the vectors will be empty so the zero spacing
+    // won't be a problem
+    let bike_spacing = length.checked_div(num_bikes).unwrap_or(0);
+    let car_spacing = length.checked_div(num_cars).unwrap_or(0);
+    let bikes = (0..num_bikes)
+        .map(|bike_id| {
+            return BikeBuilder::default()
+                .with_front_at((bike_spacing * bike_id) as isize)
+                .with_right_at(width as isize - 1);
+        })
+        .collect();
+    let cars = (0..num_cars)
+        .map(|car_id| {
+            return CarBuilder::default().with_front_at((car_spacing * car_id) as isize);
+        })
+        .collect();
+    return (bikes, cars);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        bike::Bike,
+        car::Car,
+        population::{density_population, evenly_spaced_bikes, evenly_spaced_cars, suggest_counts},
+        road::Road,
+    };
+
+    fn road_from_population<
+        const B: usize,
+        const C: usize,
+        const L: usize,
+        const BLW: usize,
+        const MLW: usize,
+    >(
+        target_density: f64,
+    ) -> Road<B, C, L, BLW, MLW> {
+        let (bikes, cars) = density_population(L, BLW + MLW, target_density);
+        let bikes: Vec<Bike> = bikes.iter().map(|bike| bike.build().unwrap()).collect();
+        let cars: Vec<Car> = cars.iter().map(|car| car.build().unwrap()).collect();
+        return Road::new(
+            bikes.try_into().expect("should be right number of bikes"),
+            cars.try_into().expect("should be right number of cars"),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn suggested_counts_round_trip_density() {
+        assert_eq!(suggest_counts(100, 14, 0.3), (15, 6));
+
+        let road = road_from_population::<15, 6, 100, 7, 7>(0.3);
+
+        assert!((road.car_density() - 0.3).abs() < 1e-9);
+        assert!((road.bike_density() - 0.3).abs() < 1e-9);
+    }
+
+    #[test]
+    fn suggested_counts_do_not_exceed_target_density() {
+        let target_density = 0.33;
+        assert_eq!(suggest_counts(100, 14, target_density), (16, 6));
+
+        let road = road_from_population::<16, 6, 100, 7, 7>(target_density);
+
+        assert!(road.car_density() <= target_density);
+        assert!(target_density - road.car_density() < 5.0 / 100.0);
+    }
+
+    #[test]
+    fn evenly_spaced_vehicles_span_the_road() {
+        let bikes = evenly_spaced_bikes::<4, 100>(15).map(|bike| bike.build().unwrap());
+        assert_eq!(bikes.map(|bike| bike.front()), [0, 25, 50, 75]);
+        assert!(bikes
+            .iter()
+            .all(|bike| bike.rectangle_occupation().right == 15));
+
+        let bikes = evenly_spaced_bikes::<3, 100>(15).map(|bike| bike.build().unwrap());
+        assert_eq!(bikes.map(|bike| bike.front()), [0, 33, 66]);
+
+        let cars = evenly_spaced_cars::<1, 100>().map(|car| car.build().unwrap());
+        assert_eq!(cars.map(|car| car.front()), [0]);
+
+        let cars = evenly_spaced_cars::<7, 100>().map(|car| car.build().unwrap());
+        assert_eq!(cars.first().unwrap().front(), 0);
+        assert_eq!(cars.last().unwrap().front(), 84);
+    }
+
+    #[test]
+    fn evenly_spaced_no_vehicles_is_empty() {
+        assert!(evenly_spaced_bikes::<0, 100>(15).is_empty());
+        assert!(evenly_spaced_cars::<0, 100>().is_empty());
+    }
+
+    #[test]
+    fn suggested_counts_are_capped_by_road_size() {
+        assert_eq!(suggest_counts(100, 14, 5.0), (50, 20));
+        assert_eq!(suggest_counts(100, 1, 0.5), (0, 10));
+        assert_eq!(suggest_counts(100, 14, 0.0), (0, 0));
+    }
+}
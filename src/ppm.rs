@@ -0,0 +1,144 @@
+use std::{
+    fs::{create_dir_all, File},
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+
+use crate::road::{Road, Vehicle};
+
+// pixels per cell along either axis
+const CELL_SIZE: usize = 4;
+const MOTOR_LANE_COLOUR: [u8; 3] = [200, 200, 200];
+const BIKE_LANE_COLOUR: [u8; 3] = [230, 240, 220];
+const CAR_COLOUR: [u8; 3] = [214, 39, 40];
+const BIKE_COLOUR: [u8; 3] = [31, 119, 180];
+const PEDESTRIAN_COLOUR: [u8; 3] = [44, 160, 44];
+
+/// The vehicle's colour, dark when stopped and reaching its full colour at
+/// its max speed.
+fn shaded(colour: [u8; 3], speed: isize, speed_max: isize) -> [u8; 3] {
+    let fraction = match 0 < speed_max {
+        true => speed.clamp(0, speed_max) as f32 / speed_max as f32,
+        false => 1.0,
+    };
+    return colour.map(|channel| (channel as f32 * (0.4 + 0.6 * fraction)) as u8);
+}
+
+impl<const B: usize, const C: usize, const L: usize, const BLW: usize, const MLW: usize>
+    Road<B, C, L, BLW, MLW>
+{
+    /// The colour of each cell, lats across and longs downwards as in the
+    /// `Display` of the cells.
+    fn cell_colours(&self) -> Vec<[u8; 3]> {
+        let width = Self::total_width() as usize;
+        let mut colours: Vec<[u8; 3]> = (0..L * width)
+            .map(|index| match index % width < MLW {
+                true => MOTOR_LANE_COLOUR,
+                false => BIKE_LANE_COLOUR,
+            })
+            .collect();
+        for (coord, vehicle) in self.cells_in_range(0..=L as isize - 1, 0..=width as isize - 1) {
+            let colour = match *vehicle {
+                Vehicle::Car(car_id) => {
+                    let car = self.get_car(car_id);
+                    shaded(CAR_COLOUR, car.speed(), car.speed_max())
+                }
+                Vehicle::Bike(bike_id) => {
+                    let bike = self.get_bike(bike_id);
+                    shaded(BIKE_COLOUR, bike.forward_speed, bike.forward_speed_max())
+                }
+                Vehicle::Pedestrian(_) => PEDESTRIAN_COLOUR,
+                Vehicle::ContinuousCar(_) => CAR_COLOUR,
+            };
+            colours[coord.long as usize * width + coord.lat as usize] = colour;
+        }
+        return colours;
+    }
+
+    /// Writes the road as a binary (P6) PPM image with a square of pixels per
+    /// cell, laid out as in `to_svg`. Cars are red and bikes blue, darker the
+    /// further below their max speed they are, continuous cars a flat red and
+    /// pedestrians green.
+    pub fn write_ppm_frame<W: Write>(&self, w: &mut W) -> Result<()> {
+        let width = Self::total_width() as usize;
+        let colours = self.cell_colours();
+        write!(w, "P6\n{} {}\n255\n", width * CELL_SIZE, L * CELL_SIZE)?;
+        for row in colours.chunks(width) {
+            let pixel_row: Vec<u8> = row
+                .iter()
+                .flat_map(|colour| colour.repeat(CELL_SIZE))
+                .collect();
+            for _ in 0..CELL_SIZE {
+                w.write_all(&pixel_row)?;
+            }
+        }
+        return Ok(());
+    }
+
+    /// Writes the road as it is to `frame_00000.ppm` in `dir`, creating it if
+    /// needed, then updates it `iterations` times writing each update to the
+    /// next numbered frame.
+    pub fn render_ppm_sequence(&mut self, iterations: usize, dir: &Path) -> Result<()> {
+        create_dir_all(dir).with_context(|| format!("couldn't create {}", dir.display()))?;
+        for frame in 0..=iterations {
+            if 0 < frame {
+                self.update()?;
+            }
+            let path = dir.join(format!("frame_{:05}.ppm", frame));
+            let file = File::create(&path)
+                .with_context(|| format!("couldn't create {}", path.display()))?;
+            let mut writer = BufWriter::new(file);
+            self.write_ppm_frame(&mut writer)?;
+            writer.flush()?;
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        bike::BikeBuilder,
+        car::CarBuilder,
+        road::{Coord, Road},
+    };
+
+    #[test]
+    fn ppm_frame_has_header_and_a_pixel_block_per_cell() {
+        let bikes = [BikeBuilder::deterministic_default()
+            .with_front_right_at(Coord { lat: 7, long: 3 })
+            .build()
+            .unwrap()];
+        let cars = [CarBuilder::default().with_front_at(9).build().unwrap()];
+        let road = Road::<1, 1, 12, 2, 6>::new(bikes, cars).unwrap();
+
+        let mut ppm = Vec::new();
+        road.write_ppm_frame(&mut ppm).unwrap();
+
+        let header = "P6\n32 48\n255\n";
+        assert!(ppm.starts_with(header.as_bytes()));
+        let pixels = &ppm[header.len()..];
+        assert_eq!(pixels.len(), 32 * 48 * 3);
+        // the stopped car's front left cell, darkened
+        let car_pixel = (9 * 4 * 32) * 3;
+        assert_eq!(pixels[car_pixel..car_pixel + 3], [85, 15, 16]);
+        // an empty cell of the bike lane
+        assert_eq!(pixels[(32 - 1) * 3..32 * 3], [230, 240, 220]);
+    }
+
+    #[test]
+    fn ppm_sequence_writes_numbered_frames() {
+        let cars = [CarBuilder::default().with_front_at(9).build().unwrap()];
+        let mut road = Road::<0, 1, 12, 2, 6>::new([], cars).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+
+        road.render_ppm_sequence(2, dir.path()).unwrap();
+
+        for frame in ["frame_00000.ppm", "frame_00001.ppm", "frame_00002.ppm"] {
+            assert!(dir.path().join(frame).exists());
+        }
+        assert!(!dir.path().join("frame_00003.ppm").exists());
+    }
+}
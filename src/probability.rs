@@ -0,0 +1,85 @@
+use anyhow::{anyhow, Result};
+use rand::distributions::Bernoulli;
+use serde::{Deserialize, Serialize};
+
+/// A probability, checked once on creation to be between 0 and 1 so that
+/// everything taking one can build its `Bernoulli` without failing.
+/// Serialized as the bare number, and checked again when deserialized.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(try_from = "f64", into = "f64")]
+pub struct Probability(f64);
+
+impl Probability {
+    pub const ZERO: Self = Self(0.0);
+    pub const ONE: Self = Self(1.0);
+
+    /// Fails for NaN and anything outside of 0 to 1 inclusive.
+    pub fn new(p: f64) -> Result<Self> {
+        return match (0.0..=1.0).contains(&p) {
+            true => Ok(Self(p)),
+            false => Err(anyhow!(
+                "probability must be between 0 and 1, instead {}",
+                p
+            )),
+        };
+    }
+
+    pub const fn get(self) -> f64 {
+        return self.0;
+    }
+
+    /// The distribution deciding true with this probability.
+    pub fn bernoulli(self) -> Bernoulli {
+        return Bernoulli::new(self.0).expect("probability should be between 0 and 1");
+    }
+}
+
+impl TryFrom<f64> for Probability {
+    type Error = anyhow::Error;
+
+    fn try_from(value: f64) -> Result<Self> {
+        return Self::new(value);
+    }
+}
+
+impl From<Probability> for f64 {
+    fn from(value: Probability) -> Self {
+        return value.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::probability::Probability;
+
+    #[test]
+    fn probability_accepts_boundaries() {
+        assert_eq!(Probability::new(0.0).unwrap(), Probability::ZERO);
+        assert_eq!(Probability::new(1.0).unwrap(), Probability::ONE);
+        assert_eq!(Probability::new(0.25).unwrap().get(), 0.25);
+        assert_eq!(Probability::new(-0.0).unwrap().get(), 0.0);
+    }
+
+    #[test]
+    fn probability_rejects_nan_and_out_of_range() {
+        for p in [
+            f64::NAN,
+            -f64::MIN_POSITIVE,
+            1.0 + f64::EPSILON,
+            -1.0,
+            1.5,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+        ] {
+            assert!(Probability::new(p).is_err(), "{} was accepted", p);
+        }
+    }
+
+    #[test]
+    fn probability_is_checked_when_deserialized() {
+        let p: Probability = serde_json::from_str("0.5").unwrap();
+        assert_eq!(p.get(), 0.5);
+        assert_eq!(serde_json::to_string(&p).unwrap(), "0.5");
+        assert!(serde_json::from_str::<Probability>("1.5").is_err());
+    }
+}
@@ -0,0 +1,53 @@
+use rand::{distributions::Bernoulli, prelude::Distribution, seq::SliceRandom, Rng};
+
+/// Where the random decisions of an update come from. Every `Rng` is a
+/// `RandSource`, while tests can implement it directly to script the
+/// decisions instead of relying on probabilities of 0 or 1.
+pub trait RandSource {
+    /// Whether a random event with the distribution's probability happens.
+    fn decide(&mut self, distribution: &Bernoulli) -> bool;
+
+    /// An index below `len`, `None` when `len` is zero.
+    fn pick(&mut self, len: usize) -> Option<usize>;
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for last in (1..items.len()).rev() {
+            let swap_with = self.pick(last + 1).expect("range should be non-empty");
+            items.swap(last, swap_with);
+        }
+    }
+}
+
+impl<R: Rng> RandSource for R {
+    fn decide(&mut self, distribution: &Bernoulli) -> bool {
+        return distribution.sample(self);
+    }
+
+    fn pick(&mut self, len: usize) -> Option<usize> {
+        return match len {
+            0 => None,
+            _ => Some(self.gen_range(0..len)),
+        };
+    }
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        items.shuffle(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{distributions::Bernoulli, rngs::StdRng, SeedableRng};
+
+    use crate::rand_source::RandSource;
+
+    #[test]
+    fn rng_source_follows_certain_distributions() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        assert!(rng.decide(&Bernoulli::new(1.0).unwrap()));
+        assert!(!rng.decide(&Bernoulli::new(0.0).unwrap()));
+        assert_eq!(rng.pick(0), None);
+        assert_eq!(rng.pick(1), Some(0));
+    }
+}
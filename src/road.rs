@@ -1,21 +1,313 @@
 use std::{
-    collections::HashMap,
+    cmp::{max, min, Reverse},
+    collections::{BTreeMap, BTreeSet, VecDeque},
     fmt::{Display, Formatter},
-    iter::{repeat, zip},
+    fs::File,
+    hash::{Hash, Hasher},
+    io::{BufReader, BufWriter},
+    iter::{repeat, repeat_n, zip},
     ops::RangeInclusive,
+    path::Path,
+    sync::mpsc::Sender,
 };
 
-use rand::{seq::SliceRandom, thread_rng};
+use rand::{distributions::Bernoulli, rngs::StdRng, thread_rng, Rng, SeedableRng};
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
+#[cfg(feature = "parallel")]
 use rayon::prelude::*;
+use rustc_hash::{FxHashMap, FxHasher};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bike::{Bike, BikeBuilder, YStarSelectionStrategy},
+    car::{Car, CarBuilder, Direction, LaneChangeDirection},
+    continuous_car::ContinuousCar,
+    pedestrian::Pedestrian,
+    population::{evenly_spaced_bikes, evenly_spaced_cars},
+    probability::Probability,
+    rand_source::RandSource,
+    simulation::IterationRecord,
+    stats::{SpeedStats, SpeedSummary, Units},
+};
 
-use crate::{bike::Bike, car::Car};
-
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Vehicle {
     Bike(usize),
     Car(usize),
+    Pedestrian(usize),
+    ContinuousCar(usize),
+}
+
+impl Vehicle {
+    /// The index of the vehicle in its road's bikes, cars, pedestrians or
+    /// continuous cars.
+    pub fn id(&self) -> usize {
+        return match self {
+            Vehicle::Bike(id)
+            | Vehicle::Car(id)
+            | Vehicle::Pedestrian(id)
+            | Vehicle::ContinuousCar(id) => *id,
+        };
+    }
+
+    pub fn is_bike(&self) -> bool {
+        return matches!(self, Vehicle::Bike(_));
+    }
+
+    pub fn is_car(&self) -> bool {
+        return matches!(self, Vehicle::Car(_));
+    }
+}
+
+/// A bike or car of a road, for walking over both without caring which.
+#[derive(Debug, Clone, Copy)]
+pub enum VehicleRef<'a> {
+    Bike(&'a Bike),
+    Car(&'a Car),
+}
+
+impl VehicleRef<'_> {
+    /// Forward speed, in cells per update.
+    pub fn speed(&self) -> isize {
+        return match self {
+            VehicleRef::Bike(bike) => bike.forward_speed,
+            VehicleRef::Car(car) => car.speed(),
+        };
+    }
+
+    pub fn length(&self) -> usize {
+        return match self {
+            VehicleRef::Bike(bike) => bike.length(),
+            VehicleRef::Car(car) => car.length(),
+        };
+    }
+}
+
+impl RoadOccupier for VehicleRef<'_> {
+    fn occupied_cells(&self) -> impl Iterator<Item = Coord> {
+        let cells: Vec<Coord> = match self {
+            VehicleRef::Bike(bike) => bike.occupied_cells().collect(),
+            VehicleRef::Car(car) => car.occupied_cells().collect(),
+        };
+        return cells.into_iter();
+    }
+}
+
+/// How the vehicles within a single update phase see each other's moves.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum UpdateMode {
+    /// Every vehicle decides its next state against the road as it was at the
+    /// start of the phase, and all moves are applied together.
+    #[default]
+    Synchronous,
+    /// Vehicles are updated one at a time in id order, each deciding against
+    /// the road as already modified by the vehicles before it.
+    Ordered,
+}
+
+/// One of the road's two lanes, the motor lane on the left and the bike lane
+/// on the right.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lane {
+    Motor,
+    Bike,
+}
+
+/// What a bike does when the lateral position it chose in a synchronous
+/// update has been taken by a bike placed before it. Ordered updates choose
+/// against the road as it is, so never lose their position.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum LateralFallback {
+    /// Stay where it was.
+    #[default]
+    Hold,
+    /// Choose again from the options left on the road as updated so far,
+    /// staying where it was only if there are none.
+    NextBest,
+}
+
+/// A stretch of road over which vehicles may not go faster than `max_speed`.
+/// The range may extend past either end of the road, in which case it wraps.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpeedZone {
+    pub long_range: RangeInclusive<isize>,
+    pub max_speed: isize,
+}
+
+impl SpeedZone {
+    pub fn contains<const L: usize>(&self, long: isize) -> bool {
+        return long_range_contains::<L>(&self.long_range, long);
+    }
+}
+
+/// A hill over a stretch of road, changing the max speed of vehicles with
+/// their front on it by `delta_speed_max`: positive downhill, letting them
+/// coast faster, and negative uphill. Vehicles brake down to a lowered max
+/// speed at once but only build up to a raised one at their usual
+/// acceleration. Like speed zones, the range may wrap.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Grade {
+    pub long_range: RangeInclusive<isize>,
+    pub delta_speed_max: isize,
+}
+
+impl Grade {
+    pub fn contains<const L: usize>(&self, long: isize) -> bool {
+        return long_range_contains::<L>(&self.long_range, long);
+    }
+}
+
+/// What happened in a single update of the road.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize)]
+pub struct StepReport {
+    /// Bikes that crossed between the lanes, see
+    /// `boundary_crossings_last_step`, and cars that moved over a car lane,
+    /// see `set_car_lane_width`.
+    pub lane_changes: usize,
+    /// Times a forward car's front passed a bike's front, however far apart
+    /// they were.
+    pub overtakes: usize,
+    /// Always 0, as an update that would put two vehicles in the same cell
+    /// errors instead of giving a report.
+    pub collisions: usize,
+    /// The mean speed of the cars after the update, `None` without cars.
+    pub mean_car_speed: Option<f64>,
+    /// Bikes and cars that ended the update somewhere other than where they
+    /// started it.
+    pub vehicles_moved: usize,
+}
+
+/// Road wide driving conditions such as rain or ice, applied on top of every
+/// vehicle's own settings. Every max speed is scaled by `speed_factor`, and
+/// vehicles that wouldn't have randomly decelerated anyway do so with
+/// probability `extra_decel_prob`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Conditions {
+    pub speed_factor: f32,
+    pub extra_decel_prob: Probability,
+}
+
+impl Default for Conditions {
+    /// Clear conditions, changing nothing.
+    fn default() -> Self {
+        return Self {
+            speed_factor: 1.0,
+            extra_decel_prob: Probability::ZERO,
+        };
+    }
+}
+
+/// A stretch of road over which the motor lane is narrowed to
+/// `motor_lane_width`, the cells between it and the bike lane being off the
+/// road. The range may extend past either end of the road, in which case it
+/// wraps.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LaneDrop {
+    pub long_range: RangeInclusive<isize>,
+    pub motor_lane_width: usize,
+}
+
+impl LaneDrop {
+    pub fn contains<const L: usize>(&self, long: isize) -> bool {
+        return long_range_contains::<L>(&self.long_range, long);
+    }
+
+    /// Whether the lat is one of the cells removed by this lane drop
+    pub fn removes_lat<const MLW: usize>(&self, lat: isize) -> bool {
+        return self.motor_lane_width as isize <= lat && lat < MLW as isize;
+    }
+
+    /// How far ahead of `long` the lane drop starts, 0 if `long` is already
+    /// within it.
+    fn distance_from<const L: usize>(&self, long: isize) -> usize {
+        return match self.contains::<L>(long) {
+            true => 0,
+            false => (self.long_range.start() - long)
+                .rem_euclid(L as isize)
+                .unsigned_abs(),
+        };
+    }
+
+    /// How far behind `long` the lane drop ends, 0 if `long` is already
+    /// within it.
+    fn distance_behind<const L: usize>(&self, long: isize) -> usize {
+        return match self.contains::<L>(long) {
+            true => 0,
+            false => (long - self.long_range.end())
+                .rem_euclid(L as isize)
+                .unsigned_abs(),
+        };
+    }
+}
+
+/// Where a vehicle is and how fast it is going, enough to draw it as a
+/// rectangle.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct VehicleState {
+    pub front: isize,
+    pub right: isize,
+    pub width: usize,
+    pub length: usize,
+    pub speed: isize,
+    pub group: u8,
+}
+
+/// What `Road::save_json` writes out, each vehicle as the builder that builds
+/// it as it is.
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedRoad {
+    seed: u64,
+    bikes: Vec<BikeBuilder>,
+    cars: Vec<CarBuilder>,
+    pedestrians: Vec<Pedestrian>,
+}
+
+/// A single cell blocked off, such as by a breakdown, until the road has been
+/// updated `remaining_steps` more times.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Incident {
+    pub coord: Coord,
+    pub remaining_steps: usize,
+}
+
+impl Incident {
+    pub fn blocks<const L: usize>(&self, coord: &Coord) -> bool {
+        return self.coord.lat == coord.lat && self.coord.long == coord.long.rem_euclid(L as isize);
+    }
+
+    /// How far ahead of `coord` the incident is in the same lat, `None` if it
+    /// is in a different lat.
+    fn distance_from<const L: usize>(&self, coord: &Coord) -> Option<usize> {
+        return match self.coord.lat == coord.lat {
+            true => Some(
+                (self.coord.long - coord.long)
+                    .rem_euclid(L as isize)
+                    .unsigned_abs(),
+            ),
+            false => None,
+        };
+    }
+
+    /// How far behind `coord` the incident is in the same lat, `None` if it
+    /// is in a different lat.
+    fn distance_behind<const L: usize>(&self, coord: &Coord) -> Option<usize> {
+        return match self.coord.lat == coord.lat {
+            true => Some(
+                (coord.long - self.coord.long)
+                    .rem_euclid(L as isize)
+                    .unsigned_abs(),
+            ),
+            false => None,
+        };
+    }
+}
+
+/// Whether a (possibly wrapping) range of longs on a road of length `L`
+/// contains `long`.
+fn long_range_contains<const L: usize>(long_range: &RangeInclusive<isize>, long: isize) -> bool {
+    let (start, end) = (*long_range.start(), *long_range.end());
+    return (long - start).rem_euclid(L as isize) <= end - start;
 }
 
 #[derive(Eq, PartialEq, Hash, Debug, Copy, Clone)]
@@ -44,10 +336,18 @@ pub trait RoadOccupier {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-// every occupier is a rectangular occupier so it may make sense
-// to do away with the abstraction and just have Bikes and Cars
-// contain RectangleOccupiers to track their position and size
+/// A group of occupiers, such as a convoy or a multi-part obstacle, treated as
+/// a single occupier covering the union of its members' cells.
+impl<T: RoadOccupier> RoadOccupier for &[T] {
+    fn occupied_cells(&self) -> impl Iterator<Item = Coord> {
+        let members: &[T] = self;
+        return members.iter().flat_map(|member| member.occupied_cells());
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+// every occupier is a rectangular occupier, Bikes and Cars both contain one
+// to track their position and size
 pub struct RectangleOccupier {
     pub front: isize,
     pub right: isize,
@@ -56,6 +356,7 @@ pub struct RectangleOccupier {
 }
 
 impl RoadOccupier for RectangleOccupier {
+    /// Yields the cells in the order of `rectangle_occupation`.
     fn occupied_cells(&self) -> impl Iterator<Item = Coord> {
         return rectangle_occupation(self.front, self.right, self.width, self.length);
         // return self
@@ -68,24 +369,62 @@ impl RoadOccupier for RectangleOccupier {
     // Optimisation: can customise the occupier is within and out implementations
 }
 
+/// The cells of a rectangle, lat-major: each lat from left to right, and within
+/// each lat every long from back to front.
+///
+/// The longs aren't wrapped, so a rectangle straddling the start of the road
+/// has a negative back and still yields `width * length` distinct cells, as
+/// long as it is no longer than the road they are wrapped onto.
 pub fn rectangle_occupation(
     front: isize,
     right: isize,
     width: usize,
     length: usize,
 ) -> impl Iterator<Item = Coord> {
+    debug_assert!(
+        right.checked_sub_unsigned(width).is_some() && front.checked_sub_unsigned(length).is_some(),
+        "{}x{} rectangle at front {} and right {} would run off the longs or lats",
+        width,
+        length,
+        front,
+        right
+    );
     return (right.saturating_sub_unsigned(width) + 1..=right)
-        .map(move |lat| {
+        .flat_map(move |lat| {
             zip(
                 repeat(lat),
                 front.saturating_sub_unsigned(length) + 1..=front,
             )
         })
-        .flatten()
+        .map(|(lat, long)| Coord { lat, long });
+}
+
+/// The cells of a rectangle, long-major: each long from back to front, and
+/// within each long every lat from left to right. This is the order to render
+/// the rectangle row by row.
+pub fn rectangle_occupation_long_major(
+    front: isize,
+    right: isize,
+    width: usize,
+    length: usize,
+) -> impl Iterator<Item = Coord> {
+    return (front.saturating_sub_unsigned(length) + 1..=front)
+        .flat_map(move |long| {
+            zip(
+                right.saturating_sub_unsigned(width) + 1..=right,
+                repeat(long),
+            )
+        })
         .map(|(lat, long)| Coord { lat, long });
 }
 
 impl RectangleOccupier {
+    /// The same cells as `occupied_cells`, in the order of
+    /// `rectangle_occupation_long_major`.
+    pub fn occupied_cells_long_major(&self) -> impl Iterator<Item = Coord> {
+        return rectangle_occupation_long_major(self.front, self.right, self.width, self.length);
+    }
+
     pub const fn left(&self) -> isize {
         return self.right.saturating_sub_unsigned(self.width) + 1;
     }
@@ -94,6 +433,20 @@ impl RectangleOccupier {
         return self.front.saturating_sub_unsigned(self.length) + 1;
     }
 
+    /// The number of cells the rectangle takes up.
+    pub const fn area(&self) -> usize {
+        return self.width * self.length;
+    }
+
+    /// The `(lat, long)` midpoint of the rectangle's cells, halfway between
+    /// its left and right lats and between its back and front longs.
+    pub fn center(&self) -> (f64, f64) {
+        return (
+            (self.left() + self.right) as f64 / 2.0,
+            (self.back() + self.front) as f64 / 2.0,
+        );
+    }
+
     pub const fn back_left(&self) -> Coord {
         return Coord {
             lat: self.left(),
@@ -115,20 +468,30 @@ impl RectangleOccupier {
     }
 }
 
-// constants to preallocate size for the hashmap, can be tuned for performance
+// default cells per vehicle to preallocate the hashmap with, see
+// `Road::new_with_allocation` for tuning them
 const CAR_ALLOCATION: usize = 12;
 const BIKE_ALLOCATION: usize = 4;
 
-#[derive(Debug)]
+// the coords are all our own, so there's no need for the default hasher's
+// protection against crafted keys, and Fx is much faster on small integer keys
+type CellMap = FxHashMap<Coord, Vehicle>;
+
+#[derive(Debug, Clone)]
 pub struct RoadCells<const L: usize, const BLW: usize, const MLW: usize> {
-    cells: HashMap<Coord, Vehicle>,
+    cells: CellMap,
+    // the car in each car occupied cell, by lat and then long, for finding the
+    // nearest car behind a cell without scanning every cell in between. Kept
+    // in step with `cells` by `insert_validated` and `remove_validated`
+    car_cells_by_lat: Vec<BTreeMap<isize, usize>>,
 }
 
 #[allow(dead_code)]
 impl<const L: usize, const BLW: usize, const MLW: usize> RoadCells<L, BLW, MLW> {
     fn empty(capacity: usize) -> Self {
         Self {
-            cells: HashMap::with_capacity(capacity),
+            cells: CellMap::with_capacity_and_hasher(capacity, Default::default()),
+            car_cells_by_lat: vec![BTreeMap::new(); Self::total_width()],
         }
     }
 
@@ -163,13 +526,86 @@ impl<const L: usize, const BLW: usize, const MLW: usize> RoadCells<L, BLW, MLW>
         return Ok(self.cells.get(&validated_coord));
     }
 
-    fn insert(&mut self, coord: Coord, vehicle: Vehicle) -> Option<Vehicle> {
+    /// `validate_coord` for one of the vehicle's cells, naming both in the
+    /// error.
+    fn validate_vehicle_coord(coord: Coord, vehicle: Vehicle) -> Result<Coord> {
+        return Self::validate_coord(coord)
+            .with_context(|| format!("{:?} has a cell off the road at {:?}", vehicle, coord));
+    }
+
+    /// Puts the vehicle in the cell, giving back whatever was there before,
+    /// or errors if the cell is off the road.
+    fn insert(&mut self, coord: Coord, vehicle: Vehicle) -> Result<Option<Vehicle>> {
+        let validated_coord = Self::validate_vehicle_coord(coord, vehicle)?;
+        return Ok(self.insert_validated(validated_coord, vehicle));
+    }
+
+    /// Puts the vehicle in the already validated cell, giving back whatever
+    /// was there before.
+    fn insert_validated(&mut self, coord: Coord, vehicle: Vehicle) -> Option<Vehicle> {
+        let found_vehicle = self.cells.insert(coord, vehicle);
+        let band = &mut self.car_cells_by_lat[coord.lat as usize];
+        if found_vehicle.is_some_and(|found_vehicle| found_vehicle.is_car()) {
+            band.remove(&coord.long);
+        }
+        if let Vehicle::Car(car_id) = vehicle {
+            band.insert(coord.long, car_id);
+        }
+        return found_vehicle;
+    }
+
+    fn remove_validated(&mut self, coord: &Coord) -> Option<Vehicle> {
+        let removed = self.cells.remove(coord);
+        if removed.is_some_and(|vehicle| vehicle.is_car()) {
+            self.car_cells_by_lat[coord.lat as usize].remove(&coord.long);
+        }
+        return removed;
+    }
+
+    /// The car in the nearest car occupied cell behind the coord in its lat,
+    /// closer than `maybe_max` (the road length if `None`) and wrapping around
+    /// the road.
+    fn first_car_back(&self, coord: &Coord, maybe_max: Option<usize>) -> Option<usize> {
         return self
-            .cells
-            .insert(Self::validate_coord(coord).unwrap(), vehicle);
+            .first_car_back_with_distance(coord, maybe_max)
+            .map(|(car_id, _)| car_id);
+    }
+
+    /// Like `first_car_back`, along with how many longs back the car's cell
+    /// is.
+    fn first_car_back_with_distance(
+        &self,
+        coord: &Coord,
+        maybe_max: Option<usize>,
+    ) -> Option<(usize, usize)> {
+        let Coord { lat, long } = Self::validate_coord(*coord).expect("lat should be in range");
+        let max_search = match maybe_max {
+            Some(set_max) => set_max as isize,
+            None => L as isize,
+        };
+        let band = &self.car_cells_by_lat[lat as usize];
+
+        let nearest = band
+            .range(..long)
+            .next_back()
+            .or_else(|| band.range(long + 1..).next_back());
+        return match nearest {
+            Some((found_long, car_id)) => {
+                let distance = (long - found_long).rem_euclid(L as isize);
+                (distance < max_search).then_some((*car_id, distance as usize))
+            }
+            // a search longer than the road comes back round to the coord
+            None => band
+                .get(&long)
+                .filter(|_| (L as isize) < max_search)
+                .map(|car_id| (*car_id, L)),
+        };
     }
 
-    fn first_car_back(&self, coord: &Coord, maybe_max: Option<usize>) -> Option<&usize> {
+    /// `first_car_back` the slow way, cell by cell, to check the car cells
+    /// index against.
+    #[cfg(test)]
+    fn first_car_back_by_scan(&self, coord: &Coord, maybe_max: Option<usize>) -> Option<usize> {
         let Coord {
             lat: start_lat,
             long: start_long,
@@ -188,54 +624,47 @@ impl<const L: usize, const BLW: usize, const MLW: usize> RoadCells<L, BLW, MLW>
             })
             .map(|coord| Self::validate_coord(coord).expect("lat should be in range"))
             .filter_map(|coord| self.get(&coord).unwrap())
-            .find_map(|found_vehicle| match found_vehicle {
-                Vehicle::Bike(_) => None,
-                Vehicle::Car(found_car_id) => Some(found_car_id),
-            });
+            .find(|found_vehicle| found_vehicle.is_car())
+            .map(|found_car| found_car.id());
     }
 
+    /// Number of clear cells ahead of the coord in its lat, up to `maybe_max`
+    /// (the road length if `None`). The search wraps around the road at most
+    /// once, so on a road barely longer than a vehicle the vehicle's own back
+    /// can be what's ahead of its front.
     fn front_gap(&self, coord: &Coord, maybe_max: Option<usize>) -> usize {
         let Coord {
             lat: start_lat,
             long: start_long,
         } = Self::validate_coord(*coord).expect("lat value should be okay");
-        let max_search = match maybe_max {
-            Some(set_max) => set_max,
-            None => L,
-        };
+        // any further and the search would only come back round to cells it
+        // had already checked
+        let max_search = min(maybe_max.unwrap_or(L), L);
+
+        return (1..max_search)
+            .find(|d_long| {
+                self.get(&Coord {
+                    lat: start_lat,
+                    long: start_long + *d_long as isize,
+                })
+                .unwrap()
+                .is_some()
+            })
+            .map_or(max_search, |d_long| d_long - 1);
+    }
 
-        let ahead_coord = (1isize..max_search as isize)
-            .map(|d_long| Coord {
-                lat: start_lat,
-                long: start_long + d_long,
+    /// Like `front_gap`, but looking towards decreasing long.
+    fn back_gap(&self, coord: &Coord, maybe_max: Option<usize>) -> usize {
+        let max_search = maybe_max.unwrap_or(L);
+        return (1..max_search)
+            .find(|d_long| {
+                let behind = Coord {
+                    lat: coord.lat,
+                    long: coord.long - *d_long as isize,
+                };
+                return self.get(&behind).unwrap().is_some();
             })
-            .find(|coord| self.get(&coord).unwrap().is_some());
-
-        return match ahead_coord {
-            Some(Coord {
-                long: found_long, ..
-            }) => {
-                let ahead = found_long - (start_long + 1);
-                match ahead.is_negative() {
-                    false => ahead,
-                    true => {
-                        debug_assert!(
-                            ahead.unsigned_abs() < L,
-                            "ahead distance ({}) shouldn't be longer than the road ({}). Started from {:?}, ending on {:?} on road \n{}",
-                            ahead.unsigned_abs(),
-                            L,
-                            coord,
-                            ahead_coord.unwrap(),
-                            self
-                        );
-                        ahead + L as isize
-                    }
-                }
-                .try_into()
-                .expect("positive should be convertible")
-            }
-            None => max_search,
-        };
+            .map_or(max_search, |d_long| d_long - 1);
     }
 
     fn route_width(&self, long: isize) -> usize {
@@ -248,14 +677,36 @@ impl<const L: usize, const BLW: usize, const MLW: usize> RoadCells<L, BLW, MLW>
                     long: validated_long,
                 };
                 debug_assert!(Self::validate_coord(coord).is_ok());
-                self.cells.get(&coord).is_some()
+                self.cells.contains_key(&coord)
             })
             .unwrap_or(Self::total_width())
     }
 
-    fn cells(&self) -> &HashMap<Coord, Vehicle> {
+    fn passable_width_for(&self, long: isize, width: usize) -> bool {
+        let validated_long = long.rem_euclid(L as isize);
+        let mut free_run = 0;
+        for lat in 0..Self::total_width_isize() {
+            free_run = match self.cells.contains_key(&Coord {
+                lat,
+                long: validated_long,
+            }) {
+                true => 0,
+                false => free_run + 1,
+            };
+            if width <= free_run {
+                return true;
+            }
+        }
+        return width == 0;
+    }
+
+    fn cells(&self) -> &CellMap {
         return &self.cells;
     }
+
+    fn shrink_to_fit(&mut self) {
+        self.cells.shrink_to_fit();
+    }
 }
 
 impl<const B: usize, const C: usize, const L: usize, const BLW: usize, const MLW: usize>
@@ -264,46 +715,55 @@ impl<const B: usize, const C: usize, const L: usize, const BLW: usize, const MLW
     type Error = anyhow::Error;
 
     fn try_from(road: &Road<B, C, L, BLW, MLW>) -> Result<Self> {
-        let mut cells = HashMap::with_capacity(C * CAR_ALLOCATION + B * BIKE_ALLOCATION);
+        return Self::from_road(road, C * CAR_ALLOCATION + B * BIKE_ALLOCATION);
+    }
+}
+
+impl<const L: usize, const BLW: usize, const MLW: usize> RoadCells<L, BLW, MLW> {
+    fn from_road<const B: usize, const C: usize>(
+        road: &Road<B, C, L, BLW, MLW>,
+        capacity: usize,
+    ) -> Result<Self> {
+        let mut cells = Self::empty(capacity);
 
         road.iter_car_positions()
             .chain(road.iter_bike_positions())
-            .try_for_each(|(cell, insert_vehicle)| {
-                match cells.insert(Self::validate_coord(cell)?, insert_vehicle) {
+            .chain(road.iter_pedestrian_positions())
+            .chain(road.iter_continuous_car_positions())
+            .try_for_each(
+                |(cell, insert_vehicle)| match cells.insert(cell, insert_vehicle)? {
                     Some(found_vehicle) => Err(anyhow!(
                         "inserted vehicle {:?} collided with found vehicle {:?} at cell {:?}",
-                        cells.get(&cell),
+                        insert_vehicle,
                         found_vehicle,
                         cell
                     )),
                     None => Ok(()),
-                }
-            })?;
+                },
+            )?;
 
-        return Ok(Self { cells });
+        return Ok(cells);
     }
 }
 
 impl<const L: usize, const BLW: usize, const MLW: usize> Display for RoadCells<L, BLW, MLW> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // an empty road still gets room for single digit ids
         let max_id_len = self
             .cells
             .values()
-            .map(|vehicle| match vehicle {
-                Vehicle::Bike(id) => id,
-                Vehicle::Car(id) => id,
-            })
+            .map(|vehicle| vehicle.id())
             .max()
-            .unwrap()
+            .unwrap_or(0)
             .to_string()
             .len();
 
         let max_long_len = (L - 1).to_string().len();
-        let long_buffer = String::from_iter(repeat(' ').take(max_long_len));
+        let long_buffer = String::from_iter(repeat_n(' ', max_long_len));
 
         let mut repr = String::new();
         repr.push_str(&long_buffer);
-        repr.push_str(" ");
+        repr.push(' ');
         for lat_header_val in 0..Self::total_width_isize() {
             let header = format!("{:>1$}", lat_header_val, max_id_len + 2); // plus 2 for space and B/C
             repr.push_str(&header);
@@ -326,7 +786,9 @@ impl<const L: usize, const BLW: usize, const MLW: usize> Display for RoadCells<L
                 {
                     Some(Vehicle::Bike(id)) => format!("B{:1$}", id, max_id_len),
                     Some(Vehicle::Car(id)) => format!("C{:1$}", id, max_id_len),
-                    None => String::from_iter(repeat(' ').take(max_id_len + 1)),
+                    Some(Vehicle::Pedestrian(id)) => format!("P{:1$}", id, max_id_len),
+                    Some(Vehicle::ContinuousCar(id)) => format!("R{:1$}", id, max_id_len),
+                    None => String::from_iter(repeat_n(' ', max_id_len + 1)),
                 };
                 repr.push_str(&cell_repr);
             }
@@ -337,1087 +799,5364 @@ impl<const L: usize, const BLW: usize, const MLW: usize> Display for RoadCells<L
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Road<const B: usize, const C: usize, const L: usize, const BLW: usize, const MLW: usize>
 {
     bikes: [Bike; B],
     cars: [Car; C],
     cells: RoadCells<L, BLW, MLW>,
+    update_mode: UpdateMode,
+    lateral_fallback: LateralFallback,
+    parallel: bool,
+    speed_zones: Vec<SpeedZone>,
+    grades: Vec<Grade>,
+    conditions: Conditions,
+    // `None` in place of a never deciding distribution so that clear
+    // conditions don't draw from the random number generator
+    extra_deceleration: Option<Bernoulli>,
+    motor_lane_speed_limit: Option<isize>,
+    bike_lane_speed_limit: Option<isize>,
+    lane_drops: Vec<LaneDrop>,
+    keep_clear_zones: Vec<RangeInclusive<isize>>,
+    incidents: Vec<Incident>,
+    bus_stops: Vec<isize>,
+    pedestrians: Vec<Pedestrian>,
+    continuous_cars: Vec<ContinuousCar>,
+    seed: u64,
+    rng: StdRng,
+    // number of updates each vehicle has ended stopped
+    bike_waiting_times: [usize; B],
+    car_waiting_times: [usize; C],
+    reaction_lag: bool,
+    brake_light_range: Option<usize>,
+    car_lane_width: Option<usize>,
+    // each car's front gaps as of its last few car updates, oldest first,
+    // as many as it lags by
+    car_gap_histories: [VecDeque<usize>; C],
+    // whether a stopped car has been at each long since it was last free of
+    // cars, for counting stop and go waves
+    stopped_since_free: Vec<bool>,
+    stop_go_waves: usize,
+    // bikes that went from being partly in the motor lane to wholly in the
+    // bike lane or back in the last lateral update
+    boundary_crossings: usize,
+    // the number of cars that have passed a bike with each number of clear
+    // cells between them
+    passes_by_separation: Vec<usize>,
+    // `widening_car_paths` as of the start of the lateral update, in which
+    // they can't change as no car moves
+    lateral_update_widening_car_paths: Option<Vec<RectangleOccupier>>,
 }
 
 #[allow(dead_code)]
 impl<const B: usize, const C: usize, const L: usize, const BLW: usize, const MLW: usize>
     Road<B, C, L, BLW, MLW>
 {
+    /// Frees the cells' spare capacity. The cells never shrink by themselves,
+    /// so this is worth calling once the road occupies far fewer cells than
+    /// it was allocated or has ever occupied, such as after overestimated
+    /// `new_with_allocation` hints, or after fast and so wide cars have been
+    /// brought to a standstill, before keeping the road around for long.
+    /// Occupying more cells again afterwards reallocates.
+    pub fn shrink_to_fit(&mut self) {
+        self.cells.shrink_to_fit();
+    }
+
+    /// Builds a road with a randomly chosen seed, see `reseed` for
+    /// reproducible runs.
     pub fn new(bikes: [Bike; B], cars: [Car; C]) -> Result<Self> {
+        return Self::new_with_allocation(bikes, cars, CAR_ALLOCATION, BIKE_ALLOCATION);
+    }
+
+    /// Like `new`, but preallocating the cells for the expected number of
+    /// cells each car and bike takes up, rather than the defaults that suit
+    /// the default vehicle sizes. The hints only affect performance.
+    pub fn new_with_allocation(
+        bikes: [Bike; B],
+        cars: [Car; C],
+        car_cells_hint: usize,
+        bike_cells_hint: usize,
+    ) -> Result<Self> {
+        let seed = thread_rng().gen();
         let mut road = Self {
             bikes,
             cars,
-            cells: RoadCells::empty(C * CAR_ALLOCATION + B * BIKE_ALLOCATION),
+            cells: RoadCells::empty(0),
+            update_mode: UpdateMode::default(),
+            lateral_fallback: LateralFallback::default(),
+            parallel: cfg!(feature = "parallel"),
+            speed_zones: Vec::new(),
+            grades: Vec::new(),
+            conditions: Conditions::default(),
+            extra_deceleration: None,
+            motor_lane_speed_limit: None,
+            bike_lane_speed_limit: None,
+            lane_drops: Vec::new(),
+            keep_clear_zones: Vec::new(),
+            incidents: Vec::new(),
+            bus_stops: Vec::new(),
+            pedestrians: Vec::new(),
+            continuous_cars: Vec::new(),
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            bike_waiting_times: [0; B],
+            car_waiting_times: [0; C],
+            reaction_lag: false,
+            brake_light_range: None,
+            car_lane_width: None,
+            car_gap_histories: std::array::from_fn(|_| VecDeque::new()),
+            stopped_since_free: vec![false; L],
+            stop_go_waves: 0,
+            boundary_crossings: 0,
+            passes_by_separation: vec![0; BLW + MLW],
+            lateral_update_widening_car_paths: None,
         };
 
-        road.cells = (&road).try_into()?;
+        road.cells = RoadCells::from_road(&road, C * car_cells_hint + B * bike_cells_hint)?;
+        road.record_stop_go();
 
         return Ok(road);
     }
 
-    pub const fn self_total_width(&self) -> isize {
-        return Self::total_width();
+    /// A road of the evenly spaced default vehicles of `run_simulation`, but
+    /// with nothing left to chance: no random deceleration or lateral
+    /// ignorance, and bikes always taking the rightmost of their best
+    /// positions. Its updates are the same whatever its seed.
+    pub fn deterministic() -> Result<Self> {
+        let bikes: Vec<Bike> = evenly_spaced_bikes::<B, L>(Self::total_width() - 1)
+            .iter()
+            .map(|bike| {
+                return bike
+                    .with_lateral_ignorance(Probability::ZERO)
+                    .with_deceleration_prob(Probability::ZERO)
+                    .with_y_star_selection_strategy(YStarSelectionStrategy::Rightmost)
+                    .build();
+            })
+            .collect::<Result<_>>()?;
+        let cars: Vec<Car> = evenly_spaced_cars::<C, L>()
+            .iter()
+            .map(|car| car.with_deceleration_prob(Probability::ZERO).build())
+            .collect::<Result<_>>()?;
+        return Self::new(
+            bikes.try_into().expect("should be right number of bikes"),
+            cars.try_into().expect("should be right number of cars"),
+        );
     }
 
-    pub const fn total_width() -> isize {
-        RoadCells::<L, BLW, MLW>::total_width_isize()
+    /// The seed the road's random number generator was last seeded with.
+    pub const fn seed(&self) -> u64 {
+        return self.seed;
     }
 
-    pub fn car_density(&self) -> f64 {
-        return self.cars.map(|car| car.length).iter().sum::<usize>() as f64 / L as f64;
+    /// Restarts the road's random number generator from `seed`, so the
+    /// updates from this point on are determined by the seed and the current
+    /// state of the road.
+    pub fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = StdRng::seed_from_u64(seed);
     }
 
-    pub fn bike_density(&self) -> f64 {
-        return self.bikes.map(|bike| bike.length()).iter().sum::<usize>() as f64 / L as f64;
+    pub const fn update_mode(&self) -> UpdateMode {
+        return self.update_mode;
     }
 
-    pub fn vehicle_positions_as_string(&self) -> String {
-        return format!(
-            "{{\"cars\":{:?},\"bikes\":{:?}}}",
-            self.cars.map(|car| car.front()),
-            self.bikes.map(|bike| bike.front()),
-        );
+    pub fn set_update_mode(&mut self, update_mode: UpdateMode) {
+        self.update_mode = update_mode;
     }
 
-    pub fn mean_car_speed(&self) -> Option<f64> {
-        return match C {
-            0 => None,
-            _ => Some((self.cars.map(|car| car.speed).iter().sum::<isize>() as f64) / (C as f64)),
-        };
+    pub const fn lateral_fallback(&self) -> LateralFallback {
+        return self.lateral_fallback;
     }
 
-    pub fn mean_bike_speed(&self) -> Option<f64> {
-        return match B {
-            0 => None,
-            _ => Some(
-                (self
-                    .bikes
-                    .map(|bike| bike.forward_speed)
-                    .iter()
-                    .sum::<isize>() as f64)
-                    / (B as f64),
-            ),
-        };
+    pub fn set_lateral_fallback(&mut self, lateral_fallback: LateralFallback) {
+        self.lateral_fallback = lateral_fallback;
     }
 
-    pub fn cells(&self) -> &RoadCells<L, BLW, MLW> {
-        return &self.cells;
+    pub const fn parallel(&self) -> bool {
+        return self.parallel;
     }
 
-    pub fn iter_car_positions(&self) -> impl Iterator<Item = (Coord, Vehicle)> + '_ {
-        return self
-            .cars
-            .iter()
-            .enumerate()
-            .map(|(index, car)| zip(car.occupied_cells(), repeat(index)))
-            .flatten()
-            // not sure if this last line is necessary, as it is clear from the function name
-            // that car ids are being returned
-            .map(|(cell, car_id)| (cell, Vehicle::Car(car_id)));
+    /// Whether the vehicles of each phase are updated across rayon's threads
+    /// (the default) or one after another. Both give the same updates, but
+    /// for only a few vehicles the threading costs more than it saves.
+    /// Without the `parallel` feature the road is always sequential.
+    pub fn set_parallel(&mut self, parallel: bool) {
+        self.parallel = parallel && cfg!(feature = "parallel");
     }
 
-    pub fn iter_bike_positions(&self) -> impl Iterator<Item = (Coord, Vehicle)> + '_ {
-        return self
-            .bikes
-            .iter()
-            .enumerate()
-            .map(|(index, bike)| zip(bike.occupied_cells(), repeat(index)))
-            .flatten()
-            // same criticism as for iter_car_positions
-            .map(|(cell, bike_id)| (cell, Vehicle::Bike(bike_id)));
+    pub const fn reaction_lag(&self) -> bool {
+        return self.reaction_lag;
     }
 
-    pub fn collisions_for(&self, occupier: &impl RoadOccupier) -> Vec<&Vehicle> {
-        return occupier
-            .occupied_cells()
-            .map(|coord| RoadCells::<L, BLW, MLW>::validate_coord(coord).unwrap())
-            .filter_map(|coord| self.cells.get(&coord).unwrap())
-            .collect();
+    /// With a reaction lag, cars decide their speed on the gap in front of
+    /// them as it was an update earlier, so they only notice a leader pulling
+    /// away one update late. They still never drive into the current gap.
+    /// Cars with a longer reaction delay of their own keep it.
+    pub fn set_reaction_lag(&mut self, reaction_lag: bool) {
+        self.reaction_lag = reaction_lag;
+        self.car_gap_histories.iter_mut().for_each(VecDeque::clear);
     }
 
-    pub fn is_collision_for(&self, occupier: &impl RoadOccupier, vehicle: Vehicle) -> bool {
-        return self
-            .collisions_for(occupier)
-            .into_iter()
-            .any(|found_vehicle| *found_vehicle != vehicle);
+    pub const fn brake_light_range(&self) -> Option<usize> {
+        return self.brake_light_range;
     }
 
-    fn bike_lane_contains_occupier(&self, occupier: &impl RoadOccupier) -> bool {
-        return occupier.occupier_is_without(MLW as isize);
-        // // old implementation, can be tested against
-        // occupier
-        //     .occupied_cells()
-        //     .into_iter()
-        //     .map(|(x, y)| x)
-        //     .any(|x| (MLW as isize) < x)
+    /// Turns on the brake light model: a car with a car showing its brake
+    /// light within `range` cells in front of it slows down by one in
+    /// anticipation, rather than waiting for the gap to shrink. `None` turns
+    /// the model off.
+    pub fn set_brake_light_range(&mut self, range: Option<usize>) {
+        self.brake_light_range = range;
     }
 
-    pub fn motor_lane_contains_occupier(&self, occupier: &impl RoadOccupier) -> bool {
-        return occupier.occupier_is_within(MLW as isize);
-        // // old implementation, can be tested against
-        // occupier
-        //     .occupied_cells()
-        //     .into_iter()
-        //     .map(|(x, y)| x)
-        //     .any(|x| x >= MLW as isize)
+    pub const fn car_lane_width(&self) -> Option<usize> {
+        return self.car_lane_width;
     }
 
-    pub fn road_contains_occupier(&self, occupier: &impl RoadOccupier) -> bool {
-        occupier
-            .occupied_cells()
-            .all(|Coord { lat, .. }| 0 <= lat && lat < Road::<B, C, L, BLW, MLW>::total_width())
+    /// Splits the motor lane into lanes `width` lats wide from lat 0, which
+    /// forward cars change between at the start of each car update, or keeps
+    /// every car on its lats with `None`, as by default. A car with its left
+    /// edge on a lane's left edge moves over to the next lane's if that's
+    /// clear beside it and the car's lane change rule accepts the move, see
+    /// `Car::accepts_lane_change_towards`. Cars look left before right, so
+    /// they overtake on the left. Errors unless the lanes fit in the motor
+    /// lane.
+    pub fn set_car_lane_width(&mut self, width: Option<usize>) -> Result<()> {
+        if let Some(width) = width.filter(|width| !(1..=MLW).contains(width)) {
+            return Err(anyhow!(
+                "car lane width must be between 1 and the motor lane width {}, instead {}",
+                MLW,
+                width
+            ));
+        }
+        self.car_lane_width = width;
+        return Ok(());
     }
 
-    fn vehicle_collides(&self, vehicle: Vehicle) -> bool {
-        let occupied_cells: Vec<Coord> = match vehicle {
-            Vehicle::Bike(bike_id) => self
-                .bikes
-                .get(bike_id)
-                .expect("bike_id should be valid")
-                .occupied_cells()
-                .collect(),
-            Vehicle::Car(car_id) => self
-                .cars
-                .get(car_id)
-                .expect("car_id should be valid")
-                .occupied_cells()
-                .collect(),
+    fn brake_light_speed_caps(&self) -> [Option<isize>; C] {
+        let Some(range) = self.brake_light_range else {
+            return [None; C];
         };
+        return self.cars.map(|car| {
+            let ahead = match car.direction() {
+                Direction::Forward => car.front() + 1..=car.front() + range as isize,
+                Direction::Backward => car.front() - range as isize..=car.front() - 1,
+            };
+            let lats = car.left()..=car.left() + car.width() as isize - 1;
+            let leader_braking = self
+                .cells_in_range(ahead, lats)
+                .any(|(_, vehicle)| vehicle.is_car() && self.get_car(vehicle.id()).brake_light());
+            return leader_braking.then(|| max(car.speed() - 1, 0));
+        });
+    }
 
-        return occupied_cells
-            .into_iter()
-            .map(|coord| RoadCells::<L, BLW, MLW>::validate_coord(coord).unwrap())
-            .filter_map(|coord| self.cells.get(&coord).unwrap())
-            .any(|found_vehicle| *found_vehicle != vehicle);
+    fn skipped_acceleration_speed_caps(&self, source: &mut impl RandSource) -> [Option<isize>; C] {
+        return self
+            .cars
+            .map(|car| car.should_skip_acceleration(source).then_some(car.speed()));
     }
 
-    pub fn get_car(&self, car_id: usize) -> &Car {
-        return self.cars.get(car_id).unwrap();
+    /// How many updates late the car with the id reacts to its gap, the
+    /// longer of its own reaction delay and the road's reaction lag.
+    fn reaction_delay_of(&self, car_id: usize) -> usize {
+        return max(
+            self.cars[car_id].reaction_delay(),
+            self.reaction_lag as usize,
+        );
     }
 
-    pub fn get_bike(&self, bike_id: usize) -> &Bike {
-        return self.bikes.get(bike_id).unwrap();
+    /// The speed caps from the gaps each car saw as many updates ago as it
+    /// reacts late by, recording the current gaps for later updates. Cars
+    /// have no cap until they have seen that many updates.
+    fn take_lagged_speed_caps(&mut self) -> [Option<isize>; C] {
+        return std::array::from_fn(|car_id| {
+            let delay = self.reaction_delay_of(car_id);
+            if delay == 0 {
+                return None;
+            }
+            let gap = self.gap_ahead_of_car(&self.cars[car_id]);
+            let history = &mut self.car_gap_histories[car_id];
+            history.push_back(gap);
+            // the road's lag may have been turned off since
+            while delay + 1 < history.len() {
+                history.pop_front();
+            }
+            return match delay < history.len() {
+                true => history.pop_front().map(|gap| gap as isize),
+                false => None,
+            };
+        });
     }
 
-    pub fn first_car_back(&self, coord: &Coord, maybe_max: Option<usize>) -> Option<&Car> {
-        return match self.cells.first_car_back(coord, maybe_max) {
-            Some(car_id) => Some(self.get_car(*car_id)),
-            None => None,
-        };
+    pub fn add_speed_zone(&mut self, speed_zone: SpeedZone) -> Result<()> {
+        if speed_zone.long_range.is_empty() {
+            return Err(anyhow!(
+                "speed zone range {:?} is empty",
+                speed_zone.long_range
+            ));
+        }
+        if speed_zone.max_speed < 1 {
+            return Err(anyhow!(
+                "speed zone max speed must be strictly positive, instead {}",
+                speed_zone.max_speed
+            ));
+        }
+        self.speed_zones.push(speed_zone);
+        return Ok(());
     }
 
-    pub fn is_blocking(&self, coord: &Coord, maybe_max: Option<usize>) -> bool {
+    pub fn speed_zones(&self) -> &[SpeedZone] {
+        return &self.speed_zones;
+    }
+
+    /// The lowest max speed of the speed zones covering `long`, if any.
+    pub fn speed_limit_at(&self, long: isize) -> Option<isize> {
         return self
-            .first_car_back(
-                coord, maybe_max, // potential optimisation: set reasonable max
-            )
-            .is_some_and(|car| {
-                // car.front() is definitely okay except for the first iteration
-                // where whatever value was given to Road::new() is used.
-                let distance = car.front() - (coord.long).rem_euclid(L as isize);
-                return car.next_iteration_potential_speed() < distance;
-            });
+            .speed_zones
+            .iter()
+            .filter(|zone| zone.contains::<L>(long))
+            .map(|zone| zone.max_speed)
+            .min();
     }
 
-    pub fn update(&mut self) -> Result<()> {
-        self.bikes_lateral_update();
-        self.bikes_forward_update()?;
-        self.cars_update()?;
+    pub fn add_grade(&mut self, grade: Grade) -> Result<()> {
+        if grade.long_range.is_empty() {
+            return Err(anyhow!("grade range {:?} is empty", grade.long_range));
+        }
+        self.grades.push(grade);
         return Ok(());
     }
 
-    pub fn bikes_lateral_update(&mut self) {
-        let shuffled_new_bikes = {
-            let mut rng = thread_rng();
-            let mut next_bikes: Vec<(usize, Bike)> =
-                self.next_bikes_lateral().into_iter().enumerate().collect();
-            next_bikes.shuffle(&mut rng);
-            next_bikes
-        };
-
-        self.wipe_bikes_from_cells();
-        for (bike_id, new_bike) in shuffled_new_bikes {
-            let bike_to_occupy = match self.collisions_for(&new_bike).is_empty() {
-                true => new_bike,
-                false => *self.bikes.get(bike_id).expect("should be a valid bike id"),
-            };
-            bike_to_occupy.occupied_cells().for_each(|occupied_cell| {
-                self.cells.insert(occupied_cell, Vehicle::Bike(bike_id));
-            });
-            self.bikes[bike_id] = bike_to_occupy;
-        }
+    pub fn grades(&self) -> &[Grade] {
+        return &self.grades;
     }
 
-    pub fn bikes_forward_update(&mut self) -> Result<()> {
-        // should be okay as there can be no collisions when moving forwards?
-        // ^ check this ^
-        let next_bikes = self.next_bikes_forward();
-        self.wipe_bikes_from_cells();
-        next_bikes
+    /// The total change in max speed from the grades covering `long`, 0 on
+    /// the flat.
+    pub fn grade_at(&self, long: isize) -> isize {
+        return self
+            .grades
             .iter()
-            .enumerate()
-            .map(|(index, bike)| zip(bike.occupied_cells(), repeat(index)))
-            .flatten()
-            // same criticism as for iter_car_positions
-            .map(|(cell, bike_id)| (RoadCells::<L, BLW, MLW>::validate_coord(cell).unwrap(), Vehicle::Bike(bike_id)))
-            .try_for_each(|(validated_cell, insert_vehicle)| {
-                match self.cells.cells.insert(validated_cell, insert_vehicle) {
-                    Some(found_vehicle) => Err(anyhow!(
-                        "inserted vehicle {:?} collided with found vehicle {:?} at cell {:?}. Full cells {}",
-                        self.cells.cells.get(&validated_cell),
-                        found_vehicle,
-                        validated_cell,
-                        self.cells
-                    )),
-                    None => Ok(()),
-                }
-            })?;
-        self.bikes = next_bikes;
+            .filter(|grade| grade.contains::<L>(long))
+            .map(|grade| grade.delta_speed_max)
+            .sum();
+    }
+
+    pub const fn conditions(&self) -> Conditions {
+        return self.conditions;
+    }
+
+    /// Changes the driving conditions from the next update. The speed factor
+    /// must be positive.
+    pub fn set_conditions(&mut self, conditions: Conditions) -> Result<()> {
+        if !(conditions.speed_factor.is_finite() && 0.0 < conditions.speed_factor) {
+            return Err(anyhow!(
+                "speed factor must be finite and strictly positive, instead {}",
+                conditions.speed_factor
+            ));
+        }
+        self.conditions = conditions;
+        self.extra_deceleration = (Probability::ZERO < conditions.extra_decel_prob)
+            .then(|| conditions.extra_decel_prob.bernoulli());
         return Ok(());
-        // let shuffled_new_bikes = {
-        //     let mut rng = thread_rng();
-        //     let mut next_bikes: Vec<(usize, Bike)> =
-        //         self.next_bikes_forward().into_iter().enumerate().collect();
-        //     next_bikes.shuffle(&mut rng);
-        //     next_bikes
-        // };
+    }
 
-        // self.replace_bikes(shuffled_new_bikes);
+    /// A vehicle's max speed under the road's conditions, rounded and kept
+    /// between 1 and the max speed itself so that conditions only ever slow
+    /// vehicles down and never stop them altogether.
+    pub fn conditions_speed_max(&self, speed_max: isize) -> isize {
+        let scaled = (speed_max as f32 * self.conditions.speed_factor).round() as isize;
+        return scaled.clamp(min(1, speed_max), speed_max);
     }
 
-    // fn replace_bikes(&mut self, new_bikes: Vec<(usize, Bike)>) {
-    //     // no need for this function if it's just being used in the one place
-    //     self.wipe_bikes_from_cells();
-    //     for (bike_id, new_bike) in new_bikes {
-    //         let bike_to_occupy = match self.collisions_for(&new_bike).is_empty() {
-    //             true => new_bike,
-    //             false => *self.bikes.get(bike_id).expect("should be a valid bike id"),
-    //         };
-    //         bike_to_occupy.occupied_cells().for_each(|occupied_cell| {
-    //             self.cells
-    //                 .cells
-    //                 .insert(occupied_cell, Vehicle::Bike(bike_id));
-    //         });
-    //         self.bikes[bike_id] = bike_to_occupy;
-    //     }
-    // }
+    /// A vehicle's max speed under the road's conditions with its front on
+    /// `long`, changed by the grades there but never below 1, so vehicles
+    /// always make it up a hill.
+    pub fn graded_speed_max(&self, speed_max: isize, long: isize) -> isize {
+        return max(
+            min(1, speed_max),
+            self.conditions_speed_max(speed_max) + self.grade_at(long),
+        );
+    }
 
-    // fn replace_bikes_with(&mut self, new_bikes: Vec<(usize, Bike)>) {
-    //     self.wipe_bikes_from_cells();
-    //     for (bike_id, new_bike) in new_bikes {
-    //         let bike_to_occupy = match self.collisions_for(&new_bike).is_empty() {
-    //             true => new_bike,
-    //             false => *self.bikes.get(bike_id).expect("should be a valid bike id"),
-    //         };
-    //         bike_to_occupy.occupied_cells().for_each(|occupied_cell| {
-    //             self.cells
-    //                 .cells
-    //                 .insert(occupied_cell, Vehicle::Bike(bike_id));
-    //         });
-    //         self.bikes[bike_id] = bike_to_occupy;
-    //     }
-    // }
+    /// Whether a vehicle decelerates, given whether it decided to by itself.
+    fn decelerates(&self, vehicle_decelerates: bool, source: &mut impl RandSource) -> bool {
+        return vehicle_decelerates
+            || self
+                .extra_deceleration
+                .is_some_and(|extra_deceleration| source.decide(&extra_deceleration));
+    }
 
-    fn wipe_bikes_from_cells(&mut self) {
-        self.bikes
-            .iter()
-            .map(|bike| bike.occupied_cells())
-            .flatten()
-            .map(|cell| RoadCells::<L, BLW, MLW>::validate_coord(cell).unwrap())
-            .for_each(|bike_cell| {
-                let removed = self.cells.cells.remove(&bike_cell);
-                debug_assert!(
-                    removed.is_some_and(|vehicle| match vehicle {
-                        Vehicle::Bike(_) => true,
-                        Vehicle::Car(_) => false,
-                    }),
-                    "expected to find a bike at this location ({:?})",
-                    bike_cell
-                );
-            })
+    /// Limits the speed of any vehicle partly in the motor lane along the
+    /// whole road, on top of any speed zones, or lifts the limit with `None`.
+    pub fn set_motor_lane_speed_limit(&mut self, limit: Option<isize>) -> Result<()> {
+        self.motor_lane_speed_limit = Self::checked_lane_speed_limit(limit)?;
+        return Ok(());
     }
 
-    fn wipe_cars_from_cells(&mut self) {
-        self.cars
-            .iter()
-            .map(|car| car.occupied_cells())
-            .flatten()
-            .map(|cell| RoadCells::<L, BLW, MLW>::validate_coord(cell).unwrap())
-            .for_each(|car_cell| {
-                let removed = self.cells.cells.remove(&car_cell);
-                debug_assert!(
-                    removed.is_some_and(|vehicle| match vehicle {
-                        Vehicle::Car(_) => true,
-                        Vehicle::Bike(_) => false,
-                    }),
-                    "expected to find a car at this location ({:?})",
-                    car_cell
-                );
-            })
+    /// Like `set_motor_lane_speed_limit` for the bike lane.
+    pub fn set_bike_lane_speed_limit(&mut self, limit: Option<isize>) -> Result<()> {
+        self.bike_lane_speed_limit = Self::checked_lane_speed_limit(limit)?;
+        return Ok(());
     }
 
-    fn next_bikes_lateral(&self) -> [Bike; B] {
-        // parallelise me for optimisation
-        return self
-            .bikes
-            .par_iter()
-            .enumerate()
-            .map(|(bike_id, bike)| bike.lateral_update(bike_id, self))
-            .collect::<Vec<Bike>>()
-            .try_into()
-            .expect("array length should be okay due to const generic B");
+    fn checked_lane_speed_limit(limit: Option<isize>) -> Result<Option<isize>> {
+        return match limit {
+            Some(max_speed) if max_speed < 1 => Err(anyhow!(
+                "lane speed limit must be strictly positive, instead {}",
+                max_speed
+            )),
+            _ => Ok(limit),
+        };
     }
 
-    fn next_bikes_forward(&self) -> [Bike; B] {
-        return self
-            .bikes
-            .par_iter()
-            .map(|bike| bike.forward_update(self))
-            .collect::<Vec<Bike>>()
-            .try_into()
-            .expect("array length should be okay due to const generic B");
+    pub const fn motor_lane_speed_limit(&self) -> Option<isize> {
+        return self.motor_lane_speed_limit;
     }
 
-    pub fn cars_update(&mut self) -> Result<()> {
-        let next_cars = self.next_cars();
-        self.wipe_cars_from_cells();
-        next_cars
-            .iter()
-            .enumerate()
-            .map(|(index, car)| zip(car.occupied_cells(), repeat(index)))
-            .flatten()
-            // same criticism as for iter_car_positions
-            .map(|(cell, car_id)| (RoadCells::<L, BLW, MLW>::validate_coord(cell).unwrap(), Vehicle::Car(car_id)))
-            .try_for_each(|(validated_cell, insert_vehicle)| {
-                match self.cells.cells.insert(validated_cell, insert_vehicle) {
-                    Some(found_vehicle) => Err(anyhow!(
-                        "inserted vehicle {:?} collided with found vehicle {:?} at cell {:?}. Full cells {}\n",
-                        self.cells.cells.get(&validated_cell),
-                        found_vehicle,
-                        validated_cell,
-                        self.cells
-                    )),
-                    None => Ok(()),
-                }
-            })?;
-        self.cars = next_cars;
+    pub const fn bike_lane_speed_limit(&self) -> Option<isize> {
+        return self.bike_lane_speed_limit;
+    }
+
+    /// The lowest speed limit of the lanes the occupation is at least partly
+    /// in, if any of them have one.
+    pub fn lane_speed_limit_for(&self, occupation: &RectangleOccupier) -> Option<isize> {
+        let motor_limit = self
+            .motor_lane_speed_limit
+            .filter(|_| occupation.left() < MLW as isize);
+        let bike_limit = self
+            .bike_lane_speed_limit
+            .filter(|_| MLW as isize <= occupation.right);
+        return motor_limit.into_iter().chain(bike_limit).min();
+    }
+
+    /// Adds a stop that buses pull up at with their front on `long`.
+    pub fn add_bus_stop(&mut self, long: isize) -> Result<()> {
+        if !(0..L as isize).contains(&long) {
+            return Err(anyhow!(
+                "bus stop long must be within 0 to {}, instead {}",
+                L - 1,
+                long
+            ));
+        }
+        self.bus_stops.push(long);
         return Ok(());
     }
 
-    fn next_cars(&self) -> [Car; C] {
-        let cars_vec: Vec<Car> = self
-            .cars
-            .par_iter()
-            .enumerate()
-            .map(|(car_id, car)| car.update(self, car_id))
-            .collect();
-        return cars_vec.try_into().unwrap();
+    pub fn bus_stops(&self) -> &[isize] {
+        return &self.bus_stops;
     }
 
-    pub fn front_gap(&self, occupation: &RectangleOccupier) -> Option<usize> {
-        occupation
-            .front_cells()
-            .map(|coord| self.cells.front_gap(&coord, None))
-            .min()
+    pub fn is_bus_stop(&self, long: isize) -> bool {
+        return self.bus_stops.contains(&long.rem_euclid(L as isize));
     }
 
-    pub(crate) fn route_width(&self, long: isize) -> usize {
-        return self.cells.route_width(long);
+    /// How far ahead of `long` the next bus stop is, not counting one at
+    /// `long` itself, so a bus leaving a stop heads for the one after.
+    pub fn distance_to_next_bus_stop(&self, long: isize) -> Option<usize> {
+        return self
+            .bus_stops
+            .iter()
+            .map(|stop| ((stop - long - 1).rem_euclid(L as isize) + 1).unsigned_abs())
+            .min();
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use std::collections::HashSet;
+    /// Narrows the motor lane over the lane drop's longs, which may wrap. The
+    /// cells it removes must be empty.
+    pub fn add_lane_drop(&mut self, lane_drop: LaneDrop) -> Result<()> {
+        if lane_drop.long_range.is_empty() {
+            return Err(anyhow!(
+                "lane drop range {:?} is empty",
+                lane_drop.long_range
+            ));
+        }
+        if MLW < lane_drop.motor_lane_width {
+            return Err(anyhow!(
+                "lane drop width {} is wider than the motor lane {}",
+                lane_drop.motor_lane_width,
+                MLW
+            ));
+        }
+        let removed_lats = lane_drop.motor_lane_width as isize..=MLW as isize - 1;
+        if let Some((coord, vehicle)) = self
+            .cells_in_range(lane_drop.long_range.clone(), removed_lats)
+            .next()
+        {
+            return Err(anyhow!(
+                "cannot drop lanes over {:?}, occupied by {:?}",
+                coord,
+                vehicle
+            ));
+        }
+        self.lane_drops.push(lane_drop);
+        return Ok(());
+    }
 
-    use proptest::{prop_assert_eq, proptest};
+    pub fn lane_drops(&self) -> &[LaneDrop] {
+        return &self.lane_drops;
+    }
 
-    use crate::{
-        bike::{Bike, BikeBuilder},
-        car::{Car, CarBuilder},
-        proptest_defs::arb_rectangle_occupier,
-        road::{Coord, RectangleOccupier, Road, RoadOccupier, Vehicle},
-    };
+    /// Marks the longs as a junction that cars must not stop in, so a car
+    /// only enters the zone if the rest of it ahead is clear. The range may
+    /// extend past either end of the road, in which case it wraps.
+    pub fn add_keep_clear(&mut self, long_range: RangeInclusive<isize>) -> Result<()> {
+        if long_range.is_empty() {
+            return Err(anyhow!("keep clear range {:?} is empty", long_range));
+        }
+        self.keep_clear_zones.push(long_range);
+        return Ok(());
+    }
 
-    #[test]
-    fn bike_is_on_road() {
-        let bikes = [BikeBuilder::default().with_lateral_ignorance(0.0).unwrap()]
-            .map(|builder| builder.try_into().unwrap());
-        let road = Road::<1, 0, 20, 3, 3>::new(bikes, []).unwrap();
+    pub fn keep_clear_zones(&self) -> &[RangeInclusive<isize>] {
+        return &self.keep_clear_zones;
+    }
 
-        let new_position = road.get_bike(0).rectangle_occupation();
+    /// Whether the car is at least partly within a keep clear zone with a
+    /// vehicle somewhere in its lats between its front and where its front
+    /// would have to be for its back to be past the end of the zone, so it
+    /// might be left standing in the zone.
+    pub(crate) fn could_stop_in_keep_clear(&self, car: &Car, car_id: usize) -> bool {
+        let lats: BTreeSet<isize> = car.occupied_cells().map(|coord| coord.lat).collect();
+        let length = car.length() as isize;
+        let occupation = car.rectangle_occupation();
+        return self
+            .keep_clear_zones
+            .iter()
+            .filter(|zone| {
+                occupation
+                    .length_iterator()
+                    .any(|long| long_range_contains::<L>(zone, long))
+            })
+            .any(|zone| {
+                // how far the back still has to go to get past the zone's far end
+                let remaining = match car.direction() {
+                    Direction::Forward => zone.end() + length - car.front(),
+                    Direction::Backward => car.front() + length - zone.start(),
+                }
+                .rem_euclid(L as isize);
+                return (1..=remaining)
+                    .flat_map(|distance| zip(lats.iter(), repeat(car.long_ahead(distance))))
+                    .filter_map(|(lat, long)| {
+                        self.cells
+                            .get(&Coord { lat: *lat, long })
+                            .expect("lat should be in range")
+                    })
+                    .any(|found_vehicle| *found_vehicle != Vehicle::Car(car_id));
+            });
+    }
 
-        assert!(road.motor_lane_contains_occupier(&new_position));
+    /// The width of the motor lane at `long`, the narrowest of any lane drops
+    /// covering it.
+    pub fn motor_lane_width_at(&self, long: isize) -> usize {
+        return self
+            .lane_drops
+            .iter()
+            .filter(|lane_drop| lane_drop.contains::<L>(long))
+            .map(|lane_drop| lane_drop.motor_lane_width)
+            .min()
+            .unwrap_or(MLW);
     }
 
-    #[test]
-    fn bike_is_on_road_after_update() {
-        let bikes = [BikeBuilder::default().with_lateral_ignorance(0.0).unwrap()]
-            .map(|builder| builder.try_into().unwrap());
-        let mut road = Road::<1, 0, 20, 3, 3>::new(bikes, []).unwrap();
+    /// Blocks the cell at `long` and `lat` for the next `duration` updates,
+    /// after which it is cleared again. The cell must be empty.
+    pub fn add_timed_incident(&mut self, long: isize, lat: isize, duration: usize) -> Result<()> {
+        let coord = RoadCells::<L, BLW, MLW>::validate_coord(Coord { lat, long })?;
+        if duration == 0 {
+            return Err(anyhow!(
+                "incident at {:?} must last at least one step",
+                coord
+            ));
+        }
+        if let Some(vehicle) = self.cells.get(&coord)? {
+            return Err(anyhow!(
+                "cannot place incident at {:?}, occupied by {:?}",
+                coord,
+                vehicle
+            ));
+        }
+        self.incidents.push(Incident {
+            coord,
+            remaining_steps: duration,
+        });
+        return Ok(());
+    }
 
-        road.update().unwrap();
+    pub fn incidents(&self) -> &[Incident] {
+        return &self.incidents;
+    }
 
-        let new_position = road.get_bike(0).rectangle_occupation();
+    /// Puts a pedestrian on the road, which vehicles treat like any other
+    /// vehicle in their way, returning its id.
+    pub fn add_pedestrian(&mut self, pedestrian: Pedestrian) -> Result<usize> {
+        let coord = RoadCells::<L, BLW, MLW>::validate_coord(Coord {
+            lat: pedestrian.lat(),
+            long: pedestrian.long(),
+        })?;
+        if let Some(vehicle) = self.cells.get(&coord)? {
+            return Err(anyhow!(
+                "cannot place pedestrian at {:?}, occupied by {:?}",
+                coord,
+                vehicle
+            ));
+        }
+        let pedestrian_id = self.pedestrians.len();
+        self.cells
+            .insert(coord, Vehicle::Pedestrian(pedestrian_id))?;
+        self.pedestrians.push(pedestrian);
+        return Ok(pedestrian_id);
+    }
 
-        assert!(road.road_contains_occupier(&new_position));
+    /// Every pedestrian added to the road, including those that have finished
+    /// crossing, indexed by id.
+    pub fn pedestrians(&self) -> &[Pedestrian] {
+        return &self.pedestrians;
     }
 
-    #[test]
-    fn bikes_same_size_after_update() {
-        let bikes = [BikeBuilder::default().with_lateral_ignorance(0.0).unwrap()]
-            .map(|builder| builder.try_into().unwrap());
-        let mut road = Road::<1, 0, 20, 3, 3>::new(bikes, []).unwrap();
-        let original_dims = road.bikes.map(|bike| {
-            let RectangleOccupier { width, length, .. } = bike.rectangle_occupation();
-            return (width, length);
-        });
-        road.update().unwrap();
-        let new_dims = road.bikes.map(|bike| {
-            let RectangleOccupier { width, length, .. } = bike.rectangle_occupation();
-            return (width, length);
-        });
+    fn pedestrians_update(&mut self) -> Result<()> {
+        for pedestrian_id in 0..self.pedestrians.len() {
+            let current = self.pedestrians[pedestrian_id];
+            let next = current.update(self, pedestrian_id);
+            self.replace_cells(Vehicle::Pedestrian(pedestrian_id), &current, &next)?;
+            self.pedestrians[pedestrian_id] = next;
+        }
+        return Ok(());
+    }
 
-        assert_eq!(original_dims, new_dims);
+    /// Puts a continuous car on the road, in the cells of its floored front,
+    /// returning its id. Other vehicles stop behind it as they would behind a
+    /// car, but cars changing lanes don't look back for it and it isn't
+    /// saved by `save_json`.
+    pub fn add_continuous_car(&mut self, continuous_car: ContinuousCar) -> Result<usize> {
+        let continuous_car_id = self.continuous_cars.len();
+        let vehicle = Vehicle::ContinuousCar(continuous_car_id);
+        let cells = continuous_car
+            .occupied_cells()
+            .map(|coord| RoadCells::<L, BLW, MLW>::validate_vehicle_coord(coord, vehicle))
+            .collect::<Result<Vec<_>>>()?;
+        if let Some(found_vehicle) = cells.iter().find_map(|coord| self.cells.cells.get(coord)) {
+            return Err(anyhow!(
+                "cannot place continuous car at {:?}, occupied by {:?}",
+                continuous_car.rectangle_occupation(),
+                found_vehicle
+            ));
+        }
+        for coord in cells {
+            self.cells.insert_validated(coord, vehicle);
+        }
+        self.continuous_cars.push(continuous_car);
+        return Ok(continuous_car_id);
     }
 
-    #[test]
-    fn single_bike_multiple_updates_work() {
-        let bikes = [BikeBuilder::default()].map(|builder| builder.try_into().unwrap());
-        let mut road = Road::<1, 0, 20, 3, 3>::new(bikes, []).unwrap();
+    /// Every continuous car added to the road, indexed by id.
+    pub fn continuous_cars(&self) -> &[ContinuousCar] {
+        return &self.continuous_cars;
+    }
 
-        for iter_num in 0u16..1000 {
-            println!("iteration #{:?}", iter_num);
-            road.update().unwrap();
+    fn continuous_cars_update(&mut self) -> Result<()> {
+        for continuous_car_id in 0..self.continuous_cars.len() {
+            let current = self.continuous_cars[continuous_car_id];
+            let next = current.update(self);
+            self.replace_cells(Vehicle::ContinuousCar(continuous_car_id), &current, &next)?;
+            self.continuous_cars[continuous_car_id] = next;
         }
+        return Ok(());
+    }
 
-        let new_position = road.get_bike(0).rectangle_occupation();
+    fn count_down_incidents(&mut self) {
+        self.incidents.retain_mut(|incident| {
+            incident.remaining_steps -= 1;
+            return 0 < incident.remaining_steps;
+        });
+    }
 
-        assert!(road.road_contains_occupier(&new_position));
+    /// Whether the coord is on the road, i.e. within its width and not removed
+    /// by a lane drop or blocked by an incident.
+    pub fn road_contains_coord(&self, coord: &Coord) -> bool {
+        let Coord { lat, long } = *coord;
+        return 0 <= lat
+            && lat < Self::total_width()
+            && !(self.motor_lane_width_at(long) as isize <= lat && lat < MLW as isize)
+            && !self
+                .incidents
+                .iter()
+                .any(|incident| incident.blocks::<L>(coord));
     }
 
-    #[test]
+    pub const fn self_total_width(&self) -> isize {
+        return Self::total_width();
+    }
+
+    pub const fn total_width() -> isize {
+        RoadCells::<L, BLW, MLW>::total_width_isize()
+    }
+
+    /// The number of cells on the road, including any removed by lane drops.
+    pub const fn total_cells(&self) -> usize {
+        return L * (BLW + MLW);
+    }
+
+    /// The index of the coord in a dense buffer of the road's cells laid out
+    /// as `long * total_width + lat`, wrapping the long onto the road first.
+    pub fn coord_to_index(&self, coord: Coord) -> Result<usize> {
+        let Coord { lat, long } = RoadCells::<L, BLW, MLW>::validate_coord(coord)?;
+        return Ok((long * Self::total_width() + lat) as usize);
+    }
+
+    /// The coord at `index` of the layout used by `coord_to_index`.
+    pub fn index_to_coord(&self, index: usize) -> Result<Coord> {
+        if self.total_cells() <= index {
+            return Err(anyhow!(
+                "index {} is past the road's {} cells",
+                index,
+                self.total_cells()
+            ));
+        }
+        let total_width = Self::total_width() as usize;
+        return Ok(Coord {
+            lat: (index % total_width) as isize,
+            long: (index / total_width) as isize,
+        });
+    }
+
+    /// The summed length of the cars over the road length `L`, i.e. the share
+    /// of the road's length cars would fill if lined up bumper to bumper. The
+    /// cars' widths, and so their speeds, don't count towards it.
+    pub fn car_density(&self) -> f64 {
+        return self.cars.map(|car| car.length()).iter().sum::<usize>() as f64 / L as f64;
+    }
+
+    /// The summed length of the bikes over the road length `L`, measured the
+    /// same way as `car_density`.
+    pub fn bike_density(&self) -> f64 {
+        return self.bikes.map(|bike| bike.length()).iter().sum::<usize>() as f64 / L as f64;
+    }
+
+    /// Every bike then every car, each by id. Pedestrians aren't included.
+    pub fn iter_vehicles(&self) -> impl Iterator<Item = (Vehicle, VehicleRef<'_>)> + '_ {
+        let bikes = self
+            .bikes
+            .iter()
+            .enumerate()
+            .map(|(bike_id, bike)| (Vehicle::Bike(bike_id), VehicleRef::Bike(bike)));
+        let cars = self
+            .cars
+            .iter()
+            .enumerate()
+            .map(|(car_id, car)| (Vehicle::Car(car_id), VehicleRef::Car(car)));
+        return bikes.chain(cars);
+    }
+
+    /// The car ids in order of increasing front, wrapped onto the road, so
+    /// from the back of the road to the front. Ties keep the lower id first.
+    pub fn cars_by_position(&self) -> Vec<usize> {
+        let mut car_ids: Vec<usize> = (0..C).collect();
+        car_ids.sort_by_key(|car_id| self.cars[*car_id].front().rem_euclid(L as isize));
+        return car_ids;
+    }
+
+    /// The bike ids in the same order as `cars_by_position`.
+    pub fn bikes_by_position(&self) -> Vec<usize> {
+        let mut bike_ids: Vec<usize> = (0..B).collect();
+        bike_ids.sort_by_key(|bike_id| self.bikes[*bike_id].front().rem_euclid(L as isize));
+        return bike_ids;
+    }
+
+    /// The number of bikes on the motor lane that can't move over onto the
+    /// bike lane in the next lateral update.
+    pub fn bikes_stuck_in_motor_lane(&self) -> usize {
+        return self
+            .bikes
+            .iter()
+            .enumerate()
+            .filter(|(bike_id, bike)| bike.is_stuck_in_motor_lane(self, *bike_id))
+            .count();
+    }
+
+    /// The id and car of the slowest car, the lowest id of any tied.
+    pub fn slowest_car(&self) -> Option<(usize, &Car)> {
+        // min_by_key keeps the first of equal minimums
+        return self
+            .cars
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, car)| car.speed());
+    }
+
+    /// The id and car of the fastest car, the lowest id of any tied.
+    pub fn fastest_car(&self) -> Option<(usize, &Car)> {
+        return self
+            .cars
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, car)| Reverse(car.speed()));
+    }
+
+    /// Whether any car has come to a standstill.
+    pub fn is_jammed(&self) -> bool {
+        return self.cars.iter().any(|car| car.speed() == 0);
+    }
+
+    pub fn car_states(&self) -> Vec<VehicleState> {
+        return self
+            .cars
+            .iter()
+            .map(|car| VehicleState {
+                front: car.front(),
+                right: car.left() + car.width() as isize - 1,
+                width: car.width(),
+                length: car.length(),
+                speed: car.speed(),
+                group: car.group(),
+            })
+            .collect();
+    }
+
+    pub fn bike_states(&self) -> Vec<VehicleState> {
+        return self
+            .bikes
+            .iter()
+            .map(|bike| {
+                let occupation = bike.rectangle_occupation();
+                return VehicleState {
+                    front: occupation.front,
+                    right: occupation.right,
+                    width: occupation.width,
+                    length: occupation.length,
+                    speed: bike.forward_speed,
+                    group: bike.group(),
+                };
+            })
+            .collect();
+    }
+
+    /// Writes the road's vehicles, pedestrians and seed to `path` as JSON, for
+    /// picking the run back up later with `load_json`. The rest of the road's
+    /// setup, such as its zones and bus stops, isn't saved, and neither are
+    /// its statistics.
+    pub fn save_json(&self, path: &Path) -> Result<()> {
+        let saved = SavedRoad {
+            seed: self.seed,
+            bikes: self.bikes.iter().map(BikeBuilder::from).collect(),
+            cars: self.cars.iter().map(CarBuilder::from).collect(),
+            pedestrians: self.pedestrians.clone(),
+        };
+        let file = File::create(path)
+            .with_context(|| format!("failed to create road file {}", path.display()))?;
+        serde_json::to_writer(BufWriter::new(file), &saved)
+            .with_context(|| format!("failed to write road to {}", path.display()))?;
+        return Ok(());
+    }
+
+    /// Builds a road from the JSON written by `save_json`, with otherwise
+    /// default settings. The random number generator is restarted from the
+    /// saved seed, so a loaded road won't make the same decisions as the
+    /// saved one would have from the same point unless that was also just
+    /// reseeded.
+    pub fn load_json(path: &Path) -> Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("failed to open road file {}", path.display()))?;
+        let saved: SavedRoad = serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("failed to read road from {}", path.display()))?;
+
+        let bikes: Vec<Bike> = saved
+            .bikes
+            .iter()
+            .map(BikeBuilder::build_coasting)
+            .collect::<Result<_>>()?;
+        let cars: Vec<Car> = saved
+            .cars
+            .iter()
+            .map(CarBuilder::build_coasting)
+            .collect::<Result<_>>()?;
+        let bikes: [Bike; B] = bikes.try_into().map_err(|bikes: Vec<Bike>| {
+            anyhow!("road has {} bikes but {} were saved", B, bikes.len())
+        })?;
+        let cars: [Car; C] = cars.try_into().map_err(|cars: Vec<Car>| {
+            anyhow!("road has {} cars but {} were saved", C, cars.len())
+        })?;
+
+        let mut road = Self::new(bikes, cars)?;
+        road.reseed(saved.seed);
+        road.pedestrians = saved.pedestrians;
+        road.cells = RoadCells::try_from(&road)?;
+        return Ok(road);
+    }
+
+    /// The same vehicles on a road of length `L2`, each moved so its front is
+    /// as far along the new road proportionally as it was along this one.
+    /// Vehicles keep their size and speed, so squeezing them onto a shorter
+    /// road fails if any of them end up overlapping. Like `concat_roads`,
+    /// only the vehicles are carried over and the new road gets a new seed.
+    pub fn rescale<const L2: usize>(&self) -> Result<Road<B, C, L2, BLW, MLW>> {
+        let rescaled_offset = |front: isize| {
+            let front = front.rem_euclid(L as isize);
+            return front * L2 as isize / L as isize - front;
+        };
+        let bikes = self
+            .bikes
+            .map(|bike| bike.shifted(rescaled_offset(bike.front())));
+        let cars = self
+            .cars
+            .map(|car| car.shifted(rescaled_offset(car.front())));
+        return Road::new(bikes, cars);
+    }
+
+    /// The states of every vehicle as JSON, in the same shape as
+    /// `vehicle_positions_as_string` but with a `VehicleState` per vehicle.
+    pub fn vehicle_states_as_string(&self) -> String {
+        return format!(
+            "{{\"cars\":{},\"bikes\":{}}}",
+            serde_json::to_string(&self.car_states()).expect("states should serialise"),
+            serde_json::to_string(&self.bike_states()).expect("states should serialise"),
+        );
+    }
+
+    pub fn vehicle_positions_as_string(&self) -> String {
+        return format!(
+            "{{\"cars\":{:?},\"bikes\":{:?}}}",
+            self.cars.map(|car| car.front()),
+            self.bikes.map(|bike| bike.front()),
+        );
+    }
+
+    pub fn mean_car_speed(&self) -> Option<f64> {
+        return match C {
+            0 => None,
+            _ => Some((self.cars.map(|car| car.speed()).iter().sum::<isize>() as f64) / (C as f64)),
+        };
+    }
+
+    /// The space-mean (harmonic mean) speed of the cars, which is what the
+    /// fundamental diagram relates to flow and density. `mean_car_speed` is
+    /// the time-mean speed instead, and is never lower than this.
+    ///
+    /// A stopped car would make the harmonic mean zero regardless of the rest
+    /// of the road, so only moving cars are included, and `None` is returned
+    /// if no car is moving.
+    pub fn space_mean_car_speed(&self) -> Option<f64> {
+        let moving_speeds: Vec<f64> = self
+            .cars
+            .iter()
+            .filter(|car| car.speed() != 0)
+            .map(|car| car.speed() as f64)
+            .collect();
+        if moving_speeds.is_empty() {
+            return None;
+        }
+        let reciprocal_sum: f64 = moving_speeds.iter().map(|speed| 1.0 / speed).sum();
+        return Some(moving_speeds.len() as f64 / reciprocal_sum);
+    }
+
+    pub fn mean_bike_speed(&self) -> Option<f64> {
+        return match B {
+            0 => None,
+            _ => Some(
+                (self
+                    .bikes
+                    .map(|bike| bike.forward_speed)
+                    .iter()
+                    .sum::<isize>() as f64)
+                    / (B as f64),
+            ),
+        };
+    }
+
+    pub fn mean_car_speed_kmh(&self, units: &Units) -> Option<f64> {
+        return self.mean_car_speed().map(|speed| units.speed_to_kmh(speed));
+    }
+
+    pub fn mean_bike_speed_kmh(&self, units: &Units) -> Option<f64> {
+        return self
+            .mean_bike_speed()
+            .map(|speed| units.speed_to_kmh(speed));
+    }
+
+    /// Number of cars per kilometre of road.
+    pub fn car_veh_per_km(&self, units: &Units) -> f64 {
+        return units.density_to_veh_per_km(C as f64 / L as f64);
+    }
+
+    /// Number of bikes per kilometre of road.
+    pub fn bike_veh_per_km(&self, units: &Units) -> f64 {
+        return units.density_to_veh_per_km(B as f64 / L as f64);
+    }
+
+    /// Min, median, max and mean of the car speeds, `None` without cars.
+    pub fn car_speed_summary(&self) -> Option<SpeedSummary> {
+        return SpeedSummary::from_speeds(self.cars.iter().map(|car| car.speed()));
+    }
+
+    /// Speed statistics over every bike and car, `None` on an empty road.
+    pub fn speed_stats(&self) -> Option<SpeedStats> {
+        let bike_speeds = self.bikes.iter().map(|bike| bike.forward_speed);
+        let car_speeds = self.cars.iter().map(|car| car.speed());
+        return SpeedStats::from_speeds(bike_speeds.chain(car_speeds));
+    }
+
+    pub fn cells(&self) -> &RoadCells<L, BLW, MLW> {
+        return &self.cells;
+    }
+
+    /// The vehicle occupying the cell, if any. The long wraps around the road,
+    /// while a lat off the road is an error.
+    pub fn vehicle_at(&self, coord: &Coord) -> Result<Option<&Vehicle>> {
+        return self.cells.get(coord);
+    }
+
+    /// Every cell's vehicle, indexed `[long][lat]`, for renderers that want
+    /// the whole road at once rather than looking up cells one by one.
+    pub fn occupancy_grid(&self) -> Vec<Vec<Option<Vehicle>>> {
+        let mut grid = vec![vec![None; Self::total_width() as usize]; L];
+        for (Coord { lat, long }, vehicle) in self.cells.cells() {
+            grid[*long as usize][*lat as usize] = Some(*vehicle);
+        }
+        return grid;
+    }
+
+    /// The occupied cells within the box of longs and lats, long by long. The
+    /// longs wrap around the road, so the coords yielded have their long
+    /// within `0..L`, and lats off the road are skipped.
+    pub fn cells_in_range(
+        &self,
+        long_range: RangeInclusive<isize>,
+        lat_range: RangeInclusive<isize>,
+    ) -> impl Iterator<Item = (Coord, &Vehicle)> + '_ {
+        let lat_range = max(*lat_range.start(), 0)..=min(*lat_range.end(), Self::total_width() - 1);
+        return long_range
+            .flat_map(move |long| zip(lat_range.clone(), repeat(long)))
+            .filter_map(|(lat, long)| {
+                let coord = Coord {
+                    lat,
+                    long: long.rem_euclid(L as isize),
+                };
+                return self.cells.cells.get(&coord).map(|vehicle| (coord, vehicle));
+            });
+    }
+
+    pub fn iter_car_positions(&self) -> impl Iterator<Item = (Coord, Vehicle)> + '_ {
+        return self
+            .cars
+            .iter()
+            .enumerate()
+            .flat_map(|(index, car)| zip(car.occupied_cells(), repeat(index)))
+            // not sure if this last line is necessary, as it is clear from the function name
+            // that car ids are being returned
+            .map(|(cell, car_id)| (cell, Vehicle::Car(car_id)));
+    }
+
+    pub fn iter_bike_positions(&self) -> impl Iterator<Item = (Coord, Vehicle)> + '_ {
+        return self
+            .bikes
+            .iter()
+            .enumerate()
+            .flat_map(|(index, bike)| zip(bike.occupied_cells(), repeat(index)))
+            // same criticism as for iter_car_positions
+            .map(|(cell, bike_id)| (cell, Vehicle::Bike(bike_id)));
+    }
+
+    pub fn iter_pedestrian_positions(&self) -> impl Iterator<Item = (Coord, Vehicle)> + '_ {
+        return self
+            .pedestrians
+            .iter()
+            .enumerate()
+            .flat_map(|(index, pedestrian)| zip(pedestrian.occupied_cells(), repeat(index)))
+            .map(|(cell, pedestrian_id)| (cell, Vehicle::Pedestrian(pedestrian_id)));
+    }
+
+    pub fn iter_continuous_car_positions(&self) -> impl Iterator<Item = (Coord, Vehicle)> + '_ {
+        return self
+            .continuous_cars
+            .iter()
+            .enumerate()
+            .flat_map(|(index, continuous_car)| zip(continuous_car.occupied_cells(), repeat(index)))
+            .map(|(cell, continuous_car_id)| (cell, Vehicle::ContinuousCar(continuous_car_id)));
+    }
+
+    pub fn collisions_for(&self, occupier: &impl RoadOccupier) -> Vec<&Vehicle> {
+        return occupier
+            .occupied_cells()
+            .map(|coord| RoadCells::<L, BLW, MLW>::validate_coord(coord).unwrap())
+            .filter_map(|coord| self.cells.get(&coord).unwrap())
+            .collect();
+    }
+
+    pub fn is_collision_for(&self, occupier: &impl RoadOccupier, vehicle: Vehicle) -> bool {
+        return self
+            .collisions_for(occupier)
+            .into_iter()
+            .any(|found_vehicle| *found_vehicle != vehicle);
+    }
+
+    fn bike_lane_contains_occupier(&self, occupier: &impl RoadOccupier) -> bool {
+        return occupier.occupier_is_without(MLW as isize);
+        // // old implementation, can be tested against
+        // occupier
+        //     .occupied_cells()
+        //     .into_iter()
+        //     .map(|(x, y)| x)
+        //     .any(|x| (MLW as isize) < x)
+    }
+
+    pub fn motor_lane_contains_occupier(&self, occupier: &impl RoadOccupier) -> bool {
+        return occupier.occupier_is_within(MLW as isize);
+        // // old implementation, can be tested against
+        // occupier
+        //     .occupied_cells()
+        //     .into_iter()
+        //     .map(|(x, y)| x)
+        //     .any(|x| x >= MLW as isize)
+    }
+
+    /// How many of the occupier's cells are in the bike lane and how many are
+    /// in the motor lane, as `(bike_lane_cells, motor_lane_cells)`. Unlike
+    /// `motor_lane_contains_occupier`, this tells apart an occupier that only
+    /// overhangs the motor lane from one that is mostly in it.
+    pub fn lane_distribution(&self, occupier: &impl RoadOccupier) -> (usize, usize) {
+        let (motor_lane_cells, bike_lane_cells): (Vec<Coord>, Vec<Coord>) = occupier
+            .occupied_cells()
+            .partition(|Coord { lat, .. }| *lat < MLW as isize);
+        return (bike_lane_cells.len(), motor_lane_cells.len());
+    }
+
+    /// Whether the occupier is in the path of a widening car, see
+    /// `widening_car_paths`.
+    pub fn is_in_widening_car_path(&self, occupier: &impl RoadOccupier) -> bool {
+        let is_in_paths = |paths: &[RectangleOccupier]| {
+            return occupier.occupied_cells().any(|Coord { lat, long }| {
+                paths.iter().any(|path| {
+                    path.width_iterator().contains(&lat)
+                        && long_range_contains::<L>(&path.length_iterator(), long)
+                })
+            });
+        };
+        return match &self.lateral_update_widening_car_paths {
+            Some(paths) => is_in_paths(paths),
+            None => is_in_paths(&self.widening_car_paths()),
+        };
+    }
+
+    /// For each car that would widen if it reached its potential speed next
+    /// update, the cells it doesn't take up now but would widen into, from
+    /// its back to as far as that speed could take it.
+    pub fn widening_car_paths(&self) -> Vec<RectangleOccupier> {
+        return self
+            .cars
+            .iter()
+            .filter_map(|car| {
+                let occupation = car.rectangle_occupation();
+                let widened_right = occupation.left() + car.potential_width(self) as isize - 1;
+                if widened_right <= occupation.right {
+                    return None;
+                }
+                let reach = car.next_iteration_potential_speed(self).max(0) as usize;
+                let front = match car.direction() {
+                    Direction::Forward => occupation.front + reach as isize,
+                    Direction::Backward => occupation.front,
+                };
+                return Some(RectangleOccupier {
+                    front,
+                    right: widened_right,
+                    width: (widened_right - occupation.right) as usize,
+                    length: occupation.length + reach,
+                });
+            })
+            .collect();
+    }
+
+    pub fn road_contains_occupier(&self, occupier: &impl RoadOccupier) -> bool {
+        occupier
+            .occupied_cells()
+            .all(|coord| self.road_contains_coord(&coord))
+    }
+
+    fn vehicle_cells(&self, vehicle: Vehicle) -> Vec<Coord> {
+        return match vehicle {
+            Vehicle::Bike(bike_id) => self
+                .bikes
+                .get(bike_id)
+                .expect("bike_id should be valid")
+                .occupied_cells()
+                .collect(),
+            Vehicle::Car(car_id) => self
+                .cars
+                .get(car_id)
+                .expect("car_id should be valid")
+                .occupied_cells()
+                .collect(),
+            Vehicle::Pedestrian(pedestrian_id) => self
+                .pedestrians
+                .get(pedestrian_id)
+                .expect("pedestrian_id should be valid")
+                .occupied_cells()
+                .collect(),
+            Vehicle::ContinuousCar(continuous_car_id) => self
+                .continuous_cars
+                .get(continuous_car_id)
+                .expect("continuous_car_id should be valid")
+                .occupied_cells()
+                .collect(),
+        };
+    }
+
+    fn vehicle_collides(&self, vehicle: Vehicle) -> bool {
+        return self
+            .vehicle_cells(vehicle)
+            .into_iter()
+            .map(|coord| RoadCells::<L, BLW, MLW>::validate_coord(coord).unwrap())
+            .filter_map(|coord| self.cells.get(&coord).unwrap())
+            .any(|found_vehicle| *found_vehicle != vehicle);
+    }
+
+    /// The cars indexed by id, read only as moving a car also has to update
+    /// the road's cells, see `modify_car`.
+    pub fn cars(&self) -> &[Car] {
+        return &self.cars;
+    }
+
+    /// The bikes indexed by id, read only like `cars`.
+    pub fn bikes(&self) -> &[Bike] {
+        return &self.bikes;
+    }
+
+    /// Whether the other road has every vehicle in the same place at the same
+    /// speed, whatever the state of the roads' random number generators or
+    /// anything else not on the road itself.
+    pub fn physically_eq(&self, other: &Self) -> bool {
+        let bikes_eq = zip(&self.bikes, &other.bikes).all(|(bike, other_bike)| {
+            bike.rectangle_occupation() == other_bike.rectangle_occupation()
+                && bike.forward_speed == other_bike.forward_speed
+        });
+        let cars_eq = zip(&self.cars, &other.cars).all(|(car, other_car)| {
+            car.rectangle_occupation() == other_car.rectangle_occupation()
+                && car.speed() == other_car.speed()
+        });
+        return bikes_eq
+            && cars_eq
+            && self.pedestrians == other.pedestrians
+            && self.continuous_cars == other.continuous_cars;
+    }
+
+    /// A hash of where every vehicle is and how fast it is going, the same
+    /// for `physically_eq` roads, for comparing runs in a single assertion.
+    /// Vehicles are hashed one by one and summed, so the hash doesn't depend
+    /// on their order, and with a fixed hasher, so it is the same across
+    /// runs and builds.
+    pub fn state_hash(&self) -> u64 {
+        let vehicle_hash = |is_car: bool, occupation: RectangleOccupier, speed: isize| {
+            let mut hasher = FxHasher::default();
+            (is_car, occupation, speed).hash(&mut hasher);
+            return hasher.finish();
+        };
+        let bike_hashes = self
+            .bikes
+            .iter()
+            .map(|bike| vehicle_hash(false, bike.rectangle_occupation(), bike.forward_speed));
+        let car_hashes = self
+            .cars
+            .iter()
+            .map(|car| vehicle_hash(true, car.rectangle_occupation(), car.speed()));
+        return bike_hashes.chain(car_hashes).fold(0, u64::wrapping_add);
+    }
+
+    pub fn get_car(&self, car_id: usize) -> &Car {
+        return self.cars.get(car_id).unwrap();
+    }
+
+    pub fn get_bike(&self, bike_id: usize) -> &Bike {
+        return self.bikes.get(bike_id).unwrap();
+    }
+
+    pub fn first_car_back(&self, coord: &Coord, maybe_max: Option<usize>) -> Option<&Car> {
+        return match self.cells.first_car_back(coord, maybe_max) {
+            Some(car_id) => Some(self.get_car(car_id)),
+            None => None,
+        };
+    }
+
+    /// The nearest other vehicle ahead of the vehicle, towards increasing long,
+    /// in any of the lats it occupies. The search wraps around the road.
+    pub fn leader_of(&self, vehicle: Vehicle) -> Option<Vehicle> {
+        let cells = self.vehicle_cells(vehicle);
+        let front = cells.iter().map(|coord| coord.long).max()?;
+        return self.nearest_other_in_band(vehicle, &cells, front, 1);
+    }
+
+    /// Like `leader_of`, but the nearest other vehicle behind the vehicle.
+    pub fn follower_of(&self, vehicle: Vehicle) -> Option<Vehicle> {
+        let cells = self.vehicle_cells(vehicle);
+        let back = cells.iter().map(|coord| coord.long).min()?;
+        return self.nearest_other_in_band(vehicle, &cells, back, -1);
+    }
+
+    fn nearest_other_in_band(
+        &self,
+        vehicle: Vehicle,
+        cells: &[Coord],
+        start_long: isize,
+        step: isize,
+    ) -> Option<Vehicle> {
+        let lats: BTreeSet<isize> = cells.iter().map(|coord| coord.lat).collect();
+        return (1..L as isize)
+            .flat_map(|distance| zip(lats.iter(), repeat(start_long + step * distance)))
+            .filter_map(|(lat, long)| {
+                self.cells
+                    .get(&Coord { lat: *lat, long })
+                    .expect("lat should be in range")
+            })
+            .find(|found_vehicle| **found_vehicle != vehicle)
+            .copied();
+    }
+
+    /// Like `first_car_back`, along with the number of clear cells between
+    /// the car and the coord, wrapping around the road.
+    pub fn first_car_back_with_gap(
+        &self,
+        coord: &Coord,
+        maybe_max: Option<usize>,
+    ) -> Option<(&Car, usize)> {
+        return self
+            .cells
+            .first_car_back_with_distance(coord, maybe_max)
+            .map(|(car_id, distance)| (self.get_car(car_id), distance - 1));
+    }
+
+    /// Whether the first car behind the coord would be held up by something
+    /// at the coord, as it could go further than the gap next update.
+    pub fn is_blocking(&self, coord: &Coord, maybe_max: Option<usize>) -> bool {
+        return self
+            .first_car_back_with_gap(
+                coord, maybe_max, // potential optimisation: set reasonable max
+            )
+            // oncoming cars are driving away from the coord
+            .filter(|(car, _)| car.direction() == Direction::Forward)
+            .is_some_and(|(car, gap)| (gap as isize) < car.next_iteration_potential_speed(self));
+    }
+
+    /// Panics unless every car is clear of every other vehicle and, updated
+    /// on its own without any random deceleration, brakes from the speed it
+    /// could reach to within the gap in front of it.
+    pub fn assert_safe_following(&self) {
+        for (car_id, car) in self.cars.iter().enumerate() {
+            assert!(
+                !self.vehicle_collides(Vehicle::Car(car_id)),
+                "car {} collides with another vehicle: {:?}",
+                car_id,
+                car
+            );
+            let front_gap = self.gap_ahead_of_car(car);
+            let next_car = car.update(self, car_id, false, None);
+            assert!(
+                next_car.speed() as usize <= front_gap,
+                "car {} would go from speed {} to {}, potentially {}, with a front gap of {}",
+                car_id,
+                car.speed(),
+                next_car.speed(),
+                car.next_iteration_potential_speed(self),
+                front_gap
+            );
+            assert!(
+                !self.is_collision_for(&next_car, Vehicle::Car(car_id)),
+                "car {} would collide after its update: {:?}",
+                car_id,
+                self.collisions_for(&next_car)
+            );
+        }
+    }
+
+    /// The road after an update, leaving this one as it is. The copy carries
+    /// on from this road's random number generator, so stepping twice gives
+    /// the same road twice.
+    pub fn stepped(&self) -> Result<Self> {
+        let mut next_road = self.clone();
+        next_road.update()?;
+        return Ok(next_road);
+    }
+
+    /// Updates the road, reporting what happened in the update.
+    pub fn update(&mut self) -> Result<StepReport> {
+        return self.with_own_rng(|road, rng| road.update_with(rng));
+    }
+
+    /// Like `update`, for callers with no use for the report.
+    pub fn update_discard(&mut self) -> Result<()> {
+        self.update()?;
+        return Ok(());
+    }
+
+    /// Updates the road taking the random decisions from `source` rather than
+    /// the road's own random number generator.
+    pub fn update_with(&mut self, source: &mut impl RandSource) -> Result<StepReport> {
+        let bikes_before = self.bikes.map(|bike| bike.rectangle_occupation());
+        let cars_before = self.cars.map(|car| car.rectangle_occupation());
+        self.bikes_lateral_update_with(source)?;
+        self.bikes_forward_update_with(source)?;
+        let car_fronts_before = self.cars.map(|car| car.front());
+        let car_lane_changes = self.cars_lane_change_update()?;
+        self.cars_update_with(source)?;
+        let overtakes = self.record_close_passes(&car_fronts_before);
+        self.pedestrians_update()?;
+        self.continuous_cars_update()?;
+        self.record_waiting();
+        self.record_stop_go();
+        self.count_down_incidents();
+
+        let bikes_moved = zip(&self.bikes, bikes_before)
+            .filter(|(bike, before)| bike.rectangle_occupation() != *before)
+            .count();
+        let cars_moved = zip(&self.cars, cars_before)
+            .filter(|(car, before)| car.rectangle_occupation() != *before)
+            .count();
+        return Ok(StepReport {
+            lane_changes: self.boundary_crossings + car_lane_changes,
+            collisions: 0,
+            overtakes,
+            mean_car_speed: self.mean_car_speed(),
+            vehicles_moved: bikes_moved + cars_moved,
+        });
+    }
+
+    fn record_stop_go(&mut self) {
+        let mut occupied = vec![false; L];
+        let mut stopped = vec![false; L];
+        for car in &self.cars {
+            for Coord { long, .. } in car.occupied_cells() {
+                let long = long.rem_euclid(L as isize) as usize;
+                occupied[long] = true;
+                stopped[long] |= car.speed() == 0;
+            }
+        }
+        for long in 0..L {
+            if stopped[long] {
+                self.stopped_since_free[long] = true;
+            } else if !occupied[long] && self.stopped_since_free[long] {
+                self.stopped_since_free[long] = false;
+                self.stop_go_waves += 1;
+            }
+        }
+    }
+
+    /// The number of times a long has been freed of cars after a stopped car
+    /// was on it, summed over every long, since the road was built. A jam
+    /// passing backwards through the traffic frees each long it covered once
+    /// it dissolves, so this grows with the number and size of stop and go
+    /// waves.
+    pub const fn count_stop_go_waves(&self) -> usize {
+        return self.stop_go_waves;
+    }
+
+    /// Records the separation of each forward car whose front moved from
+    /// behind a bike's front to level with or ahead of it in the car update,
+    /// returning the number of such passes.
+    fn record_close_passes(&mut self, car_fronts_before: &[isize; C]) -> usize {
+        let mut passes = 0;
+        for (car, front_before) in zip(&self.cars, car_fronts_before) {
+            if car.direction() == Direction::Backward {
+                continue;
+            }
+            let car_right = car.left() + car.width() as isize - 1;
+            for bike in &self.bikes {
+                let ahead = (bike.front() - front_before).rem_euclid(L as isize);
+                if ahead == 0 || car.speed() < ahead {
+                    continue;
+                }
+                let occupation = bike.rectangle_occupation();
+                let separation =
+                    max(occupation.left() - car_right, car.left() - occupation.right) - 1;
+                self.passes_by_separation[separation.max(0) as usize] += 1;
+                passes += 1;
+            }
+        }
+        return passes;
+    }
+
+    /// The number of times a car has passed a bike with fewer than
+    /// `threshold` clear cells between them, since the road was built.
+    pub fn close_pass_events(&self, threshold: usize) -> usize {
+        let below = min(threshold, self.passes_by_separation.len());
+        return self.passes_by_separation[..below].iter().sum();
+    }
+
+    fn record_waiting(&mut self) {
+        for (waiting_time, bike) in zip(&mut self.bike_waiting_times, &self.bikes) {
+            if bike.forward_speed == 0 {
+                *waiting_time += 1;
+            }
+        }
+        for (waiting_time, car) in zip(&mut self.car_waiting_times, &self.cars) {
+            if car.speed() == 0 {
+                *waiting_time += 1;
+            }
+        }
+    }
+
+    /// The number of updates after which each bike was stopped.
+    pub const fn bike_waiting_times(&self) -> &[usize; B] {
+        return &self.bike_waiting_times;
+    }
+
+    /// The number of updates after which each car was stopped.
+    pub const fn car_waiting_times(&self) -> &[usize; C] {
+        return &self.car_waiting_times;
+    }
+
+    /// Does up to `iterations` updates, sending the state after each one to
+    /// `tx`, for running on a worker thread while another one renders. Gives
+    /// back the road unless an update failed, in which case the error is sent
+    /// instead of the state. Stops early if the receiver hangs up.
+    pub fn run_to_channel(
+        mut self,
+        iterations: usize,
+        tx: Sender<Result<IterationRecord>>,
+    ) -> Option<Self> {
+        for _ in 0..iterations {
+            let frame = match self.update() {
+                Ok(_) => Ok(IterationRecord::from_road(&self)),
+                Err(error) => {
+                    // nothing more can be done if the receiver is gone
+                    let _ = tx.send(Err(error));
+                    return None;
+                }
+            };
+            if tx.send(frame).is_err() {
+                break;
+            }
+        }
+        return Some(self);
+    }
+
+    /// Updates until `predicate` holds for the road or `max_steps` updates
+    /// have been done, returning the number of updates done. The predicate is
+    /// checked before every update, so a road it already holds for isn't
+    /// updated at all.
+    pub fn update_until(
+        &mut self,
+        max_steps: usize,
+        predicate: impl Fn(&Self) -> bool,
+    ) -> Result<usize> {
+        for step in 0..max_steps {
+            if predicate(self) {
+                return Ok(step);
+            }
+            self.update()?;
+        }
+        return Ok(max_steps);
+    }
+
+    fn with_own_rng<T>(&mut self, update: impl FnOnce(&mut Self, &mut StdRng) -> T) -> T {
+        let mut rng = self.rng.clone();
+        let result = update(self, &mut rng);
+        self.rng = rng;
+        return result;
+    }
+
+    pub fn bikes_lateral_update(&mut self) -> Result<()> {
+        return self.with_own_rng(|road, rng| road.bikes_lateral_update_with(rng));
+    }
+
+    fn bikes_lateral_update_with(&mut self, source: &mut impl RandSource) -> Result<()> {
+        let were_in_motor_lane = self.bikes_in_motor_lane();
+        self.lateral_update_widening_car_paths = Some(self.widening_car_paths());
+        let updated = match self.update_mode {
+            UpdateMode::Synchronous => self.bikes_lateral_update_synchronous(source),
+            UpdateMode::Ordered => self.bikes_lateral_update_ordered(source),
+        };
+        self.lateral_update_widening_car_paths = None;
+        updated?;
+        self.boundary_crossings = zip(were_in_motor_lane, self.bikes_in_motor_lane())
+            .filter(|(was_in_motor_lane, is_in_motor_lane)| was_in_motor_lane != is_in_motor_lane)
+            .count();
+        return Ok(());
+    }
+
+    fn bikes_in_motor_lane(&self) -> [bool; B] {
+        return self
+            .bikes
+            .map(|bike| self.motor_lane_contains_occupier(&bike));
+    }
+
+    /// The number of bikes that crossed between the lanes in the last
+    /// lateral update, going from being at least partly in the motor lane to
+    /// being wholly in the bike lane or the other way round.
+    pub const fn boundary_crossings_last_step(&self) -> usize {
+        return self.boundary_crossings;
+    }
+
+    fn bikes_lateral_update_synchronous(&mut self, source: &mut impl RandSource) -> Result<()> {
+        let shuffled_new_bikes = {
+            let mut next_bikes: Vec<(usize, Bike)> = self
+                .next_bikes_lateral(source)
+                .into_iter()
+                .enumerate()
+                .collect();
+            source.shuffle(&mut next_bikes);
+            next_bikes
+        };
+
+        // each bike leaves its old cells only as it's placed, so a fallback
+        // is chosen around the bikes still to be placed, which may yet hold
+        // onto their old cells
+        for (bike_id, new_bike) in shuffled_new_bikes {
+            let bike = self.bikes[bike_id];
+            let bike_to_occupy = match self.is_collision_for(&new_bike, Vehicle::Bike(bike_id)) {
+                false => new_bike,
+                true => match self.lateral_fallback {
+                    LateralFallback::Hold => bike,
+                    LateralFallback::NextBest => {
+                        let options = bike.lateral_options(self, bike_id);
+                        bike.choose_lateral(options, source)
+                    }
+                },
+            };
+            self.replace_bike(bike_id, bike_to_occupy)?;
+        }
+        return Ok(());
+    }
+
+    fn bikes_lateral_update_ordered(&mut self, source: &mut impl RandSource) -> Result<()> {
+        for bike_id in 0..B {
+            let bike = self.bikes[bike_id];
+            let new_bike = match bike.should_ignore_lateral_movement(source) {
+                true => bike,
+                false => {
+                    let options = bike.lateral_options(self, bike_id);
+                    bike.choose_lateral(options, source)
+                }
+            };
+            if !self.is_collision_for(&new_bike, Vehicle::Bike(bike_id)) {
+                self.replace_bike(bike_id, new_bike)?;
+            }
+        }
+        return Ok(());
+    }
+
+    pub fn bikes_forward_update(&mut self) -> Result<()> {
+        return self.with_own_rng(|road, rng| road.bikes_forward_update_with(rng));
+    }
+
+    fn bikes_forward_update_with(&mut self, source: &mut impl RandSource) -> Result<()> {
+        return match self.update_mode {
+            UpdateMode::Synchronous => self.bikes_forward_update_synchronous(source),
+            UpdateMode::Ordered => self.bikes_forward_update_ordered(source),
+        };
+    }
+
+    fn bikes_forward_update_ordered(&mut self, source: &mut impl RandSource) -> Result<()> {
+        for bike_id in 0..B {
+            let decelerate =
+                self.decelerates(self.bikes[bike_id].should_decelerate(source), source);
+            let next_bike = self.bikes[bike_id].forward_update(self, decelerate);
+            self.replace_bike(bike_id, next_bike)?;
+        }
+        return Ok(());
+    }
+
+    fn bikes_forward_update_synchronous(&mut self, source: &mut impl RandSource) -> Result<()> {
+        // should be okay as there can be no collisions when moving forwards?
+        // ^ check this ^
+        let next_bikes = self.next_bikes_forward(source);
+        self.wipe_bikes_from_cells()?;
+        next_bikes
+            .iter()
+            .enumerate()
+            .flat_map(|(index, bike)| zip(bike.occupied_cells(), repeat(Vehicle::Bike(index))))
+            .try_for_each(|(cell, insert_vehicle)| {
+                let validated_cell = RoadCells::<L, BLW, MLW>::validate_vehicle_coord(cell, insert_vehicle)?;
+                match self.cells.insert_validated(validated_cell, insert_vehicle) {
+                    Some(found_vehicle) => Err(anyhow!(
+                        "inserted vehicle {:?} collided with found vehicle {:?} at cell {:?}. Full cells {}",
+                        self.cells.cells.get(&validated_cell),
+                        found_vehicle,
+                        validated_cell,
+                        self.cells
+                    )),
+                    None => Ok(()),
+                }
+            })?;
+        self.bikes = next_bikes;
+        return Ok(());
+        // let shuffled_new_bikes = {
+        //     let mut rng = thread_rng();
+        //     let mut next_bikes: Vec<(usize, Bike)> =
+        //         self.next_bikes_forward().into_iter().enumerate().collect();
+        //     next_bikes.shuffle(&mut rng);
+        //     next_bikes
+        // };
+
+        // self.replace_bikes(shuffled_new_bikes);
+    }
+
+    // fn replace_bikes(&mut self, new_bikes: Vec<(usize, Bike)>) {
+    //     // no need for this function if it's just being used in the one place
+    //     self.wipe_bikes_from_cells();
+    //     for (bike_id, new_bike) in new_bikes {
+    //         let bike_to_occupy = match self.collisions_for(&new_bike).is_empty() {
+    //             true => new_bike,
+    //             false => *self.bikes.get(bike_id).expect("should be a valid bike id"),
+    //         };
+    //         bike_to_occupy.occupied_cells().for_each(|occupied_cell| {
+    //             self.cells
+    //                 .cells
+    //                 .insert(occupied_cell, Vehicle::Bike(bike_id));
+    //         });
+    //         self.bikes[bike_id] = bike_to_occupy;
+    //     }
+    // }
+
+    // fn replace_bikes_with(&mut self, new_bikes: Vec<(usize, Bike)>) {
+    //     self.wipe_bikes_from_cells();
+    //     for (bike_id, new_bike) in new_bikes {
+    //         let bike_to_occupy = match self.collisions_for(&new_bike).is_empty() {
+    //             true => new_bike,
+    //             false => *self.bikes.get(bike_id).expect("should be a valid bike id"),
+    //         };
+    //         bike_to_occupy.occupied_cells().for_each(|occupied_cell| {
+    //             self.cells
+    //                 .cells
+    //                 .insert(occupied_cell, Vehicle::Bike(bike_id));
+    //         });
+    //         self.bikes[bike_id] = bike_to_occupy;
+    //     }
+    // }
+
+    fn wipe_bikes_from_cells(&mut self) -> Result<()> {
+        for (bike_id, bike) in self.bikes.iter().enumerate() {
+            for cell in bike.occupied_cells() {
+                let bike_cell =
+                    RoadCells::<L, BLW, MLW>::validate_vehicle_coord(cell, Vehicle::Bike(bike_id))?;
+                let removed = self.cells.remove_validated(&bike_cell);
+                debug_assert!(
+                    removed.is_some_and(|vehicle| vehicle.is_bike()),
+                    "expected to find a bike at this location ({:?})",
+                    bike_cell
+                );
+            }
+        }
+        return Ok(());
+    }
+
+    fn wipe_cars_from_cells(&mut self) -> Result<()> {
+        for (car_id, car) in self.cars.iter().enumerate() {
+            for cell in car.occupied_cells() {
+                let car_cell =
+                    RoadCells::<L, BLW, MLW>::validate_vehicle_coord(cell, Vehicle::Car(car_id))?;
+                let removed = self.cells.remove_validated(&car_cell);
+                debug_assert!(
+                    removed.is_some_and(|vehicle| vehicle.is_car()),
+                    "expected to find a car at this location ({:?})",
+                    car_cell
+                );
+            }
+        }
+        return Ok(());
+    }
+
+    // The random decisions are drawn in id order before (or after) the
+    // parallel part of each phase, so that a seeded road updates the same way
+    // however the work is scheduled.
+
+    /// Maps each vehicle along with its id, in parallel unless the road is set
+    /// to update sequentially.
+    fn map_vehicles<T: Sync, U: Send>(
+        &self,
+        vehicles: &[T],
+        map: impl Fn(usize, &T) -> U + Sync + Send,
+    ) -> Vec<U> {
+        #[cfg(feature = "parallel")]
+        if self.parallel {
+            return vehicles
+                .par_iter()
+                .enumerate()
+                .map(|(id, vehicle)| map(id, vehicle))
+                .collect();
+        }
+        return vehicles
+            .iter()
+            .enumerate()
+            .map(|(id, vehicle)| map(id, vehicle))
+            .collect();
+    }
+
+    fn next_bikes_lateral(&self, source: &mut impl RandSource) -> [Bike; B] {
+        let ignores: Vec<bool> = self
+            .bikes
+            .iter()
+            .map(|bike| bike.should_ignore_lateral_movement(source))
+            .collect();
+        let options: Vec<Vec<RectangleOccupier>> =
+            self.map_vehicles(&self.bikes, |bike_id, bike| match ignores[bike_id] {
+                // no options means the bike stays where it is
+                true => Vec::new(),
+                false => bike.lateral_options(self, bike_id),
+            });
+        return zip(self.bikes, options)
+            .map(|(bike, options)| bike.choose_lateral(options, source))
+            .collect::<Vec<Bike>>()
+            .try_into()
+            .expect("array length should be okay due to const generic B");
+    }
+
+    fn next_bikes_forward(&self, source: &mut impl RandSource) -> [Bike; B] {
+        let decelerations: Vec<bool> = self
+            .bikes
+            .iter()
+            .map(|bike| self.decelerates(bike.should_decelerate(source), source))
+            .collect();
+        return self
+            .map_vehicles(&self.bikes, |bike_id, bike| {
+                bike.forward_update(self, decelerations[bike_id])
+            })
+            .try_into()
+            .expect("array length should be okay due to const generic B");
+    }
+
+    pub fn cars_update(&mut self) -> Result<()> {
+        return self.with_own_rng(|road, rng| road.cars_update_with(rng));
+    }
+
+    fn cars_update_with(&mut self, source: &mut impl RandSource) -> Result<()> {
+        let all_caps = [
+            self.take_lagged_speed_caps(),
+            self.brake_light_speed_caps(),
+            self.skipped_acceleration_speed_caps(source),
+        ];
+        let speed_caps: [Option<isize>; C] = std::array::from_fn(|car_id| {
+            return all_caps.iter().filter_map(|caps| caps[car_id]).min();
+        });
+        return match self.update_mode {
+            UpdateMode::Synchronous => self.cars_update_synchronous(source, speed_caps),
+            UpdateMode::Ordered => self.cars_update_ordered(source, speed_caps),
+        };
+    }
+
+    /// Lets each car in turn change lanes, see `set_car_lane_width`.
+    /// Gives the number of cars that changed lanes.
+    fn cars_lane_change_update(&mut self) -> Result<usize> {
+        let Some(lane_width) = self.car_lane_width else {
+            return Ok(0);
+        };
+        let mut lane_changes = 0;
+        for car_id in 0..C {
+            for d_lat in [-(lane_width as isize), lane_width as isize] {
+                if self.change_car_lane(car_id, d_lat)? {
+                    lane_changes += 1;
+                    break;
+                }
+            }
+        }
+        return Ok(lane_changes);
+    }
+
+    /// Moves the car `d_lat` lats over, onto the next lane, if it is on a
+    /// lane and the move is clear and accepted by the car. The move is tried
+    /// out on the road to weigh it up and undone unless accepted, giving
+    /// whether the car moved.
+    fn change_car_lane(&mut self, car_id: usize, d_lat: isize) -> Result<bool> {
+        let lane_width = self.car_lane_width.expect("car lanes should be set");
+        let car = self.cars[car_id];
+        let changed = car.shifted_laterally(d_lat);
+        // oncoming cars and buses at a stop keep to their lane
+        if car.direction() == Direction::Backward
+            || car.is_dwelling()
+            || car.left() % lane_width as isize != 0
+            || !(0..MLW as isize).contains(&changed.left())
+            || !self.road_contains_occupier(&changed)
+            || self.is_collision_for(&changed, Vehicle::Car(car_id))
+        {
+            return Ok(false);
+        }
+        let changed_cells: Vec<Coord> = changed.occupied_cells().collect();
+        let changed_back = changed_cells.iter().map(|coord| coord.long).min();
+        let followers = [
+            self.follower_of(Vehicle::Car(car_id)),
+            changed_back.and_then(|back| {
+                self.nearest_other_in_band(Vehicle::Car(car_id), &changed_cells, back, -1)
+            }),
+        ]
+        .map(|follower| match follower {
+            Some(Vehicle::Car(follower_id))
+                if self.cars[follower_id].direction() == Direction::Forward =>
+            {
+                Some(follower_id)
+            }
+            _ => None,
+        });
+        let safe_accelerations = |road: &Self| {
+            return [Some(car_id), followers[0], followers[1]].map(|maybe_id| {
+                maybe_id.map_or(0, |id| road.cars[id].safe_acceleration(road, id))
+            });
+        };
+
+        let before = safe_accelerations(self);
+        self.replace_car(car_id, changed)?;
+        let after = safe_accelerations(self);
+        let [own_gain, old_follower_gain, new_follower_gain] =
+            [0, 1, 2].map(|index| after[index] - before[index]);
+        let direction = match d_lat.is_negative() {
+            true => LaneChangeDirection::Left,
+            false => LaneChangeDirection::Right,
+        };
+        if car.accepts_lane_change_towards(
+            direction,
+            own_gain,
+            new_follower_gain,
+            old_follower_gain,
+        ) {
+            return Ok(true);
+        }
+        self.replace_car(car_id, car)?;
+        return Ok(false);
+    }
+
+    fn cars_update_ordered(
+        &mut self,
+        source: &mut impl RandSource,
+        speed_caps: [Option<isize>; C],
+    ) -> Result<()> {
+        for (car_id, speed_cap) in speed_caps.into_iter().enumerate() {
+            let decelerate = self.decelerates(self.cars[car_id].should_decelerate(source), source);
+            let next_car = self.cars[car_id].update(self, car_id, decelerate, speed_cap);
+            self.replace_car(car_id, next_car)?;
+        }
+        return Ok(());
+    }
+
+    fn cars_update_synchronous(
+        &mut self,
+        source: &mut impl RandSource,
+        speed_caps: [Option<isize>; C],
+    ) -> Result<()> {
+        let next_cars = self.next_cars(source, speed_caps);
+        self.wipe_cars_from_cells()?;
+        next_cars
+            .iter()
+            .enumerate()
+            .flat_map(|(index, car)| zip(car.occupied_cells(), repeat(Vehicle::Car(index))))
+            .try_for_each(|(cell, insert_vehicle)| {
+                let validated_cell = RoadCells::<L, BLW, MLW>::validate_vehicle_coord(cell, insert_vehicle)?;
+                match self.cells.insert_validated(validated_cell, insert_vehicle) {
+                    Some(found_vehicle) => Err(anyhow!(
+                        "inserted vehicle {:?} collided with found vehicle {:?} at cell {:?}. Full cells {}\n",
+                        self.cells.cells.get(&validated_cell),
+                        found_vehicle,
+                        validated_cell,
+                        self.cells
+                    )),
+                    None => Ok(()),
+                }
+            })?;
+        self.cars = next_cars;
+        return Ok(());
+    }
+
+    fn replace_bike(&mut self, bike_id: usize, next_bike: Bike) -> Result<()> {
+        let current_bike = self.bikes[bike_id];
+        self.replace_cells(Vehicle::Bike(bike_id), &current_bike, &next_bike)?;
+        self.bikes[bike_id] = next_bike;
+        return Ok(());
+    }
+
+    /// Changes a car through a builder of its current state, such as to make
+    /// it brake suddenly. Errors, leaving the car as it was, if the changed car
+    /// is off the road or overlaps another vehicle.
+    pub fn modify_car(
+        &mut self,
+        car_id: usize,
+        modify: impl FnOnce(&mut CarBuilder),
+    ) -> Result<()> {
+        let current_car = self
+            .cars
+            .get(car_id)
+            .ok_or_else(|| anyhow!("no car with id {}, only {} cars", car_id, C))?;
+        let mut builder = CarBuilder::from(current_car);
+        modify(&mut builder);
+        let next_car = builder.build_coasting()?;
+        if !self.road_contains_occupier(&next_car) {
+            return Err(anyhow!(
+                "modified car {} would be off the road: {:?}",
+                car_id,
+                next_car
+            ));
+        }
+        if self.is_collision_for(&next_car, Vehicle::Car(car_id)) {
+            return Err(anyhow!(
+                "modified car {} would collide with {:?}",
+                car_id,
+                self.collisions_for(&next_car)
+            ));
+        }
+        self.replace_car(car_id, next_car)?;
+        // the gaps it saw were from wherever it was before
+        self.car_gap_histories[car_id].clear();
+        return Ok(());
+    }
+
+    fn replace_car(&mut self, car_id: usize, next_car: Car) -> Result<()> {
+        let current_car = self.cars[car_id];
+        self.replace_cells(Vehicle::Car(car_id), &current_car, &next_car)?;
+        self.cars[car_id] = next_car;
+        return Ok(());
+    }
+
+    /// Moves a single vehicle in the cells from its current to its next
+    /// occupation, erroring if the next occupation overlaps another vehicle.
+    fn replace_cells(
+        &mut self,
+        vehicle: Vehicle,
+        current: &impl RoadOccupier,
+        next: &impl RoadOccupier,
+    ) -> Result<()> {
+        for cell in current.occupied_cells() {
+            let cell = RoadCells::<L, BLW, MLW>::validate_vehicle_coord(cell, vehicle)?;
+            let removed = self.cells.remove_validated(&cell);
+            debug_assert_eq!(
+                removed,
+                Some(vehicle),
+                "expected to find {:?} at this location ({:?})",
+                vehicle,
+                cell
+            );
+        }
+        return next.occupied_cells().try_for_each(|cell| {
+            let validated_cell = RoadCells::<L, BLW, MLW>::validate_vehicle_coord(cell, vehicle)?;
+            match self.cells.insert_validated(validated_cell, vehicle) {
+                    Some(found_vehicle) => Err(anyhow!(
+                        "inserted vehicle {:?} collided with found vehicle {:?} at cell {:?}. Full cells {}\n",
+                        vehicle,
+                        found_vehicle,
+                        validated_cell,
+                        self.cells
+                    )),
+                    None => Ok(()),
+                }
+            });
+    }
+
+    fn next_cars(&self, source: &mut impl RandSource, speed_caps: [Option<isize>; C]) -> [Car; C] {
+        let decelerations: Vec<bool> = self
+            .cars
+            .iter()
+            .map(|car| self.decelerates(car.should_decelerate(source), source))
+            .collect();
+        let cars_vec: Vec<Car> = self.map_vehicles(&self.cars, |car_id, car| {
+            car.update(self, car_id, decelerations[car_id], speed_caps[car_id])
+        });
+        return cars_vec.try_into().unwrap();
+    }
+
+    pub fn front_gap(&self, occupation: &RectangleOccupier) -> Option<usize> {
+        occupation
+            .front_cells()
+            .map(|coord| self.cell_gap(&coord, Direction::Forward))
+            .min()
+    }
+
+    /// Number of clear cells in front of the car with the id in its direction
+    /// of travel, `None` if there is no such car.
+    pub fn car_front_gap(&self, car_id: usize) -> Option<usize> {
+        return self.cars.get(car_id).map(|car| self.gap_ahead_of_car(car));
+    }
+
+    fn gap_ahead_of_car(&self, car: &Car) -> usize {
+        return car
+            .occupied_cells()
+            .filter(|coord| coord.long == car.front())
+            .map(|coord| self.cell_gap(&coord, car.direction()))
+            .min()
+            .expect("car should occupy cells");
+    }
+
+    /// Number of clear cells from the coord in `direction` before a vehicle,
+    /// a lane drop or an incident.
+    fn cell_gap(&self, coord: &Coord, direction: Direction) -> usize {
+        let blockage_gap = self
+            .incidents
+            .iter()
+            .filter_map(|incident| match direction {
+                Direction::Forward => incident.distance_from::<L>(&Coord {
+                    lat: coord.lat,
+                    long: coord.long + 1,
+                }),
+                Direction::Backward => incident.distance_behind::<L>(&Coord {
+                    lat: coord.lat,
+                    long: coord.long - 1,
+                }),
+            })
+            .chain(self.lane_drop_gap(coord, direction))
+            .min();
+        let vehicle_gap = match direction {
+            Direction::Forward => self.cells.front_gap(coord, None),
+            Direction::Backward => self.cells.back_gap(coord, None),
+        };
+        return match blockage_gap {
+            Some(blockage_gap) => vehicle_gap.min(blockage_gap),
+            None => vehicle_gap,
+        };
+    }
+
+    /// Number of clear cells from the coord in `direction` before one removed
+    /// by a lane drop, if any lane drop removes its lat.
+    fn lane_drop_gap(&self, coord: &Coord, direction: Direction) -> Option<usize> {
+        return self
+            .lane_drops
+            .iter()
+            .filter(|lane_drop| lane_drop.removes_lat::<MLW>(coord.lat))
+            .map(|lane_drop| match direction {
+                Direction::Forward => lane_drop.distance_from::<L>(coord.long + 1),
+                Direction::Backward => lane_drop.distance_behind::<L>(coord.long - 1),
+            })
+            .min();
+    }
+
+    pub(crate) fn route_width(&self, long: isize) -> usize {
+        return self.cells.route_width(long);
+    }
+
+    /// Whether there are `width` free lats side by side anywhere across the
+    /// road at `long`, so a vehicle that wide could get through. Unlike
+    /// `route_width`, free cells either side of a vehicle don't add up.
+    pub fn passable_width_for(&self, long: isize, width: usize) -> bool {
+        return self.cells.passable_width_for(long, width);
+    }
+}
+
+/// Joins two roads of the same widths end to end, `b` following on from `a`,
+/// into a road with the vehicles of both. The bikes and cars of `b` come after
+/// those of `a` and have their ids shifted accordingly.
+///
+/// The const generics of the joined road can't be computed from those of the
+/// parts, so they are checked when joining instead: `B`, `C` and `L` must be
+/// the sums of those of `a` and `b`. Vehicles wrapping around the end of `a`
+/// end up at the end of the joined road, so they are rejected if they overlap
+/// vehicles at the end of `b`. Speed zones, grades, lane speed limits,
+/// conditions, lane drops, keep clear zones, incidents, bus stops and
+/// pedestrians are not carried over, and the joined road gets a new seed.
+pub fn concat_roads<
+    const B1: usize,
+    const C1: usize,
+    const L1: usize,
+    const B2: usize,
+    const C2: usize,
+    const L2: usize,
+    const B: usize,
+    const C: usize,
+    const L: usize,
+    const BLW: usize,
+    const MLW: usize,
+>(
+    a: &Road<B1, C1, L1, BLW, MLW>,
+    b: &Road<B2, C2, L2, BLW, MLW>,
+) -> Result<Road<B, C, L, BLW, MLW>> {
+    if (B, C, L) != (B1 + B2, C1 + C2, L1 + L2) {
+        return Err(anyhow!(
+            "joined road should have {} bikes, {} cars and length {}, instead {}, {} and {}",
+            B1 + B2,
+            C1 + C2,
+            L1 + L2,
+            B,
+            C,
+            L
+        ));
+    }
+    let offset = L1 as isize;
+    let bikes: Vec<Bike> = a
+        .bikes
+        .iter()
+        .copied()
+        .chain(b.bikes.iter().map(|bike| bike.shifted(offset)))
+        .collect();
+    let cars: Vec<Car> = a
+        .cars
+        .iter()
+        .copied()
+        .chain(b.cars.iter().map(|car| car.shifted(offset)))
+        .collect();
+    return Road::new(
+        bikes.try_into().expect("bike count was checked"),
+        cars.try_into().expect("car count was checked"),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, iter::zip};
+
+    use proptest::{prop_assert, prop_assert_eq, proptest};
+    use rand::{distributions::Bernoulli, thread_rng, Rng};
+
+    use crate::{
+        bike::{Bike, BikeBuilder},
+        car::{Car, CarBuilder, Direction, LaneChangePolicy},
+        pedestrian::{Pedestrian, WalkDirection},
+        probability::Probability,
+        proptest_defs::arb_rectangle_occupier,
+        rand_source::RandSource,
+        road::{
+            concat_roads, Conditions, Coord, Grade, Incident, LaneDrop, LateralFallback,
+            RectangleOccupier, Road, RoadCells, RoadOccupier, SpeedZone, StepReport, UpdateMode,
+            Vehicle,
+        },
+        simulation::IterationRecord,
+        stats::Units,
+    };
+
+    #[test]
+    fn bike_is_on_road() {
+        let bikes = [BikeBuilder::default().with_lateral_ignorance(Probability::ZERO)]
+            .map(|builder| builder.try_into().unwrap());
+        let road = Road::<1, 0, 20, 3, 3>::new(bikes, []).unwrap();
+
+        let new_position = road.get_bike(0).rectangle_occupation();
+
+        assert!(road.motor_lane_contains_occupier(&new_position));
+    }
+
+    #[test]
+    fn bike_is_on_road_after_update() {
+        let bikes = [BikeBuilder::default().with_lateral_ignorance(Probability::ZERO)]
+            .map(|builder| builder.try_into().unwrap());
+        let mut road = Road::<1, 0, 20, 3, 3>::new(bikes, []).unwrap();
+
+        road.update().unwrap();
+
+        let new_position = road.get_bike(0).rectangle_occupation();
+
+        assert!(road.road_contains_occupier(&new_position));
+    }
+
+    #[test]
+    fn bikes_same_size_after_update() {
+        let bikes = [BikeBuilder::default().with_lateral_ignorance(Probability::ZERO)]
+            .map(|builder| builder.try_into().unwrap());
+        let mut road = Road::<1, 0, 20, 3, 3>::new(bikes, []).unwrap();
+        let original_dims = road.bikes.map(|bike| {
+            let RectangleOccupier { width, length, .. } = bike.rectangle_occupation();
+            return (width, length);
+        });
+        road.update().unwrap();
+        let new_dims = road.bikes.map(|bike| {
+            let RectangleOccupier { width, length, .. } = bike.rectangle_occupation();
+            return (width, length);
+        });
+
+        assert_eq!(original_dims, new_dims);
+    }
+
+    #[test]
+    fn single_bike_multiple_updates_work() {
+        let bikes = [BikeBuilder::default()].map(|builder| builder.try_into().unwrap());
+        let mut road = Road::<1, 0, 20, 3, 3>::new(bikes, []).unwrap();
+
+        for iter_num in 0u16..1000 {
+            println!("iteration #{:?}", iter_num);
+            road.update().unwrap();
+        }
+
+        let new_position = road.get_bike(0).rectangle_occupation();
+
+        assert!(road.road_contains_occupier(&new_position));
+    }
+
+    #[test]
     fn single_bike_front_gap_works_as_expected() {
         let front_right = Coord { lat: 4, long: 16 };
         let bikes = [BikeBuilder::default().with_front_right_at(front_right)]
             .map(|builder| builder.try_into().unwrap());
         let road = Road::<1, 0, 20, 3, 3>::new(bikes, []).unwrap();
 
-        let bike_front_gap_1 = road
-            .front_gap(&road.get_bike(0).rectangle_occupation())
-            .expect("bike should have width");
-        let bike_front_gap_2 = road.cells.front_gap(&front_right, None);
+        let bike_front_gap_1 = road
+            .front_gap(&road.get_bike(0).rectangle_occupation())
+            .expect("bike should have width");
+        let bike_front_gap_2 = road.cells.front_gap(&front_right, None);
+
+        assert_eq!(bike_front_gap_1, bike_front_gap_2);
+        assert_eq!(bike_front_gap_1, 18)
+    }
+
+    proptest! {
+        #[test]
+        fn cars_follow_safely_proptest(
+            seed: u64,
+            spacing in 5..15isize,
+            speed in 0..=10isize,
+            deceleration_prob in 0.0..=1.0f64,
+        ) {
+            let cars = [0, 1, 2, 3].map(|index| {
+                CarBuilder::default()
+                    .with_front_at(index * spacing)
+                    .with_speed(speed)
+                    .with_deceleration_prob(Probability::new(deceleration_prob).unwrap())
+                    .build()
+                    .unwrap()
+            });
+            let mut road = Road::<0, 4, 60, 0, 14>::new([], cars).unwrap();
+            road.reseed(seed);
+
+            for _ in 0..20 {
+                road.update().unwrap();
+                road.assert_safe_following();
+            }
+        }
+
+        #[test]
+        fn stored_cells_match_fresh_occupied_cells_proptest(seed: u64, updates in 0..8usize) {
+            let mut road = random_mixed_road();
+            road.reseed(seed);
+            for _ in 0..updates {
+                road.update().unwrap();
+            }
+
+            let fresh = RoadCells::<100, 7, 7>::try_from(&road).unwrap();
+            prop_assert_eq!(&road.cells.cells, &fresh.cells);
+            prop_assert_eq!(&road.cells.car_cells_by_lat, &fresh.car_cells_by_lat);
+            for car in road.cars() {
+                // rebuilt from its speed and position rather than its stored shape
+                let rebuilt = CarBuilder::from(car).build_coasting().unwrap();
+                prop_assert_eq!(car.rectangle_occupation(), rebuilt.rectangle_occupation());
+                prop_assert_eq!(
+                    car.occupied_cells().collect::<Vec<_>>(),
+                    rebuilt.occupied_cells().collect::<Vec<_>>()
+                );
+            }
+        }
+
+        #[test]
+        fn front_gap_counts_free_cells_between_bikes_proptest(
+            follower_front in -40..40isize,
+            length in 1..5isize,
+            separation in 0..10isize,
+            right in 1..6isize,
+        ) {
+            const L: usize = 30;
+            let leader_front = follower_front + length + separation;
+            let bikes = [follower_front, leader_front].map(|front| {
+                BikeBuilder::default()
+                    .with_length(length)
+                    .unwrap()
+                    .with_front_right_at(Coord { lat: right, long: front })
+                    .build()
+                    .unwrap()
+            });
+            let road = Road::<2, 0, L, 3, 3>::new(bikes, []).unwrap();
+
+            let follower_gap = road.front_gap(&road.get_bike(0).rectangle_occupation());
+            let leader_gap = road.front_gap(&road.get_bike(1).rectangle_occupation());
+
+            prop_assert_eq!(follower_gap, Some(separation as usize));
+            // the leader's gap goes round the road to the follower's back
+            prop_assert_eq!(leader_gap, Some(L - 2 * length as usize - separation as usize));
+        }
+
+        #[test]
+        fn indexed_first_car_back_matches_scan_proptest(
+            seed: u64,
+            updates in 0..4usize,
+            lat in 0..14isize,
+            long in -200..200isize,
+            maybe_max in proptest::option::of(0..250usize),
+        ) {
+            let mut road = random_mixed_road();
+            road.reseed(seed);
+            for _ in 0..updates {
+                road.update().unwrap();
+            }
+
+            let coord = Coord { lat, long };
+            prop_assert_eq!(
+                road.cells.first_car_back(&coord, maybe_max),
+                road.cells.first_car_back_by_scan(&coord, maybe_max)
+            );
+        }
+
+        #[test]
+        fn single_bike_any_pos_update_works(
+            right in 1..6isize,
+            speed in 0..=6isize,
+            front in 0..20isize,
+        ) {
+            let bikes = [BikeBuilder::default().with_front_at(front).with_right_at(right).with_forward_speed(speed).unwrap()]
+                .map(|builder| builder.try_into().unwrap());
+            let mut road = Road::<1, 0, 20, 3, 3>::new(bikes, []).unwrap();
+
+            println!("{}", road.cells);
+            road.update().unwrap();
+            println!("{}", road.cells);
+
+            let new_position = road.get_bike(0).rectangle_occupation();
+
+            assert!(road.road_contains_occupier(&new_position));
+        }
+
+        #[test]
+        fn coord_index_round_trip_proptest(lat in 0..16isize, long: isize) {
+            let road = Road::<0, 0, 30, 2, 14>::new([], []).unwrap();
+            let coord = Coord { lat, long };
+
+            let index = road.coord_to_index(coord).unwrap();
+
+            prop_assert!(index < road.total_cells());
+            prop_assert_eq!(
+                road.index_to_coord(index).unwrap(),
+                Coord { lat, long: long.rem_euclid(30) }
+            );
+        }
+    }
+
+    #[test]
+    fn every_index_round_trips() {
+        let road = Road::<0, 0, 30, 2, 14>::new([], []).unwrap();
+
+        for index in 0..road.total_cells() {
+            assert_eq!(
+                road.coord_to_index(road.index_to_coord(index).unwrap())
+                    .unwrap(),
+                index
+            );
+        }
+        assert!(road.index_to_coord(road.total_cells()).is_err());
+        assert!(road.coord_to_index(Coord { lat: 16, long: 0 }).is_err());
+        assert!(road.coord_to_index(Coord { lat: -1, long: 0 }).is_err());
+    }
+
+    #[test]
+    fn multiple_bikes_multiple_updates_work() -> anyhow::Result<()> {
+        let bikes: [Bike; 5] = [0, 3, 6, 9, 12]
+            .map(|front| BikeBuilder::default().with_front_at(front))
+            .map(|builder| builder.try_into().unwrap());
+        for (bike_id, bike) in bikes.iter().enumerate() {
+            println!("bike {}", bike_id);
+            let occupied_cells: Vec<Coord> = bike.occupied_cells().collect();
+            println!("occupied cells: {:?}", occupied_cells)
+        }
+        let mut road = Road::<5, 0, 20, 3, 3>::new(bikes, []).unwrap();
+
+        for iter_num in 0u16..1000 {
+            println!("iteration #{:?}", iter_num);
+            println!("road cells:");
+            println!("{}", road.cells);
+            road.update().unwrap();
+        }
+
+        println!("{:?}", road);
+        return Ok(());
+    }
+
+    #[test]
+    fn single_bike_lateral_update_works() {
+        let bikes =
+            [BikeBuilder::deterministic_default()].map(|builder| builder.try_into().unwrap());
+        let mut road = Road::<1, 0, 20, 3, 3>::new(bikes, []).unwrap();
+
+        road.bikes_lateral_update().unwrap();
+
+        let new_position = road.get_bike(0).rectangle_occupation();
+
+        assert!(road.road_contains_occupier(&new_position));
+    }
+
+    #[test]
+    fn single_bike_forward_update_works() {
+        let bikes =
+            [BikeBuilder::deterministic_default()].map(|builder| builder.try_into().unwrap());
+        let mut road = Road::<1, 0, 20, 3, 3>::new(bikes, []).unwrap();
+
+        road.bikes_forward_update().unwrap();
+
+        let new_position = road.get_bike(0).rectangle_occupation();
+
+        assert!(road.road_contains_occupier(&new_position));
+    }
+
+    #[test]
+    fn single_bike_forward_update_works_as_expected() -> anyhow::Result<()> {
+        let bikes = [
+            BikeBuilder::default()
+                .with_front_at(2) // 2
+                .with_forward_speed(3)? // + 3 = 5
+                .with_forward_acceleration(1)? // + 1 = 6
+                .with_forward_max_speed(10)? // min(6, 10) = 6
+                .with_deceleration_prob(Probability::ZERO), // - 0 = 6
+        ]
+        .map(|builder| builder.try_into().unwrap());
+        let mut road = Road::<1, 0, 20, 3, 3>::new(bikes, []).unwrap();
+
+        road.bikes_forward_update().unwrap();
+
+        let RectangleOccupier {
+            front: new_front, ..
+        } = road.get_bike(0).rectangle_occupation();
+
+        assert_eq!(new_front, 6);
+        return Ok(());
+    }
+
+    #[test]
+    fn single_bike_next_forward_works_as_expected() -> anyhow::Result<()> {
+        let bikes = [
+            BikeBuilder::default()
+                .with_front_at(2) // 2
+                .with_forward_speed(3)? // + 3 = 5
+                .with_forward_acceleration(1)? // + 1 = 6
+                .with_forward_max_speed(10)? // min(6, 10) = 6
+                .with_deceleration_prob(Probability::ZERO), // - 0 = 6
+        ]
+        .map(|builder| builder.try_into().unwrap());
+        let road = Road::<1, 0, 20, 3, 3>::new(bikes, []).unwrap();
+
+        let [next_bike] = road.next_bikes_forward(&mut thread_rng());
+
+        let RectangleOccupier {
+            front: new_front, ..
+        } = next_bike.rectangle_occupation();
+
+        assert_eq!(new_front, 6);
+        return Ok(());
+    }
+
+    #[test]
+    fn single_bike_forward_update_speeds_up_as_expected() -> anyhow::Result<()> {
+        let speed = 3;
+        let acceleration = 1;
+        let expected_speed = speed + acceleration;
+        let bikes = [
+            BikeBuilder::default()
+                .with_forward_speed(speed)?
+                .with_forward_acceleration(acceleration)?
+                .with_forward_max_speed(expected_speed + 25)? // too big to matter
+                .with_deceleration_prob(Probability::ZERO), // won't be messed up
+        ]
+        .map(|builder| builder.try_into().unwrap());
+        let road = Road::<1, 0, 20, 3, 3>::new(bikes, []).unwrap();
+
+        let [Bike { forward_speed, .. }] = road.next_bikes_forward(&mut thread_rng());
+
+        assert_eq!(forward_speed, expected_speed);
+        return Ok(());
+    }
+
+    #[test]
+    fn single_bike_lateral_and_forward_update_works() {
+        let bikes =
+            [BikeBuilder::deterministic_default()].map(|builder| builder.try_into().unwrap());
+        let mut road = Road::<1, 0, 20, 3, 3>::new(bikes, []).unwrap();
+
+        road.bikes_lateral_update().unwrap();
+        road.bikes_forward_update().unwrap();
+
+        let new_position = road.get_bike(0).rectangle_occupation();
+
+        assert!(road.road_contains_occupier(&new_position));
+    }
+
+    #[test]
+    fn rectangle_front_cells_correct() {
+        let occupation = RectangleOccupier {
+            front: 2,
+            right: 5,
+            width: 3,
+            length: 3,
+        };
+
+        let front: Vec<Coord> = occupation.front_cells().collect();
+
+        assert_eq!(
+            front,
+            vec![
+                Coord { lat: 3, long: 2 },
+                Coord { lat: 4, long: 2 },
+                Coord { lat: 5, long: 2 }
+            ]
+        )
+    }
+
+    #[test]
+    fn rectangle_occupation_orders() {
+        let occupation = RectangleOccupier {
+            front: 13,
+            right: 4,
+            width: 3,
+            length: 4,
+        };
+
+        let lat_major: Vec<Coord> = occupation.occupied_cells().collect();
+        let long_major: Vec<Coord> = occupation.occupied_cells_long_major().collect();
+
+        assert_eq!(lat_major[0], Coord { lat: 2, long: 10 });
+        assert_eq!(lat_major[1], Coord { lat: 2, long: 11 });
+        assert_eq!(lat_major[11], Coord { lat: 4, long: 13 });
+        assert_eq!(long_major[0], Coord { lat: 2, long: 10 });
+        assert_eq!(long_major[1], Coord { lat: 3, long: 10 });
+        assert_eq!(long_major[11], Coord { lat: 4, long: 13 });
+        assert_eq!(
+            lat_major.iter().collect::<HashSet<_>>(),
+            long_major.iter().collect::<HashSet<_>>()
+        );
+    }
+
+    #[test]
+    fn rectangle_occupies_cells_correct() {
+        let width = 2;
+        let length = 2;
+        let area = width * length;
+        let occupation = RectangleOccupier {
+            front: 2,
+            right: 5,
+            width,
+            length,
+        };
+
+        let cells: HashSet<Coord> = occupation.occupied_cells().collect();
+
+        println!("occupier: {:?}", occupation);
+
+        assert_eq!(occupation.occupied_cells().count(), area);
+        assert_eq!(cells.len(), area);
+        assert_eq!(
+            cells,
+            HashSet::from([
+                Coord { lat: 4, long: 1 },
+                Coord { lat: 5, long: 1 },
+                Coord { lat: 4, long: 2 },
+                Coord { lat: 5, long: 2 }
+            ])
+        );
+    }
+
+    #[test]
+    fn rectangle_straddling_road_start_occupies_wrapped_cells() {
+        let bikes = [BikeBuilder::default()
+            .with_dimensions((1, 3))
+            .unwrap()
+            .with_front_right_at(Coord { lat: 0, long: 1 })
+            .build()
+            .unwrap()];
+        let road = Road::<1, 0, 4, 1, 0>::new(bikes, []).unwrap();
+
+        let cells: Vec<Coord> = road.get_bike(0).occupied_cells().collect();
+        assert_eq!(
+            cells.iter().map(|coord| coord.long).collect::<Vec<_>>(),
+            [-1, 0, 1]
+        );
+        let mut wrapped_longs: Vec<isize> = road
+            .cells()
+            .cells()
+            .keys()
+            .map(|coord| coord.long)
+            .collect();
+        wrapped_longs.sort();
+        assert_eq!(wrapped_longs, [0, 1, 3]);
+    }
+
+    #[test]
+    fn front_gap_on_road_barely_longer_than_car() {
+        let bikes = [BikeBuilder::deterministic_default()
+            .with_front_right_at(Coord { lat: 5, long: 1 })
+            .with_width(1)
+            .unwrap()
+            .build()
+            .unwrap()];
+        let cars = [CarBuilder::default()
+            .with_front_at(4)
+            .with_deceleration_prob(Probability::ZERO)
+            .build()
+            .unwrap()];
+        let mut road = Road::<1, 1, 6, 3, 3>::new(bikes, cars).unwrap();
+
+        // the car's own back is all that's ahead of it
+        assert_eq!(road.car_front_gap(0), Some(1));
+        assert_eq!(
+            road.cells.front_gap(&Coord { lat: 0, long: 4 }, Some(20)),
+            1
+        );
+        assert_eq!(road.cells.front_gap(&Coord { lat: 5, long: 1 }, None), 4);
+        // neither vehicle drives through its own back, which a car doing a
+        // full lap of the road each update would
+        for _ in 0..20 {
+            road.update().unwrap();
+            assert_eq!(road.car_front_gap(0), Some(1));
+            assert!(road.cars()[0].speed() <= 1);
+            assert!(road.get_bike(0).forward_speed <= 6 - 2);
+        }
+        assert_eq!(road.cars()[0].speed(), 1);
+
+        let mut lone_car_road = Road::<0, 1, 6, 3, 3>::new([], cars).unwrap();
+        for _ in 0..20 {
+            lone_car_road.update().unwrap();
+            assert!(lone_car_road.cars()[0].speed() <= 1);
+        }
+    }
+
+    #[test]
+    fn first_car_back_gap_wraps_around_road() {
+        let car_at_speed = |speed| {
+            CarBuilder::default()
+                .with_front_at(18)
+                .with_speed(speed)
+                .build()
+                .unwrap()
+        };
+        let coord = Coord { lat: 1, long: 2 };
+
+        let road = Road::<0, 1, 20, 3, 7>::new([], [car_at_speed(0)]).unwrap();
+        let (car, gap) = road.first_car_back_with_gap(&coord, None).unwrap();
+        assert_eq!(car.front(), 18);
+        assert_eq!(gap, 3);
+        assert_eq!(
+            road.first_car_back_with_gap(&coord, Some(4))
+                .map(|(_, gap)| gap),
+            None
+        );
+        assert_eq!(
+            road.first_car_back_with_gap(&coord, Some(5))
+                .map(|(_, gap)| gap),
+            Some(3)
+        );
+        // can only speed up to 2
+        assert!(!road.is_blocking(&coord, None));
+
+        let road = Road::<0, 1, 20, 3, 7>::new([], [car_at_speed(3)]).unwrap();
+        assert!(road.is_blocking(&coord, None));
+    }
+
+    #[test]
+    fn inserting_off_road_coord_is_a_descriptive_error() {
+        let mut cells = RoadCells::<10, 3, 3>::empty(0);
+
+        let error = cells
+            .insert(Coord { lat: -1, long: 4 }, Vehicle::Car(2))
+            .unwrap_err();
+
+        assert_eq!(
+            format!("{:#}", error),
+            "Car(2) has a cell off the road at Coord { lat: -1, long: 4 }: lat value -1 was less than 0"
+        );
+        assert!(cells.cells().is_empty());
+    }
+
+    #[test]
+    fn empty_road_displays_blank_grid() {
+        let road = Road::<0, 0, 10, 3, 3>::new([], []).unwrap();
+
+        let repr = format!("{}", road.cells());
+
+        let rows: Vec<&str> = repr.lines().collect();
+        assert_eq!(rows.len(), 11);
+        assert_eq!(rows[0], "    0  1  2  3  4  5");
+        for (long, row) in rows[1..].iter().enumerate() {
+            assert_eq!(*row, format!("{}|         |        |", long));
+        }
+    }
+
+    #[test]
+    fn front_gap_works() {
+        let bikes = [
+            BikeBuilder::default().with_front_right_at(Coord { lat: 3, long: 3 }),
+            BikeBuilder::default().with_front_right_at(Coord { lat: 3, long: 10 }),
+        ]
+        .map(|builder| builder.try_into().unwrap());
+        let road = Road::<2, 0, 20, 3, 3>::new(bikes, []).unwrap();
+
+        let trailing_bike = road.get_bike(0);
+
+        let front_gap = road
+            .front_gap(&trailing_bike.rectangle_occupation())
+            .unwrap();
+
+        assert_eq!(front_gap, 5);
+    }
+
+    #[test]
+    fn cells_front_gap_works() {
+        /*
+        0 1 2 3 4 5 6 7 8 9 10
+              t             l
+            x x           x x
+                - - - - >
+        length 5
+        */
+        let trailing_coord = Coord { lat: 3, long: 3 };
+        let leading_coord = Coord { lat: 3, long: 10 };
+        let dimensions = (2, 2);
+        let bikes = [
+            BikeBuilder::default()
+                .with_dimensions(dimensions)
+                .unwrap()
+                .with_front_right_at(trailing_coord),
+            BikeBuilder::default()
+                .with_dimensions(dimensions)
+                .unwrap()
+                .with_front_right_at(leading_coord),
+        ]
+        .map(|builder| builder.try_into().unwrap());
+        let road = Road::<2, 0, 20, 3, 3>::new(bikes, []).unwrap();
+
+        let front_gap = road.cells.front_gap(&trailing_coord, None);
+
+        assert_eq!(front_gap, 5);
+    }
+
+    #[test]
+    fn route_width_works_bike() {
+        /*
+        right ->
+          0 1 2¦3 4 5 ^ back
+        0
+        1
+        2     \ ^
+        3 . . < x
+        4
+
+        long in [2, 3] => width = 2
+        otherwise => width = 6
+        */
+
+        let long = 3;
+        let lat = 3;
+        let dimensions = (2, 2);
+        let bikes = [BikeBuilder::default()
+            .with_dimensions(dimensions)
+            .unwrap()
+            .with_front_at(long)
+            .with_right_at(lat)]
+        .map(|builder| builder.try_into().unwrap());
+        let road = Road::<1, 0, 20, 3, 3>::new(bikes, []).unwrap();
+
+        assert_eq!(road.route_width(0), 6);
+        assert_eq!(road.route_width(1), 6);
+        assert_eq!(road.route_width(2), 2);
+        assert_eq!(road.route_width(3), 2);
+        assert_eq!(road.route_width(4), 6);
+        assert_eq!(road.route_width(5), 6);
+        assert_eq!(road.route_width(21), 6);
+        assert_eq!(road.route_width(22), 2);
+        assert_eq!(road.route_width(23), 2);
+        assert_eq!(road.route_width(24), 6);
+    }
+
+    #[test]
+    fn bike_cannot_route_through_single_cell_gap() {
+        // bikes at lats 0 to 1 and 3 to 4 leave a one cell gap at lat 2
+        let bikes = [1, 4].map(|right| {
+            BikeBuilder::default()
+                .with_front_right_at(Coord {
+                    lat: right,
+                    long: 3,
+                })
+                .build()
+                .unwrap()
+        });
+        let road = Road::<2, 0, 20, 2, 3>::new(bikes, []).unwrap();
+
+        assert!(road.passable_width_for(3, 1));
+        assert!(!road.passable_width_for(3, 2));
+        assert!(road.passable_width_for(4, 5));
+        assert!(!road.passable_width_for(4, 6));
+        assert!(!road.passable_width_for(23, 2));
+    }
+
+    #[test]
+    fn route_width_works_car() {
+        /*
+        where a car is always has 0 route width
+        */
+
+        let long = 3;
+        let cars =
+            [CarBuilder::default().with_front_at(long)].map(|builder| builder.try_into().unwrap());
+        const ROAD_LEN: usize = 20;
+        const ROAD_WID: usize = 14;
+        let road = Road::<0, 1, ROAD_LEN, 0, ROAD_WID>::new([], cars).unwrap();
+
+        println!("cells:\n{}", road.cells());
+        let car_longs: HashSet<isize> = road
+            .get_car(0)
+            .occupied_cells()
+            .map(|coord| coord.long.rem_euclid(ROAD_LEN as isize))
+            .collect();
+        println!("occupied longs: {:?}", car_longs);
+        for long in 0..ROAD_LEN as isize {
+            println!("long: {}", long);
+            let expected_width = match car_longs.contains(&long) {
+                true => {
+                    println!("expected 0 width as car is here");
+                    0
+                }
+                false => {
+                    println!("expected full width as car is not here");
+                    ROAD_WID
+                }
+            };
+            assert_eq!(road.route_width(long), expected_width);
+        }
+    }
+
+    #[test]
+    fn cells_front_gap_works_no_space() {
+        /*
+        0 1 2 3 4 5 6 7 8 9 10
+              t   l
+            x x o o
+                >
+        length 0
+        */
+        let trailing_coord = Coord { lat: 3, long: 3 };
+        let leading_coord = Coord { lat: 3, long: 5 };
+        let dimensions = (2, 2);
+        let bikes = [
+            BikeBuilder::default()
+                .with_dimensions(dimensions)
+                .unwrap()
+                .with_front_right_at(trailing_coord),
+            BikeBuilder::default()
+                .with_dimensions(dimensions)
+                .unwrap()
+                .with_front_right_at(leading_coord),
+        ]
+        .map(|builder| builder.try_into().unwrap());
+        let road = Road::<2, 0, 20, 3, 3>::new(bikes, []).unwrap();
+
+        let front_gap = road.cells.front_gap(&trailing_coord, None);
+
+        assert_eq!(front_gap, 0);
+    }
+
+    #[test]
+    fn bike_is_where_expected() {
+        /*
+        0 1 2 3 4 5 6 7 8 9 10
+              t             l
+            x x           x x
+                - - - - >
+        length 5
+        */
+        let coord = Coord { lat: 3, long: 10 };
+        let dimensions = (2, 2);
+        let bikes = [BikeBuilder::default()
+            .with_dimensions(dimensions)
+            .unwrap()
+            .with_front_right_at(coord)]
+        .map(|builder| builder.try_into().unwrap());
+        let road = Road::<1, 0, 20, 3, 3>::new(bikes, []).unwrap();
+
+        let bike = road.get_bike(0);
+        let occupied_coords: Vec<Coord> = bike.rectangle_occupation().occupied_cells().collect();
+
+        println!("occupation: {:?}", bike.rectangle_occupation());
+        println!("occupied coords: {:?}", occupied_coords);
+        println!("length: {}", occupied_coords.len());
+
+        let found_vehicle = road.cells.get(&coord).unwrap().unwrap();
+
+        assert_eq!(*found_vehicle, Vehicle::Bike(0));
+    }
+
+    #[test]
+    fn rectangle_occupies_cells_correct_size_larger() {
+        let width = 3;
+        let length = 4;
+        let area = width * length;
+        let occupier = RectangleOccupier {
+            front: 2,
+            right: 5,
+            width,
+            length,
+        };
+        println!("occupier: {:?}", occupier);
+
+        assert_eq!(occupier.occupied_cells().count(), area);
+    }
+
+    #[test]
+    fn rectangle_occupier_correct_size() {
+        let width = 2;
+        let length = 2;
+        let area = width * length;
+        let occupier = RectangleOccupier {
+            front: 2,
+            right: 2,
+            width,
+            length,
+        };
+
+        assert_eq!(occupier.occupied_cells().count(), area)
+    }
+
+    proptest!(
+        #[test]
+        fn rectangle_occupier_correct_size_proptest(occupier in arb_rectangle_occupier(-10_000..10_000, -100..100, 10, 10)) {
+            let area = occupier.width * occupier.length;
+            println!("occupier: {:?}", occupier);
+            prop_assert_eq!(occupier.occupied_cells().count(), area)
+        }
+
+        #[test]
+        fn rectangle_occupier_correct_width_proptest(occupier: RectangleOccupier) {
+            println!("occupier: {:?}", occupier);
+            prop_assert_eq!(occupier.width_iterator().count(), occupier.width);
+        }
+    );
+
+    #[test]
+    fn rectangle_occupier_correct_size_v2() {
+        let width = 2;
+        let length = 2;
+        let area = width * length;
+        let occupier = RectangleOccupier {
+            front: 2,
+            right: 2,
+            width,
+            length,
+        };
+
+        println!("occupier: {:?}", occupier);
+        assert_eq!(occupier.occupied_cells().count(), area)
+    }
+
+    #[test]
+    fn rectangle_area_and_center() {
+        let square = RectangleOccupier {
+            front: 2,
+            right: 2,
+            width: 2,
+            length: 2,
+        };
+        assert_eq!(square.area(), 4);
+        assert_eq!(square.center(), (1.5, 1.5));
+
+        let rectangle = RectangleOccupier {
+            front: 10,
+            right: 5,
+            width: 3,
+            length: 4,
+        };
+        assert_eq!(rectangle.area(), 12);
+        assert_eq!(rectangle.occupied_cells().count(), rectangle.area());
+        // lats 3 to 5 and longs 7 to 10
+        assert_eq!(rectangle.center(), (4.0, 8.5));
+    }
+
+    #[test]
+    fn rectangle_width_correct() {
+        let width = 2;
+        let occupier = RectangleOccupier {
+            front: 2,
+            right: 2,
+            width,
+            length: 2,
+        };
+
+        assert_eq!(occupier.width_iterator().count(), width)
+    }
+
+    #[test]
+    fn rectangle_length_correct() {
+        let length = 2;
+        let occupier = RectangleOccupier {
+            front: 2,
+            right: 2,
+            width: 2,
+            length,
+        };
+
+        assert_eq!(occupier.length_iterator().count(), length)
+    }
+
+    #[test]
+    fn positions_on_nm_lane_higher_priority_than_m_lane() {
+        let _road = Road::<1, 0, 100, 7, 7>::new([BikeBuilder::default().build().unwrap()], []);
+    }
+
+    #[test]
+    fn medium_sized_example_road_builds() {
+        let _road: Road<10, 10, 100, 7, 7> = {
+            let bikes: Vec<Bike> = (0..10)
+                .map(|bike_id| {
+                    return BikeBuilder::default()
+                        .with_front_at(10 * bike_id)
+                        .with_right_at(8)
+                        .build()
+                        .unwrap();
+                })
+                .collect();
+            for bike in &bikes {
+                println!(
+                    "occupied_cells: {:?}",
+                    bike.occupied_cells().collect::<Vec<Coord>>()
+                )
+            }
+            let cars: Vec<Car> = (0..10)
+                .map(|car_id| {
+                    return CarBuilder::default()
+                        .with_front_at(10 * car_id)
+                        .build()
+                        .unwrap();
+                })
+                .collect();
+            for car in &cars {
+                println!(
+                    "occupied_cells: {:?}",
+                    car.occupied_cells().collect::<Vec<Coord>>()
+                )
+            }
+            Road::new(
+                bikes.try_into().expect("should be right number of bikes"),
+                cars.try_into().expect("should be right number of cars"),
+            )
+            .unwrap()
+        };
+    }
+
+    #[test]
+    fn medium_sized_example_road_updates() {
+        let mut road: Road<10, 10, 100, 7, 7> = {
+            let bikes: Vec<Bike> = (0..10)
+                .map(|bike_id| {
+                    return BikeBuilder::default()
+                        .with_front_at(10 * bike_id)
+                        .with_right_at(8)
+                        .build()
+                        .unwrap();
+                })
+                .collect();
+            let cars: Vec<Car> = (0..10)
+                .map(|car_id| {
+                    return CarBuilder::default()
+                        .with_front_at(10 * car_id)
+                        .build()
+                        .unwrap();
+                })
+                .collect();
+            Road::new(
+                bikes.try_into().expect("should be right number of bikes"),
+                cars.try_into().expect("should be right number of cars"),
+            )
+            .unwrap()
+        };
+
+        for iter_num in 0u16..1000 {
+            println!("Iteration #{}", iter_num);
+            println!("{}", road.cells());
+            road.update().unwrap();
+        }
+    }
+
+    #[test]
+    fn one_car_one_bike_updates() {
+        let mut road: Road<1, 1, 10, 4, 4> = Road::new(
+            [BikeBuilder::default()
+                .with_front_at(0)
+                .with_right_at(7)
+                .build()
+                .unwrap()],
+            [CarBuilder::default().with_front_at(0).build().unwrap()],
+        )
+        .unwrap();
+
+        for iter_num in 0u16..60000 {
+            println!("Iteration #{}", iter_num);
+            println!("{}\n", road.cells());
+            road.update().unwrap();
+        }
+    }
+
+    #[test]
+    fn one_car_one_bike_updates_v2() {
+        let mut road: Road<1, 1, 10, 4, 4> = Road::new(
+            [BikeBuilder::default()
+                .with_front_at(5)
+                .with_right_at(5)
+                .with_forward_speed(3)
+                .unwrap()
+                .build()
+                .unwrap()],
+            [CarBuilder::default().with_front_at(0).build().unwrap()],
+        )
+        .unwrap();
+
+        println!("{}", road.cells());
+        road.update().unwrap();
+    }
+
+    #[test]
+    fn car_occupation_correct() {
+        let cars = [CarBuilder::default()].map(|builder| builder.try_into().unwrap());
+        let road = Road::<0, 1, 20, 3, 3>::new([], cars).unwrap();
+
+        let car_occupation: HashSet<Coord> = road.get_car(0).occupied_cells().collect();
+        let cells_occupation: HashSet<Coord> = road
+            .cells()
+            .cells()
+            .keys()
+            .map(|coord| coord.to_owned())
+            .collect();
+
+        assert_eq!(car_occupation, cells_occupation);
+    }
+
+    fn dense_reverse_id_car_road(update_mode: UpdateMode) -> Road<0, 4, 24, 0, 14> {
+        // cars packed nose to tail with the leader having the lowest id, so
+        // only the ordered update lets the followers use the space freed up
+        // by the car in front of them within the same step
+        let cars = [19, 14, 9, 4].map(|front| {
+            CarBuilder::default()
+                .with_front_at(front)
+                .with_deceleration_prob(Probability::ZERO)
+                .build()
+                .unwrap()
+        });
+        let mut road = Road::new([], cars).unwrap();
+        road.set_update_mode(update_mode);
+        return road;
+    }
+
+    #[test]
+    fn update_modes_diverge_and_stay_collision_free() {
+        let mut synchronous = dense_reverse_id_car_road(UpdateMode::Synchronous);
+        let mut ordered = dense_reverse_id_car_road(UpdateMode::Ordered);
+
+        for _ in 0..10 {
+            synchronous.update().unwrap();
+            ordered.update().unwrap();
+            for road in [&synchronous, &ordered] {
+                let expected_cells: usize = road
+                    .cars
+                    .iter()
+                    .map(|car| car.occupied_cells().count())
+                    .sum();
+                assert_eq!(road.cells().cells().len(), expected_cells);
+            }
+        }
+
+        let synchronous_fronts = synchronous.cars.map(|car| car.front());
+        let ordered_fronts = ordered.cars.map(|car| car.front());
+        assert_ne!(synchronous_fronts, ordered_fronts);
+    }
+
+    #[test]
+    fn ordered_update_moves_followers_into_freed_space() {
+        let mut synchronous = dense_reverse_id_car_road(UpdateMode::Synchronous);
+        let mut ordered = dense_reverse_id_car_road(UpdateMode::Ordered);
+
+        synchronous.update().unwrap();
+        ordered.update().unwrap();
+
+        assert_eq!(synchronous.cars.map(|car| car.speed()), [2, 0, 0, 0]);
+        assert_eq!(ordered.cars.map(|car| car.speed()), [2, 2, 2, 2]);
+    }
+
+    #[test]
+    fn occupier_group_is_union_of_members() {
+        let back = RectangleOccupier {
+            front: 3,
+            right: 1,
+            width: 2,
+            length: 2,
+        };
+        let ahead = RectangleOccupier {
+            front: 8,
+            right: 5,
+            width: 2,
+            length: 3,
+        };
+        let group: &[RectangleOccupier] = &[back, ahead];
+
+        let group_cells: HashSet<Coord> = group.occupied_cells().collect();
+        let member_cells: HashSet<Coord> = back
+            .occupied_cells()
+            .chain(ahead.occupied_cells())
+            .collect();
+
+        assert_eq!(group.occupied_cells().count(), 4 + 6);
+        assert_eq!(group_cells, member_cells);
+        assert!(group.occupier_is_within(1));
+        assert!(!group.occupier_is_entirely_within(5));
+        assert!(group.occupier_is_entirely_within(6));
+    }
+
+    #[test]
+    fn occupier_group_collides_if_any_member_does() {
+        let bikes = [BikeBuilder::default().with_front_right_at(Coord { lat: 5, long: 10 })]
+            .map(|builder| builder.build().unwrap());
+        let road = Road::<1, 0, 20, 3, 3>::new(bikes, []).unwrap();
+        let clear = RectangleOccupier {
+            front: 3,
+            right: 1,
+            width: 2,
+            length: 2,
+        };
+        let colliding = RectangleOccupier {
+            front: 10,
+            right: 4,
+            width: 2,
+            length: 2,
+        };
+
+        let clear_group: &[RectangleOccupier] = &[clear];
+        let colliding_group: &[RectangleOccupier] = &[clear, colliding];
+
+        assert!(road.collisions_for(&clear_group).is_empty());
+        // the colliding member overlaps the bike in two cells
+        assert_eq!(
+            road.collisions_for(&colliding_group),
+            vec![&Vehicle::Bike(0), &Vehicle::Bike(0)]
+        );
+        assert!(road.road_contains_occupier(&colliding_group));
+    }
+
+    #[test]
+    fn speed_zones_compose_and_wrap() {
+        let mut road = Road::<0, 0, 100, 3, 3>::new([], []).unwrap();
+        road.add_speed_zone(SpeedZone {
+            long_range: 10..=30,
+            max_speed: 5,
+        })
+        .unwrap();
+        road.add_speed_zone(SpeedZone {
+            long_range: 25..=40,
+            max_speed: 3,
+        })
+        .unwrap();
+        road.add_speed_zone(SpeedZone {
+            long_range: 95..=104,
+            max_speed: 4,
+        })
+        .unwrap();
+
+        assert_eq!(road.speed_limit_at(9), None);
+        assert_eq!(road.speed_limit_at(10), Some(5));
+        assert_eq!(road.speed_limit_at(25), Some(3));
+        assert_eq!(road.speed_limit_at(30), Some(3));
+        assert_eq!(road.speed_limit_at(40), Some(3));
+        assert_eq!(road.speed_limit_at(41), None);
+        assert_eq!(road.speed_limit_at(94), None);
+        assert_eq!(road.speed_limit_at(95), Some(4));
+        assert_eq!(road.speed_limit_at(0), Some(4));
+        assert_eq!(road.speed_limit_at(4), Some(4));
+        assert_eq!(road.speed_limit_at(5), None);
+    }
+
+    #[test]
+    fn invalid_speed_zones_are_rejected() {
+        let mut road = Road::<0, 0, 100, 3, 3>::new([], []).unwrap();
+
+        assert!(road
+            .add_speed_zone(SpeedZone {
+                long_range: 10..=30,
+                max_speed: 0,
+            })
+            .is_err());
+        #[allow(clippy::reversed_empty_ranges)]
+        let empty_range = 30..=10;
+        assert!(road
+            .add_speed_zone(SpeedZone {
+                long_range: empty_range,
+                max_speed: 3,
+            })
+            .is_err());
+        assert!(road.speed_zones().is_empty());
+    }
+
+    #[test]
+    fn car_slows_in_speed_zone_and_accelerates_after() {
+        let zone_limit = 2;
+        let cars = [CarBuilder::default()
+            .with_front_at(0)
+            .with_deceleration_prob(Probability::ZERO)
+            .build()
+            .unwrap()];
+        let mut road = Road::<0, 1, 100, 0, 14>::new([], cars).unwrap();
+        road.add_speed_zone(SpeedZone {
+            long_range: 50..=59,
+            max_speed: zone_limit,
+        })
+        .unwrap();
+
+        let mut speed_entering_zone = None;
+        let mut in_zone_speeds = Vec::new();
+        let mut speeds_after_zone = Vec::new();
+        while speeds_after_zone.len() < 3 {
+            let speed_before = road.get_car(0).speed();
+            let was_in_zone = road.speed_limit_at(road.get_car(0).front()).is_some();
+            road.update().unwrap();
+            let speed = road.get_car(0).speed();
+            match (was_in_zone, in_zone_speeds.is_empty()) {
+                (true, true) => {
+                    speed_entering_zone = Some(speed_before);
+                    in_zone_speeds.push(speed);
+                }
+                (true, false) => in_zone_speeds.push(speed),
+                (false, false) => speeds_after_zone.push(speed),
+                (false, true) => (),
+            }
+        }
+
+        assert!(speed_entering_zone.unwrap() > zone_limit);
+        assert!(in_zone_speeds.iter().all(|&speed| speed == zone_limit));
+        assert!(speeds_after_zone.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(speeds_after_zone[0] > zone_limit);
+    }
+
+    #[test]
+    fn car_slows_climbing_and_speeds_up_descending() {
+        let cars = [CarBuilder::default()
+            .with_speed_max(6)
+            .unwrap()
+            .with_speed(6)
+            .with_deceleration_prob(Probability::ZERO)
+            .build()
+            .unwrap()];
+        let mut road = Road::<0, 1, 200, 0, 14>::new([], cars).unwrap();
+        road.add_grade(Grade {
+            long_range: 30..=69,
+            delta_speed_max: -4,
+        })
+        .unwrap();
+        road.add_grade(Grade {
+            long_range: 70..=129,
+            delta_speed_max: 2,
+        })
+        .unwrap();
+        assert_eq!(road.grade_at(29), 0);
+        assert_eq!(road.grade_at(69), -4);
+
+        let mut uphill_speeds = Vec::new();
+        let mut downhill_speeds = Vec::new();
+        while road.cars()[0].front() < 130 {
+            let front = road.cars()[0].front();
+            road.update().unwrap();
+            let speed = road.cars()[0].speed();
+            match front {
+                30..=69 => uphill_speeds.push(speed),
+                70..=129 => downhill_speeds.push(speed),
+                _ => {}
+            }
+        }
+
+        // dropping straight to the lowered max speed climbing
+        assert!(uphill_speeds.iter().all(|&speed| speed == 2));
+        // but building back up past the usual max speed descending
+        assert!(downhill_speeds.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(downhill_speeds.first(), Some(&4));
+        assert_eq!(downhill_speeds.last(), Some(&8));
+    }
+
+    #[test]
+    fn polite_car_declines_lane_change_cutting_off_follower() {
+        // the first car is stuck behind the slow car in the right lane, while
+        // the fast car in the left lane would have a single cell to brake in
+        // behind it
+        let lane_change = |politeness, follower_front| {
+            let cars = [(24, 2, 7, 10), (30, 0, 7, 1), (follower_front, 8, 0, 10)].map(
+                |(front, speed, left, speed_max)| {
+                    CarBuilder::default()
+                        .with_front_at(front)
+                        .with_speed(speed)
+                        .with_speed_max(speed_max)
+                        .unwrap()
+                        .with_left_at(left)
+                        .unwrap()
+                        .with_politeness(politeness)
+                        .unwrap()
+                        .with_deceleration_prob(Probability::ZERO)
+                        .build()
+                        .unwrap()
+                },
+            );
+            let mut road = Road::<0, 3, 100, 0, 14>::new([], cars).unwrap();
+            road.set_car_lane_width(Some(7)).unwrap();
+            let lefts_before = road.cars.map(|car| car.left());
+            let report = road.update().unwrap();
+            let lane_changes = zip(road.cars.map(|car| car.left()), lefts_before)
+                .filter(|(left, left_before)| left != left_before)
+                .count();
+            assert_eq!(report.lane_changes, lane_changes);
+            return road.cars()[0].left();
+        };
+
+        assert_eq!(lane_change(0.0, 18), 0);
+        assert_eq!(lane_change(1.0, 18), 7);
+        // with the fast car well back there's no one to cut off
+        assert_eq!(lane_change(1.0, 80), 0);
+    }
+
+    #[test]
+    fn fast_car_overtakes_slow_car_on_the_left() {
+        // the fast car starts closing in on the slow car in the right lane
+        let overtake = |lane_change_policy| {
+            let cars = [(20, 5, 10), (30, 1, 1)].map(|(front, speed, speed_max)| {
+                CarBuilder::default()
+                    .with_front_at(front)
+                    .with_speed(speed)
+                    .with_speed_max(speed_max)
+                    .unwrap()
+                    .with_left_at(7)
+                    .unwrap()
+                    .with_lane_change_policy(lane_change_policy)
+                    .with_deceleration_prob(Probability::ZERO)
+                    .build()
+                    .unwrap()
+            });
+            let mut road = Road::<0, 2, 200, 0, 14>::new([], cars).unwrap();
+            road.set_car_lane_width(Some(7)).unwrap();
+            let mut lefts = Vec::new();
+            // the fast car gains 9 cells a step when clear, well short of
+            // lapping the slow car
+            let mut lead = -10;
+            for _ in 0..10 {
+                road.update().unwrap();
+                lead += road.cars()[0].speed() - road.cars()[1].speed();
+                lefts.push(road.cars.map(|car| car.left()));
+            }
+            assert!(0 < lead, "fast car didn't get past, {} behind", -lead);
+            return lefts;
+        };
+
+        for lane_change_policy in [LaneChangePolicy::Symmetric, LaneChangePolicy::KeepRight] {
+            let lefts = overtake(lane_change_policy);
+            assert_eq!(lefts[0], [0, 7]);
+            assert!(lefts.iter().all(|[_, slow_left]| *slow_left == 7));
+        }
+        // keeping right, the fast car moves back over once past
+        assert_eq!(overtake(LaneChangePolicy::Symmetric).last(), Some(&[0, 7]));
+        assert_eq!(overtake(LaneChangePolicy::KeepRight).last(), Some(&[7, 7]));
+    }
+
+    #[test]
+    fn car_lanes_fit_motor_lane() {
+        let mut road = Road::<0, 0, 100, 2, 14>::new([], []).unwrap();
+
+        assert!(road.set_car_lane_width(Some(0)).is_err());
+        assert!(road.set_car_lane_width(Some(15)).is_err());
+        road.set_car_lane_width(Some(7)).unwrap();
+        assert_eq!(road.car_lane_width(), Some(7));
+    }
+
+    #[test]
+    fn car_coasting_past_max_speed_saves_and_modifies() {
+        let cars = [CarBuilder::default()
+            .with_speed_max(6)
+            .unwrap()
+            .with_speed(6)
+            .with_deceleration_prob(Probability::ZERO)
+            .build()
+            .unwrap()];
+        let mut road = Road::<0, 1, 200, 0, 14>::new([], cars).unwrap();
+        road.add_grade(Grade {
+            long_range: 0..=199,
+            delta_speed_max: 2,
+        })
+        .unwrap();
+        road.update_until(10, |road| road.cars()[0].speed() == 8)
+            .unwrap();
+        assert_eq!(road.cars()[0].speed(), 8);
+        let file = tempfile::NamedTempFile::new().unwrap();
+
+        road.save_json(file.path()).unwrap();
+        let loaded = Road::<0, 1, 200, 0, 14>::load_json(file.path()).unwrap();
+        road.modify_car(0, |builder| *builder = builder.with_group(1))
+            .unwrap();
+
+        assert!(loaded.physically_eq(&road));
+        assert_eq!(road.cars()[0].speed(), 8);
+        assert_eq!(road.cars()[0].group(), 1);
+        // only the road's own rebuilding lets the speed past the max
+        assert!(CarBuilder::from(&road.cars()[0]).build().is_err());
+    }
+
+    #[test]
+    fn wet_conditions_halve_free_flow_speed() {
+        let free_flow_speed = |conditions| {
+            let cars = [CarBuilder::default()
+                .with_speed_max(10)
+                .unwrap()
+                .with_deceleration_prob(Probability::ZERO)
+                .build()
+                .unwrap()];
+            let mut road = Road::<0, 1, 200, 0, 14>::new([], cars).unwrap();
+            road.set_conditions(conditions).unwrap();
+            for _ in 0..20 {
+                road.update().unwrap();
+            }
+            return road.mean_car_speed().unwrap();
+        };
+
+        let clear_speed = free_flow_speed(Conditions::default());
+        let wet_speed = free_flow_speed(Conditions {
+            speed_factor: 0.5,
+            extra_decel_prob: Probability::ZERO,
+        });
+
+        assert_eq!(clear_speed, 10.0);
+        assert_eq!(wet_speed, 5.0);
+    }
+
+    #[test]
+    fn conditions_are_validated_and_clamped() {
+        let mut road = Road::<0, 0, 20, 0, 14>::new([], []).unwrap();
+        for conditions in [
+            Conditions {
+                speed_factor: 0.0,
+                extra_decel_prob: Probability::ZERO,
+            },
+            Conditions {
+                speed_factor: f32::NAN,
+                extra_decel_prob: Probability::ZERO,
+            },
+        ] {
+            assert!(road.set_conditions(conditions).is_err());
+        }
+        assert_eq!(road.conditions(), Conditions::default());
+
+        road.set_conditions(Conditions {
+            speed_factor: 0.01,
+            extra_decel_prob: Probability::new(0.2).unwrap(),
+        })
+        .unwrap();
+        assert_eq!(road.conditions_speed_max(5), 1);
+        assert_eq!(road.conditions_speed_max(0), 0);
+        road.set_conditions(Conditions {
+            speed_factor: 3.0,
+            extra_decel_prob: Probability::ZERO,
+        })
+        .unwrap();
+        assert_eq!(road.conditions_speed_max(5), 5);
+    }
+
+    #[test]
+    fn car_never_exceeds_its_speed_max() {
+        let cars = [CarBuilder::default()
+            .with_speed_max(3)
+            .unwrap()
+            .with_deceleration_prob(Probability::ZERO)
+            .build()
+            .unwrap()];
+        let mut road = Road::<0, 1, 100, 0, 14>::new([], cars).unwrap();
+
+        let mut speeds = Vec::new();
+        for _ in 0..50 {
+            road.update().unwrap();
+            speeds.push(road.get_car(0).speed());
+        }
+
+        assert!(speeds.iter().all(|&speed| speed <= 3));
+        assert_eq!(speeds.last(), Some(&3));
+    }
+
+    #[test]
+    fn lane_speed_limits_only_apply_in_their_lane() {
+        let bikes = [BikeBuilder::deterministic_default()
+            .with_right_at(13)
+            .build()
+            .unwrap()];
+        let cars = [CarBuilder::default()
+            .with_front_at(50)
+            .with_speed_max(5)
+            .unwrap()
+            .with_deceleration_prob(Probability::ZERO)
+            .build()
+            .unwrap()];
+        let mut road = Road::<1, 1, 100, 4, 10>::new(bikes, cars).unwrap();
+        assert!(road.set_bike_lane_speed_limit(Some(0)).is_err());
+        road.set_bike_lane_speed_limit(Some(2)).unwrap();
+
+        for _ in 0..20 {
+            road.update().unwrap();
+            assert!(road.get_bike(0).forward_speed <= 2);
+        }
+        assert_eq!(road.get_car(0).speed(), 5);
+
+        road.set_motor_lane_speed_limit(Some(1)).unwrap();
+        road.update().unwrap();
+        assert_eq!(road.get_car(0).speed(), 1);
+        assert_eq!(road.bike_lane_speed_limit(), Some(2));
+    }
+
+    fn random_mixed_road() -> Road<10, 10, 100, 7, 7> {
+        let bikes = [0, 10, 20, 30, 40, 50, 60, 70, 80, 90]
+            .map(|front| {
+                BikeBuilder::default()
+                    .with_front_at(front)
+                    .with_right_at(13)
+            })
+            .map(|builder| builder.build().unwrap());
+        let cars = [0, 10, 20, 30, 40, 50, 60, 70, 80, 90]
+            .map(|front| CarBuilder::default().with_front_at(front))
+            .map(|builder| builder.build().unwrap());
+        return Road::new(bikes, cars).unwrap();
+    }
+
+    fn next_outputs<
+        const B: usize,
+        const C: usize,
+        const L: usize,
+        const BLW: usize,
+        const MLW: usize,
+    >(
+        road: &mut Road<B, C, L, BLW, MLW>,
+        steps: usize,
+    ) -> Vec<String> {
+        return (0..steps)
+            .map(|_| {
+                road.update().unwrap();
+                format!(
+                    "{} {:?} {:?}",
+                    road.vehicle_positions_as_string(),
+                    road.cars.map(|car| car.speed()),
+                    road.bikes.map(|bike| bike.rectangle_occupation().right),
+                )
+            })
+            .collect();
+    }
+
+    #[test]
+    fn reseed_sets_seed() {
+        let mut road = random_mixed_road();
+
+        road.reseed(42);
+
+        assert_eq!(road.seed(), 42);
+    }
+
+    #[test]
+    fn differently_seeded_roads_diverge() {
+        let mut first = random_mixed_road();
+        let mut second = random_mixed_road();
+        first.reseed(1);
+        second.reseed(2);
+
+        assert_ne!(next_outputs(&mut first, 10), next_outputs(&mut second, 10));
+    }
+
+    #[test]
+    fn reseeded_roads_update_identically() {
+        let mut first = random_mixed_road();
+        let mut second = random_mixed_road();
+        // the roads start from random seeds and so have diverged rng streams,
+        // one even more so by having drawn some extra numbers
+        first.rng.gen::<u64>();
+
+        first.reseed(7);
+        second.reseed(7);
+
+        assert_eq!(next_outputs(&mut first, 10), next_outputs(&mut second, 10));
+    }
+
+    #[test]
+    fn identically_seeded_roads_are_physically_eq_until_they_diverge() {
+        let mut first = random_mixed_road();
+        let mut second = first.clone();
+        first.reseed(3);
+        second.reseed(3);
+
+        for _ in 0..5 {
+            first.update().unwrap();
+            second.update().unwrap();
+        }
+        assert!(first.physically_eq(&second));
+        // the rng isn't part of the road's physical state
+        second.reseed(4);
+        assert!(first.physically_eq(&second));
+
+        first.update().unwrap();
+        assert!(!first.physically_eq(&second));
+    }
+
+    #[test]
+    fn step_report_matches_road_after_update() {
+        let mut road = random_mixed_road();
+        road.reseed(2);
+        for _ in 0..10 {
+            let report = road.update().unwrap();
+            assert_eq!(report.mean_car_speed, road.mean_car_speed());
+            assert_eq!(report.lane_changes, road.boundary_crossings_last_step());
+        }
+
+        let cars = [(10, 3), (30, 0)].map(|(front, speed)| {
+            CarBuilder::default()
+                .with_front_at(front)
+                .with_speed(speed)
+                .with_speed_max(speed.max(1))
+                .unwrap()
+                .with_deceleration_prob(Probability::ZERO)
+                .build()
+                .unwrap()
+        });
+        let bikes = [BikeBuilder::default()
+            .with_front_at(12)
+            .with_right_at(9)
+            .with_forward_max_speed(0)
+            .unwrap()
+            .with_lateral_ignorance(Probability::ONE)
+            .build()
+            .unwrap()];
+        let mut road = Road::<1, 2, 60, 3, 7>::new(bikes, cars).unwrap();
+        let report = road.update().unwrap();
+        assert_eq!(
+            report,
+            StepReport {
+                lane_changes: 0,
+                // the first car passes the stopped bike
+                overtakes: 1,
+                collisions: 0,
+                mean_car_speed: Some(2.0),
+                // and both cars move, the second one starting off
+                vehicles_moved: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn state_hash_follows_physical_state() {
+        let mut first = random_mixed_road();
+        let mut second = first.clone();
+        second.reseed(5);
+        assert!(first.physically_eq(&second));
+        assert_eq!(first.state_hash(), second.state_hash());
+
+        first.update().unwrap();
+        assert!(!first.physically_eq(&second));
+        assert_ne!(first.state_hash(), second.state_hash());
+
+        let cars = [10, 30].map(|front| CarBuilder::default().with_front_at(front));
+        let road = Road::<0, 2, 60, 0, 7>::new([], cars.map(|car| car.build().unwrap())).unwrap();
+        let swapped =
+            Road::<0, 2, 60, 0, 7>::new([], [cars[1], cars[0]].map(|car| car.build().unwrap()))
+                .unwrap();
+        let mut moved = road.clone();
+        moved
+            .modify_car(0, |car| *car = car.with_front_at(11))
+            .unwrap();
+        assert_eq!(road.state_hash(), swapped.state_hash());
+        assert_ne!(road.state_hash(), moved.state_hash());
+    }
+
+    #[test]
+    fn reseeding_mid_run_restarts_the_update_stream() {
+        let mut first = random_mixed_road();
+        let mut second = random_mixed_road();
+        first.reseed(3);
+        second.reseed(3);
+        next_outputs(&mut first, 5);
+        next_outputs(&mut second, 5);
+
+        first.reseed(11);
+        second.reseed(11);
+
+        assert_eq!(next_outputs(&mut first, 10), next_outputs(&mut second, 10));
+    }
+
+    #[test]
+    fn lane_drop_removes_motor_lane_cells() {
+        let mut road = Road::<0, 0, 60, 7, 7>::new([], []).unwrap();
+        road.add_lane_drop(LaneDrop {
+            long_range: 40..=45,
+            motor_lane_width: 3,
+        })
+        .unwrap();
+
+        assert_eq!(road.motor_lane_width_at(39), 7);
+        assert_eq!(road.motor_lane_width_at(40), 3);
+        assert!(road.road_contains_coord(&Coord { lat: 2, long: 40 }));
+        assert!(!road.road_contains_coord(&Coord { lat: 3, long: 40 }));
+        assert!(!road.road_contains_coord(&Coord { lat: 6, long: 45 }));
+        assert!(road.road_contains_coord(&Coord { lat: 7, long: 45 }));
+        assert!(road.road_contains_coord(&Coord { lat: 6, long: 46 }));
+        assert!(road
+            .add_lane_drop(LaneDrop {
+                long_range: 0..=5,
+                motor_lane_width: 8,
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn lane_drop_over_vehicle_is_rejected() {
+        let cars = [CarBuilder::default()
+            .with_front_at(2)
+            .with_left_at(3)
+            .unwrap()
+            .build()
+            .unwrap()];
+        let mut road = Road::<0, 1, 60, 7, 14>::new([], cars).unwrap();
+        let drop_to = |motor_lane_width, long_range| LaneDrop {
+            long_range,
+            motor_lane_width,
+        };
+
+        // wrapping round onto the car's back
+        assert!(road.add_lane_drop(drop_to(6, 55..=58)).is_err());
+        assert!(road.add_lane_drop(drop_to(3, 2..=3)).is_err());
+        assert!(road.lane_drops().is_empty());
+        // the car's lats are left in the narrowed lane
+        road.add_lane_drop(drop_to(8, 0..=5)).unwrap();
+        road.add_lane_drop(drop_to(3, 3..=8)).unwrap();
+    }
+
+    #[test]
+    fn front_gap_stops_at_lane_drop() {
+        let bikes = [BikeBuilder::default().with_front_right_at(Coord { lat: 5, long: 30 })]
+            .map(|builder| builder.build().unwrap());
+        let mut road = Road::<1, 0, 60, 7, 7>::new(bikes, []).unwrap();
+        let occupation = road.get_bike(0).rectangle_occupation();
+        road.add_lane_drop(LaneDrop {
+            long_range: 40..=45,
+            motor_lane_width: 5,
+        })
+        .unwrap();
+
+        assert_eq!(road.front_gap(&occupation), Some(9));
+    }
+
+    #[test]
+    fn cars_queue_upstream_of_impassable_lane_drop() {
+        let cars = [0, 10, 20].map(|front| {
+            CarBuilder::default()
+                .with_front_at(front)
+                .with_deceleration_prob(Probability::ZERO)
+                .build()
+                .unwrap()
+        });
+        let mut road = Road::<0, 3, 60, 0, 14>::new([], cars).unwrap();
+        // narrower than a stationary car
+        road.add_lane_drop(LaneDrop {
+            long_range: 40..=45,
+            motor_lane_width: 4,
+        })
+        .unwrap();
+
+        for _ in 0..100 {
+            road.update().unwrap();
+            assert!(road.cars.iter().all(|car| road.road_contains_occupier(car)));
+        }
+
+        let mut fronts = road.cars.map(|car| car.front());
+        fronts.sort();
+        assert_eq!(fronts, [29, 34, 39]);
+        assert_eq!(road.cars.map(|car| car.speed()), [0, 0, 0]);
+    }
+
+    #[test]
+    fn cars_slow_to_fit_through_lane_drop() {
+        let cars = [CarBuilder::default()
+            .with_front_at(0)
+            .with_deceleration_prob(Probability::ZERO)
+            .build()
+            .unwrap()];
+        let mut road = Road::<0, 1, 100, 0, 14>::new([], cars).unwrap();
+        // only wide enough for a car going at most 6 cells per step
+        road.add_lane_drop(LaneDrop {
+            long_range: 50..=69,
+            motor_lane_width: 6,
+        })
+        .unwrap();
+
+        let mut passed_through = false;
+        for _ in 0..100 {
+            road.update().unwrap();
+            let car = road.get_car(0);
+            assert!(road.road_contains_occupier(car));
+            if road.motor_lane_width_at(car.front()) == 6 {
+                passed_through = true;
+                assert!(car.speed() <= 6);
+            }
+        }
+        assert!(passed_through);
+    }
+
+    #[test]
+    fn car_speed_summary_matches_speeds() {
+        let cars = [(0, 0), (20, 2), (40, 4), (60, 10)].map(|(front, speed)| {
+            CarBuilder::default()
+                .with_front_at(front)
+                .with_speed(speed)
+                .build()
+                .unwrap()
+        });
+        let road = Road::<0, 4, 100, 0, 14>::new([], cars).unwrap();
+
+        let summary = road.car_speed_summary().unwrap();
+
+        assert_eq!(summary.min, 0);
+        assert_eq!(summary.max, 10);
+        assert_eq!(summary.median, 3.0);
+        assert_eq!(summary.mean, 4.0);
+        assert_eq!(Some(summary.mean), road.mean_car_speed());
+    }
+
+    #[test]
+    fn car_speed_summary_empty_without_cars() {
+        let road = Road::<0, 0, 100, 0, 14>::new([], []).unwrap();
+
+        assert_eq!(road.car_speed_summary(), None);
+    }
+
+    #[test]
+    fn road_metrics_convert_to_real_units() {
+        let units = Units {
+            cell_length_m: 7.5,
+            step_duration_s: 1.0,
+        };
+        let cars = [(0, 0), (20, 2), (40, 4), (60, 14)].map(|(front, speed)| {
+            CarBuilder::default()
+                .with_front_at(front)
+                .with_speed(speed)
+                .build()
+                .unwrap()
+        });
+        let road = Road::<0, 4, 100, 0, 14>::new([], cars).unwrap();
+
+        assert_eq!(road.mean_car_speed_kmh(&units), Some(135.0));
+        assert_eq!(road.mean_bike_speed_kmh(&units), None);
+        assert_eq!(road.car_veh_per_km(&units), 4.0 * 1000.0 / 750.0);
+    }
+
+    #[test]
+    fn vehicle_accessors() {
+        assert_eq!(Vehicle::Bike(3).id(), 3);
+        assert_eq!(Vehicle::Car(5).id(), 5);
+        assert!(Vehicle::Bike(0).is_bike() && !Vehicle::Bike(0).is_car());
+        assert!(Vehicle::Car(0).is_car() && !Vehicle::Car(0).is_bike());
+    }
+
+    #[test]
+    fn space_mean_speed_is_harmonic() {
+        let cars = [(0, 2), (30, 6), (60, 0)].map(|(front, speed)| {
+            CarBuilder::default()
+                .with_front_at(front)
+                .with_speed(speed)
+                .build()
+                .unwrap()
+        });
+        let road = Road::<0, 3, 90, 0, 14>::new([], cars).unwrap();
+
+        assert_eq!(road.space_mean_car_speed(), Some(3.0));
+        let moving_road = Road::<0, 2, 90, 0, 14>::new([], [cars[0], cars[1]]).unwrap();
+        assert_eq!(moving_road.mean_car_speed(), Some(4.0));
+        assert_eq!(moving_road.space_mean_car_speed(), Some(3.0));
+    }
+
+    #[test]
+    fn space_mean_speed_of_stopped_cars_is_none() {
+        let car = CarBuilder::default().with_speed(0).build().unwrap();
+        let road = Road::<0, 1, 90, 0, 14>::new([], [car]).unwrap();
+
+        assert_eq!(road.space_mean_car_speed(), None);
+    }
+
+    fn car_approaching_closed_road() -> Road<0, 1, 60, 0, 14> {
+        let car = CarBuilder::default()
+            .with_front_at(0)
+            .with_speed(5)
+            .with_deceleration_prob(Probability::ZERO)
+            .build()
+            .unwrap();
+        let mut road = Road::new([], [car]).unwrap();
+        road.add_lane_drop(LaneDrop {
+            long_range: 30..=35,
+            motor_lane_width: 0,
+        })
+        .unwrap();
+        return road;
+    }
+
+    #[test]
+    fn update_until_stops_once_jammed() {
+        let mut road = car_approaching_closed_road();
+
+        let steps = road.update_until(100, |road| road.is_jammed()).unwrap();
+
+        assert!(0 < steps && steps < 100);
+        assert!(road.is_jammed());
+        let mut one_step_short = car_approaching_closed_road();
+        for _ in 1..steps {
+            one_step_short.update().unwrap();
+        }
+        assert!(!one_step_short.is_jammed());
+    }
+
+    #[test]
+    fn update_until_runs_max_steps_without_jam() {
+        let car = CarBuilder::default()
+            .with_deceleration_prob(Probability::ZERO)
+            .with_speed(1)
+            .build()
+            .unwrap();
+        let mut road = Road::<0, 1, 60, 0, 14>::new([], [car]).unwrap();
+
+        assert_eq!(road.update_until(20, |road| road.is_jammed()).unwrap(), 20);
+        assert_eq!(road.update_until(20, |_| true).unwrap(), 0);
+    }
+
+    #[test]
+    fn speed_stats_cover_bikes_and_cars() {
+        let bikes = [0, 10].map(|front| {
+            BikeBuilder::default()
+                .with_front_right_at(Coord {
+                    lat: 17,
+                    long: front,
+                })
+                .build()
+                .unwrap()
+        });
+        let cars = [(0, 2), (30, 6)].map(|(front, speed)| {
+            CarBuilder::default()
+                .with_front_at(front)
+                .with_speed(speed)
+                .build()
+                .unwrap()
+        });
+        let road = Road::<2, 2, 60, 4, 14>::new(bikes, cars).unwrap();
+
+        let stats = road.speed_stats().unwrap();
+        assert_eq!(stats.mean, 2.0);
+        assert_eq!(stats.p50, 1.0);
+        assert_eq!(
+            Road::<0, 0, 60, 4, 14>::new([], []).unwrap().speed_stats(),
+            None
+        );
+    }
+
+    #[test]
+    fn waiting_times_count_stopped_updates() {
+        let cars = [(29, 0), (100, 0)].map(|(front, speed)| {
+            CarBuilder::default()
+                .with_front_at(front)
+                .with_speed(speed)
+                .with_deceleration_prob(Probability::ZERO)
+                .build()
+                .unwrap()
+        });
+        let mut road = Road::<0, 2, 200, 0, 14>::new([], cars).unwrap();
+        road.add_lane_drop(LaneDrop {
+            long_range: 30..=35,
+            motor_lane_width: 0,
+        })
+        .unwrap();
 
-        assert_eq!(bike_front_gap_1, bike_front_gap_2);
-        assert_eq!(bike_front_gap_1, 18)
+        for _ in 0..10 {
+            road.update().unwrap();
+        }
+
+        assert_eq!(road.car_waiting_times(), &[10, 0]);
     }
 
-    proptest! {
-        #[test]
-        fn single_bike_any_pos_update_works(
-            right in 1..6isize,
-            speed in 0..=6isize,
-            front in 0..20isize,
-        ) {
-            let bikes = [BikeBuilder::default().with_front_at(front).with_right_at(right).with_forward_speed(speed).unwrap()]
-                .map(|builder| builder.try_into().unwrap());
-            let mut road = Road::<1, 0, 20, 3, 3>::new(bikes, []).unwrap();
+    fn packed_car_road() -> Road<0, 8, 60, 0, 14> {
+        let cars = [35, 30, 25, 20, 15, 10, 5, 0].map(|front| {
+            CarBuilder::default()
+                .with_front_at(front)
+                .with_deceleration_prob(Probability::ZERO)
+                .build()
+                .unwrap()
+        });
+        return Road::new([], cars).unwrap();
+    }
 
-            println!("{}", road.cells);
+    #[test]
+    fn stop_go_waves_counted_for_dissolving_jam() {
+        let mut road = packed_car_road();
+        let mut same_road = packed_car_road();
+
+        for _ in 0..100 {
             road.update().unwrap();
-            println!("{}", road.cells);
+            same_road.update().unwrap();
+        }
 
-            let new_position = road.get_bike(0).rectangle_occupation();
+        assert!(0 < road.count_stop_go_waves());
+        assert_eq!(road.count_stop_go_waves(), same_road.count_stop_go_waves());
+    }
 
-            assert!(road.road_contains_occupier(&new_position));
+    #[test]
+    fn bike_moving_into_bike_lane_crosses_boundary_once() {
+        let bikes = [BikeBuilder::deterministic_default()
+            .with_front_right_at(Coord { lat: 3, long: 10 })
+            .build()
+            .unwrap()];
+        let mut road = Road::<1, 0, 30, 4, 4>::new(bikes, []).unwrap();
+        assert_eq!(road.boundary_crossings_last_step(), 0);
+
+        road.bikes_lateral_update().unwrap();
+        assert!(!road.motor_lane_contains_occupier(road.get_bike(0)));
+        assert_eq!(road.boundary_crossings_last_step(), 1);
+
+        road.bikes_lateral_update().unwrap();
+        assert!(!road.motor_lane_contains_occupier(road.get_bike(0)));
+        assert_eq!(road.boundary_crossings_last_step(), 0);
+    }
+
+    #[test]
+    fn no_stop_go_waves_in_free_flow() {
+        let car = CarBuilder::default()
+            .with_speed(5)
+            .with_deceleration_prob(Probability::ZERO)
+            .build()
+            .unwrap();
+        let mut road = Road::<0, 1, 60, 0, 14>::new([], [car]).unwrap();
+
+        for _ in 0..50 {
+            road.update().unwrap();
+        }
+
+        assert_eq!(road.count_stop_go_waves(), 0);
+    }
+
+    /// Gives the scripted decisions in order, then never decides for
+    /// anything, and always picks the first option.
+    struct ScriptedSource {
+        decisions: Vec<bool>,
+    }
+
+    impl RandSource for ScriptedSource {
+        fn decide(&mut self, _distribution: &Bernoulli) -> bool {
+            return match self.decisions.is_empty() {
+                true => false,
+                false => self.decisions.remove(0),
+            };
+        }
+
+        fn pick(&mut self, len: usize) -> Option<usize> {
+            return (0 < len).then_some(0);
         }
     }
 
     #[test]
-    fn multiple_bikes_multiple_updates_work() -> anyhow::Result<()> {
-        let bikes: [Bike; 5] = [0, 3, 6, 9, 12]
-            .map(|front| BikeBuilder::default().with_front_at(front))
-            .map(|builder| builder.try_into().unwrap());
-        for (bike_id, bike) in bikes.iter().enumerate() {
-            println!("bike {}", bike_id);
-            let occupied_cells: Vec<Coord> = bike.occupied_cells().collect();
-            println!("occupied cells: {:?}", occupied_cells)
+    fn scripted_source_decides_deceleration() {
+        let car = CarBuilder::default()
+            .with_deceleration_prob(Probability::new(0.5).unwrap())
+            .build()
+            .unwrap();
+        let mut road = Road::<0, 1, 100, 0, 14>::new([], [car]).unwrap();
+        // a single car draws only its deceleration each update
+        let mut source = ScriptedSource {
+            decisions: vec![false, true, false],
+        };
+
+        let speeds: Vec<isize> = (0..3)
+            .map(|_| {
+                road.update_with(&mut source).unwrap();
+                return road.get_car(0).speed();
+            })
+            .collect();
+
+        assert_eq!(speeds, vec![2, 3, 5]);
+    }
+
+    #[test]
+    fn timed_incident_queues_then_clears() {
+        let cars = [60, 50, 40, 30].map(|front| {
+            CarBuilder::default()
+                .with_front_at(front)
+                .with_deceleration_prob(Probability::ZERO)
+                .build()
+                .unwrap()
+        });
+        let mut road = Road::<0, 4, 100, 0, 14>::new([], cars).unwrap();
+        road.add_timed_incident(80, 1, 40).unwrap();
+        assert!(road.add_timed_incident(58, 0, 5).is_err());
+
+        for _ in 0..39 {
+            road.update().unwrap();
         }
-        let mut road = Road::<5, 0, 20, 3, 3>::new(bikes, []).unwrap();
+        assert_eq!(road.mean_car_speed(), Some(0.0));
+        assert_eq!(road.get_car(0).front(), 79);
+        assert_eq!(
+            road.incidents(),
+            &[Incident {
+                coord: Coord { lat: 1, long: 80 },
+                remaining_steps: 1
+            }]
+        );
 
-        for iter_num in 0u16..1000 {
-            println!("iteration #{:?}", iter_num);
-            println!("road cells:");
-            println!("{}", road.cells);
+        road.update().unwrap();
+        assert!(road.incidents().is_empty());
+        for _ in 0..30 {
             road.update().unwrap();
         }
+        assert!(road.mean_car_speed().unwrap() > 5.0);
+    }
 
-        println!("{:?}", road);
-        return Ok(());
+    #[test]
+    fn deterministic_roads_update_identically() {
+        let mut first = Road::<5, 3, 100, 7, 7>::deterministic().unwrap();
+        let mut second = Road::<5, 3, 100, 7, 7>::deterministic().unwrap();
+        assert_ne!(first.seed(), second.seed());
+
+        assert_eq!(next_outputs(&mut first, 40), next_outputs(&mut second, 40));
+        assert!(first.physically_eq(&second));
     }
 
     #[test]
-    fn single_bike_lateral_update_works() {
-        let bikes =
-            [BikeBuilder::deterministic_default()].map(|builder| builder.try_into().unwrap());
-        let mut road = Road::<1, 0, 20, 3, 3>::new(bikes, []).unwrap();
+    fn rescaled_road_spreads_vehicles_out() {
+        let road = random_mixed_road();
 
-        road.bikes_lateral_update();
+        let doubled: Road<10, 10, 200, 7, 7> = road.rescale().unwrap();
 
-        let new_position = road.get_bike(0).rectangle_occupation();
+        for (car, doubled_car) in zip(road.cars(), doubled.cars()) {
+            assert_eq!(doubled_car.front(), 2 * car.front().rem_euclid(100));
+            assert_eq!(doubled_car.speed(), car.speed());
+        }
+        for (bike, doubled_bike) in zip(road.bikes(), doubled.bikes()) {
+            assert_eq!(doubled_bike.front(), 2 * bike.front().rem_euclid(100));
+        }
+        assert_eq!(
+            doubled.iter_car_positions().count(),
+            road.iter_car_positions().count()
+        );
+        assert!(road.rescale::<20>().is_err());
+    }
 
-        assert!(road.road_contains_occupier(&new_position));
+    #[test]
+    fn concat_roads_offsets_second_road() {
+        let car = CarBuilder::default().with_front_at(10).build().unwrap();
+        let first = Road::<0, 1, 30, 0, 14>::new([], [car]).unwrap();
+        let second = Road::<0, 1, 20, 0, 14>::new([], [car]).unwrap();
+
+        let joined: Road<0, 2, 50, 0, 14> = concat_roads(&first, &second).unwrap();
+
+        assert_eq!(joined.get_car(0).front(), 10);
+        assert_eq!(joined.get_car(1).front(), 40);
+        assert_eq!(
+            joined.cells().get(&Coord { lat: 0, long: 36 }).unwrap(),
+            Some(&Vehicle::Car(1))
+        );
+        assert!(concat_roads::<0, 1, 30, 0, 1, 20, 0, 2, 49, 0, 14>(&first, &second).is_err());
     }
 
     #[test]
-    fn single_bike_forward_update_works() {
-        let bikes =
-            [BikeBuilder::deterministic_default()].map(|builder| builder.try_into().unwrap());
-        let mut road = Road::<1, 0, 20, 3, 3>::new(bikes, []).unwrap();
+    fn concat_roads_rejects_overlap_at_seam() {
+        let wrapping_car = CarBuilder::default().with_front_at(1).build().unwrap();
+        let last_car = CarBuilder::default().with_front_at(19).build().unwrap();
+        let first = Road::<0, 1, 30, 0, 14>::new([], [wrapping_car]).unwrap();
+        let second = Road::<0, 1, 20, 0, 14>::new([], [last_car]).unwrap();
 
-        road.bikes_forward_update().unwrap();
+        assert!(concat_roads::<0, 1, 30, 0, 1, 20, 0, 2, 50, 0, 14>(&first, &second).is_err());
+    }
 
-        let new_position = road.get_bike(0).rectangle_occupation();
+    #[test]
+    fn vehicle_states_json_has_lateral_positions() {
+        let bikes = [BikeBuilder::default()
+            .with_front_right_at(Coord { lat: 16, long: 30 })
+            .build()
+            .unwrap()];
+        let cars = [CarBuilder::default()
+            .with_front_at(10)
+            .with_speed(4)
+            .build()
+            .unwrap()];
+        let road = Road::<1, 1, 60, 4, 14>::new(bikes, cars).unwrap();
+
+        let states: serde_json::Value =
+            serde_json::from_str(&road.vehicle_states_as_string()).unwrap();
+
+        let car = &states["cars"][0];
+        assert_eq!(car["front"], 10);
+        assert_eq!(car["right"], 5);
+        assert_eq!(car["width"], 6);
+        assert_eq!(car["speed"], 4);
+        assert_eq!(states["bikes"][0]["right"], 16);
+        assert_eq!(states["bikes"][0]["front"], 30);
+    }
 
-        assert!(road.road_contains_occupier(&new_position));
+    #[test]
+    fn modified_car_brakes_on_next_update() {
+        let car = CarBuilder::default()
+            .with_speed(10)
+            .with_deceleration_prob(Probability::ZERO)
+            .build()
+            .unwrap();
+        let mut road = Road::<0, 1, 100, 0, 14>::new([], [car]).unwrap();
+
+        road.modify_car(0, |builder| *builder = builder.with_speed(0))
+            .unwrap();
+        assert_eq!(road.get_car(0).speed(), 0);
+        road.update().unwrap();
+
+        assert_eq!(road.get_car(0).speed(), 2);
+        assert_eq!(road.get_car(0).front(), 7);
     }
 
     #[test]
-    fn single_bike_forward_update_works_as_expected() -> anyhow::Result<()> {
-        let bikes = [
-            BikeBuilder::default()
-                .with_front_at(2) // 2
-                .with_forward_speed(3)? // + 3 = 5
-                .with_forward_acceleration(1)? // + 1 = 6
-                .with_forward_max_speed(10)? // min(6, 10) = 6
-                .with_deceleration_prob(0.0)?, // - 0 = 6
-        ]
-        .map(|builder| builder.try_into().unwrap());
-        let mut road = Road::<1, 0, 20, 3, 3>::new(bikes, []).unwrap();
+    fn modify_car_rejects_collisions() {
+        let cars =
+            [10, 20].map(|front| CarBuilder::default().with_front_at(front).build().unwrap());
+        let mut road = Road::<0, 2, 100, 0, 14>::new([], cars).unwrap();
+
+        assert!(road
+            .modify_car(0, |builder| *builder = builder.with_front_at(18))
+            .is_err());
+        assert_eq!(road.get_car(0).front(), 10);
+        assert!(road.modify_car(2, |_| {}).is_err());
+        road.modify_car(0, |builder| *builder = builder.with_front_at(14))
+            .unwrap();
+        assert_eq!(road.get_car(0).front(), 14);
+        road.assert_safe_following();
+    }
 
-        road.bikes_forward_update().unwrap();
+    #[test]
+    fn cells_in_range_finds_cells_in_box() {
+        let cars =
+            [10, 30].map(|front| CarBuilder::default().with_front_at(front).build().unwrap());
+        let road = Road::<0, 2, 40, 0, 14>::new([], cars).unwrap();
 
-        let RectangleOccupier {
-            front: new_front, ..
-        } = road.get_bike(0).rectangle_occupation();
+        let found: Vec<(Coord, &Vehicle)> = road.cells_in_range(8..=12, 3..=4).collect();
 
-        assert_eq!(new_front, 6);
-        return Ok(());
+        assert_eq!(
+            found,
+            [(3, 8), (4, 8), (3, 9), (4, 9), (3, 10), (4, 10)]
+                .map(|(lat, long)| (Coord { lat, long }, &Vehicle::Car(0)))
+        );
+        assert_eq!(road.cells_in_range(36..=40, -2..=0).count(), 0);
+        assert_eq!(road.cells_in_range(-14..=-12, -2..=0).count(), 3);
+    }
+
+    fn steps_until_jam_tail_moves(reaction_lag: bool, reaction_delay: usize) -> usize {
+        let cars = [25, 20, 15, 10, 5].map(|front| {
+            CarBuilder::default()
+                .with_front_at(front)
+                .with_deceleration_prob(Probability::ZERO)
+                .with_reaction_delay(reaction_delay)
+                .build()
+                .unwrap()
+        });
+        let mut road = Road::<0, 5, 100, 0, 14>::new([], cars).unwrap();
+        road.set_reaction_lag(reaction_lag);
+
+        return road
+            .update_until(100, |road| road.get_car(4).speed() > 0)
+            .unwrap();
     }
 
     #[test]
-    fn single_bike_next_forward_works_as_expected() -> anyhow::Result<()> {
-        let bikes = [
-            BikeBuilder::default()
-                .with_front_at(2) // 2
-                .with_forward_speed(3)? // + 3 = 5
-                .with_forward_acceleration(1)? // + 1 = 6
-                .with_forward_max_speed(10)? // min(6, 10) = 6
-                .with_deceleration_prob(0.0)?, // - 0 = 6
-        ]
-        .map(|builder| builder.try_into().unwrap());
-        let road = Road::<1, 0, 20, 3, 3>::new(bikes, []).unwrap();
+    fn reaction_lag_slows_jam_dissolving() {
+        // the start of the jam travels back one car per update without a lag
+        assert_eq!(steps_until_jam_tail_moves(false, 0), 5);
+        assert_eq!(steps_until_jam_tail_moves(true, 0), 9);
+    }
 
-        let [next_bike] = road.next_bikes_forward();
+    fn smallest_car_gap_over_run(min_gap: usize) -> usize {
+        let cars: [Car; 7] = std::array::from_fn(|index| {
+            CarBuilder::default()
+                .with_front_at(index as isize * 8)
+                .with_speed_max(5)
+                .unwrap()
+                .with_min_gap(min_gap)
+                .build()
+                .unwrap()
+        });
+        let mut road = Road::<0, 7, 56, 0, 14>::new([], cars).unwrap();
+        road.reseed(11);
+
+        return (0..50)
+            .flat_map(|_| {
+                road.update().unwrap();
+                return (0..7)
+                    .map(|car_id| road.car_front_gap(car_id).unwrap())
+                    .collect::<Vec<_>>();
+            })
+            .min()
+            .unwrap();
+    }
 
-        let RectangleOccupier {
-            front: new_front, ..
-        } = next_bike.rectangle_occupation();
+    #[test]
+    fn cars_keep_min_gap_to_leader() {
+        assert!(smallest_car_gap_over_run(0) < 2);
+        assert!(2 <= smallest_car_gap_over_run(2));
+    }
 
-        assert_eq!(new_front, 6);
-        return Ok(());
+    #[test]
+    fn car_reaction_delay_slows_jam_dissolving() {
+        assert_eq!(steps_until_jam_tail_moves(false, 1), 9);
+        assert_eq!(steps_until_jam_tail_moves(true, 1), 9);
+        assert_eq!(steps_until_jam_tail_moves(false, 2), 11);
+        // the longer delay wins
+        assert_eq!(steps_until_jam_tail_moves(true, 2), 11);
+    }
+
+    fn speeds_after_gap_closes(reaction_delay: usize) -> Vec<isize> {
+        // a crawling leader and a follower at full speed
+        let cars = [(60, 1), (10, 5)].map(|(front, speed_max)| {
+            CarBuilder::default()
+                .with_front_at(front)
+                .with_speed(speed_max)
+                .with_speed_max(speed_max)
+                .unwrap()
+                .with_deceleration_prob(Probability::ZERO)
+                .with_reaction_delay(reaction_delay)
+                .build()
+                .unwrap()
+        });
+        let mut road = Road::<0, 2, 100, 0, 14>::new([], cars).unwrap();
+        road.update().unwrap();
+        road.modify_car(0, |car| *car = car.with_front_at(22))
+            .unwrap();
+
+        return (0..3)
+            .map(|_| {
+                road.update().unwrap();
+                return road.get_car(1).speed();
+            })
+            .collect();
     }
 
     #[test]
-    fn single_bike_forward_update_speeds_up_as_expected() -> anyhow::Result<()> {
-        let speed = 3;
-        let acceleration = 1;
-        let expected_speed = speed + acceleration;
-        let bikes = [
-            BikeBuilder::default()
-                .with_forward_speed(speed)?
-                .with_forward_acceleration(acceleration)?
-                .with_forward_max_speed(expected_speed + 25)? // too big to matter
-                .with_deceleration_prob(0.0)?, // won't be messed up
-        ]
-        .map(|builder| builder.try_into().unwrap());
-        let road = Road::<1, 0, 20, 3, 3>::new(bikes, []).unwrap();
+    fn delayed_car_still_brakes_for_gap_that_just_closed() {
+        // the car never drives into the current gap however late it reacts
+        assert_eq!(speeds_after_gap_closes(0), vec![2, 1, 1]);
+        assert_eq!(speeds_after_gap_closes(1), speeds_after_gap_closes(0));
+        assert_eq!(speeds_after_gap_closes(3), speeds_after_gap_closes(0));
+    }
+
+    #[test]
+    fn modified_car_forgets_gaps_it_saw_before() {
+        // a delayed car stuck right behind a crawling one is moved to open road
+        let cars = [(16, 1, 0), (11, 5, 2)].map(|(front, speed_max, reaction_delay)| {
+            CarBuilder::default()
+                .with_front_at(front)
+                .with_speed_max(speed_max)
+                .unwrap()
+                .with_deceleration_prob(Probability::ZERO)
+                .with_reaction_delay(reaction_delay)
+                .build()
+                .unwrap()
+        });
+        let mut road = Road::<0, 2, 100, 0, 14>::new([], cars).unwrap();
+        road.update().unwrap();
+        road.update().unwrap();
+        road.modify_car(1, |car| *car = car.with_front_at(60))
+            .unwrap();
+
+        let speeds: Vec<isize> = (0..3)
+            .map(|_| {
+                road.update().unwrap();
+                return road.get_car(1).speed();
+            })
+            .collect();
+
+        // the gaps behind the crawling car would have braked it to 0
+        assert_eq!(speeds, vec![3, 5, 5]);
+    }
+
+    fn follower_speeds_approaching_slow_zone(brake_light_range: Option<usize>) -> Vec<isize> {
+        let cars = [(40, 8), (12, 8)].map(|(front, speed)| {
+            CarBuilder::default()
+                .with_front_at(front)
+                .with_speed(speed)
+                .with_deceleration_prob(Probability::ZERO)
+                .build()
+                .unwrap()
+        });
+        let mut road = Road::<0, 2, 100, 0, 14>::new([], cars).unwrap();
+        road.add_speed_zone(SpeedZone {
+            long_range: 50..=99,
+            max_speed: 2,
+        })
+        .unwrap();
+        road.set_brake_light_range(brake_light_range);
+
+        return (0..10)
+            .map(|_| {
+                road.update().unwrap();
+                road.assert_safe_following();
+                return road.get_car(1).speed();
+            })
+            .collect();
+    }
+
+    #[test]
+    fn brake_lights_make_followers_slow_earlier() {
+        let first_slowing = |speeds: Vec<isize>| {
+            return speeds
+                .windows(2)
+                .position(|pair| pair[1] < pair[0])
+                .unwrap();
+        };
+
+        let baseline = follower_speeds_approaching_slow_zone(None);
+        let with_brake_lights = follower_speeds_approaching_slow_zone(Some(20));
+
+        assert!(first_slowing(with_brake_lights) < first_slowing(baseline));
+    }
+
+    #[test]
+    fn stepped_matches_update_of_clone() {
+        let mut road = random_mixed_road();
+        road.reseed(7);
+        let before = road.vehicle_states_as_string();
+
+        let stepped = road.stepped().unwrap();
+        let mut updated = road.clone();
+        updated.update().unwrap();
+
+        assert_eq!(
+            stepped.vehicle_states_as_string(),
+            updated.vehicle_states_as_string()
+        );
+        assert_ne!(stepped.vehicle_states_as_string(), before);
+        assert_eq!(road.vehicle_states_as_string(), before);
+    }
+
+    fn speeds_with_acceleration_prob(acceleration_prob: Probability) -> Vec<isize> {
+        let car = CarBuilder::default()
+            .with_speed(3)
+            .with_deceleration_prob(Probability::ZERO)
+            .with_acceleration_prob(acceleration_prob)
+            .build()
+            .unwrap();
+        let mut road = Road::<0, 1, 100, 0, 14>::new([], [car]).unwrap();
+
+        return (0..4)
+            .map(|_| {
+                road.update().unwrap();
+                return road.get_car(0).speed();
+            })
+            .collect();
+    }
+
+    #[test]
+    fn acceleration_prob_skips_acceleration() {
+        assert_eq!(
+            speeds_with_acceleration_prob(Probability::ZERO),
+            vec![5, 7, 8, 9]
+        );
+        assert_eq!(
+            speeds_with_acceleration_prob(Probability::ONE),
+            vec![3, 3, 3, 3]
+        );
+    }
+
+    #[test]
+    fn bike_blocked_from_bike_lane_is_stuck() {
+        let builders = [(3, 10), (5, 10)].map(|(right, front)| {
+            BikeBuilder::deterministic_default().with_front_right_at(Coord {
+                lat: right,
+                long: front,
+            })
+        });
+        let bikes = builders.map(|builder| builder.build().unwrap());
+        let road = Road::<2, 0, 20, 2, 4>::new(bikes, []).unwrap();
 
-        let [Bike { forward_speed, .. }] = road.next_bikes_forward();
+        assert_eq!(road.bikes_stuck_in_motor_lane(), 1);
+        assert!(road.get_bike(0).is_stuck_in_motor_lane(&road, 0));
 
-        assert_eq!(forward_speed, expected_speed);
-        return Ok(());
+        let unblocked = Road::<1, 0, 20, 2, 4>::new([bikes[0]], []).unwrap();
+        assert_eq!(unblocked.bikes_stuck_in_motor_lane(), 0);
     }
 
     #[test]
-    fn single_bike_lateral_and_forward_update_works() {
-        let bikes =
-            [BikeBuilder::deterministic_default()].map(|builder| builder.try_into().unwrap());
-        let mut road = Road::<1, 0, 20, 3, 3>::new(bikes, []).unwrap();
-
-        road.bikes_lateral_update();
-        road.bikes_forward_update().unwrap();
+    fn allocation_hints_do_not_change_the_road() {
+        let bikes = [0, 6, 12].map(|front| {
+            BikeBuilder::deterministic_default()
+                .with_front_right_at(Coord {
+                    lat: 15,
+                    long: front,
+                })
+                .build()
+                .unwrap()
+        });
+        let cars = [5, 15].map(|front| {
+            CarBuilder::default()
+                .with_front_at(front)
+                .with_speed(4)
+                .build()
+                .unwrap()
+        });
 
-        let new_position = road.get_bike(0).rectangle_occupation();
+        let default = Road::<3, 2, 20, 2, 14>::new(bikes, cars).unwrap();
+        let tiny = Road::<3, 2, 20, 2, 14>::new_with_allocation(bikes, cars, 0, 0).unwrap();
+        let large = Road::<3, 2, 20, 2, 14>::new_with_allocation(bikes, cars, 1000, 1000).unwrap();
 
-        assert!(road.road_contains_occupier(&new_position));
+        assert!(default.cells.cells.capacity() < large.cells.cells.capacity());
+        assert_eq!(tiny.cells.cells, default.cells.cells);
+        assert_eq!(large.cells.cells, default.cells.cells);
     }
 
     #[test]
-    fn rectangle_front_cells_correct() {
-        let occupation = RectangleOccupier {
-            front: 2,
-            right: 5,
-            width: 3,
-            length: 3,
-        };
+    fn shrinking_frees_capacity_but_keeps_cells() {
+        let cars = [5, 15].map(|front| CarBuilder::default().with_front_at(front).build().unwrap());
+        let mut road = Road::<0, 2, 20, 2, 14>::new_with_allocation([], cars, 1000, 0).unwrap();
+        let cells_before = road.cells.cells.clone();
+        let capacity_before = road.cells.cells.capacity();
 
-        let front: Vec<Coord> = occupation.front_cells().collect();
+        road.shrink_to_fit();
 
+        assert!(road.cells.cells.capacity() < capacity_before);
+        assert_eq!(road.cells.cells, cells_before);
         assert_eq!(
-            front,
-            vec![
-                Coord { lat: 3, long: 2 },
-                Coord { lat: 4, long: 2 },
-                Coord { lat: 5, long: 2 }
-            ]
-        )
+            road.vehicle_at(&Coord { lat: 0, long: 15 }).unwrap(),
+            Some(&Vehicle::Car(1))
+        );
+        road.update().unwrap();
+        road.assert_safe_following();
     }
 
     #[test]
-    fn rectangle_occupies_cells_correct() {
-        let width = 2;
-        let length = 2;
-        let area = (width * length) as usize;
-        let occupation = RectangleOccupier {
-            front: 2,
-            right: 5,
-            width,
-            length,
-        };
+    fn followers_respect_long_vehicle_footprint() {
+        let truck = CarBuilder::default()
+            .with_front_at(30)
+            .with_length(12)
+            .unwrap()
+            .with_acceleration_prob(Probability::ONE)
+            .build()
+            .unwrap();
+        let follower = CarBuilder::default()
+            .with_front_at(0)
+            .with_speed(2)
+            .with_deceleration_prob(Probability::ZERO)
+            .build()
+            .unwrap();
+        let mut road = Road::<0, 2, 60, 2, 14>::new([], [truck, follower]).unwrap();
 
-        let cells: HashSet<Coord> = occupation.occupied_cells().collect();
+        let truck_cells: HashSet<Coord> = truck.occupied_cells().collect();
+        assert_eq!(truck_cells.len(), truck.width() * 12);
+        assert!(truck_cells
+            .iter()
+            .all(|coord| (19..=30).contains(&coord.long)));
+        assert_eq!(road.car_front_gap(1), Some(18));
 
-        println!("occupier: {:?}", occupation);
+        for _ in 0..30 {
+            road.cars_update().unwrap();
+            let follower = road.get_car(1);
+            assert!(follower
+                .occupied_cells()
+                .all(|coord| !truck_cells.contains(&coord)));
+        }
+        assert_eq!(road.get_car(1).front(), 18);
+        assert_eq!(road.get_car(0).front(), 30);
+    }
 
-        assert_eq!(occupation.occupied_cells().count(), area);
-        assert_eq!(cells.len(), area);
+    #[test]
+    fn densities_are_vehicle_length_over_road_length() {
+        let bikes = [0, 10, 20].map(|front| {
+            BikeBuilder::deterministic_default()
+                .with_front_right_at(Coord {
+                    lat: 15,
+                    long: front,
+                })
+                .build()
+                .unwrap()
+        });
+        let cars = [
+            CarBuilder::default().with_front_at(10),
+            CarBuilder::default()
+                .with_front_at(30)
+                .with_length(12)
+                .unwrap()
+                .with_speed(6),
+        ]
+        .map(|builder| builder.build().unwrap());
+        let road = Road::<3, 2, 40, 2, 14>::new(bikes, cars).unwrap();
+
+        let bike_length = bikes[0].length();
+        assert_eq!(road.car_density(), (5 + 12) as f64 / 40.0);
+        assert_eq!(road.bike_density(), (3 * bike_length) as f64 / 40.0);
         assert_eq!(
-            cells,
-            HashSet::from([
-                Coord { lat: 4, long: 1 },
-                Coord { lat: 5, long: 1 },
-                Coord { lat: 4, long: 2 },
-                Coord { lat: 5, long: 2 }
-            ])
+            Road::<0, 0, 40, 2, 14>::new([], []).unwrap().car_density(),
+            0.0
         );
     }
 
     #[test]
-    fn front_gap_works() {
-        let bikes = [
-            BikeBuilder::default().with_front_right_at(Coord { lat: 3, long: 3 }),
-            BikeBuilder::default().with_front_right_at(Coord { lat: 3, long: 10 }),
-        ]
-        .map(|builder| builder.try_into().unwrap());
-        let road = Road::<2, 0, 20, 3, 3>::new(bikes, []).unwrap();
+    fn saved_road_loads_physically_eq() {
+        let mut road = random_mixed_road();
+        road.reseed(3);
+        road.add_pedestrian(Pedestrian::new(5, 0, WalkDirection::Right))
+            .unwrap();
+        for _ in 0..5 {
+            road.update().unwrap();
+        }
+        let file = tempfile::NamedTempFile::new().unwrap();
 
-        let trailing_bike = road.get_bike(0);
+        road.save_json(file.path()).unwrap();
+        let mut loaded = Road::<10, 10, 100, 7, 7>::load_json(file.path()).unwrap();
 
-        let front_gap = road
-            .front_gap(&trailing_bike.rectangle_occupation())
+        assert!(loaded.physically_eq(&road));
+        assert_eq!(loaded.pedestrians(), road.pedestrians());
+        road.reseed(loaded.seed());
+        road.update().unwrap();
+        loaded.update().unwrap();
+        assert!(loaded.physically_eq(&road));
+        assert!(Road::<10, 9, 100, 7, 7>::load_json(file.path()).is_err());
+    }
+
+    #[test]
+    fn car_stops_for_crossing_pedestrian() {
+        let car = CarBuilder::default()
+            .with_front_at(10)
+            .with_speed(3)
+            .with_deceleration_prob(Probability::ZERO)
+            .build()
             .unwrap();
+        let mut road = Road::<0, 1, 60, 2, 14>::new([], [car]).unwrap();
+        road.add_pedestrian(Pedestrian::new(20, 0, WalkDirection::Right))
+            .unwrap();
+        assert!(road
+            .add_pedestrian(Pedestrian::new(8, 0, WalkDirection::Right))
+            .is_err());
 
-        assert_eq!(front_gap, 5);
+        let mut stopped = false;
+        for _ in 0..30 {
+            road.update().unwrap();
+            let car = road.get_car(0);
+            let pedestrian = road.pedestrians()[0];
+            stopped |= car.speed() == 0;
+            if 20 <= car.front() {
+                assert!(pedestrian.is_finished() || (car.width() as isize) <= pedestrian.lat());
+                break;
+            }
+        }
+
+        assert!(stopped);
+        assert!(20 <= road.get_car(0).front());
     }
 
     #[test]
-    fn cells_front_gap_works() {
-        /*
-        0 1 2 3 4 5 6 7 8 9 10
-              t             l
-            x x           x x
-                - - - - >
-        length 5
-        */
-        let trailing_coord = Coord { lat: 3, long: 3 };
-        let leading_coord = Coord { lat: 3, long: 10 };
-        let dimensions = (2, 2);
-        let bikes = [
-            BikeBuilder::default()
-                .with_dimensions(dimensions)
-                .unwrap()
-                .with_front_right_at(trailing_coord),
+    fn sequential_updates_match_parallel() {
+        let bikes = [0, 7, 14, 21, 28].map(|front| {
             BikeBuilder::default()
-                .with_dimensions(dimensions)
+                .with_front_right_at(Coord {
+                    lat: 15,
+                    long: front,
+                })
+                .build()
                 .unwrap()
-                .with_front_right_at(leading_coord),
-        ]
-        .map(|builder| builder.try_into().unwrap());
-        let road = Road::<2, 0, 20, 3, 3>::new(bikes, []).unwrap();
-
-        let front_gap = road.cells.front_gap(&trailing_coord, None);
-
-        assert_eq!(front_gap, 5);
+        });
+        let cars = [4, 12, 20, 28, 36]
+            .map(|front| CarBuilder::default().with_front_at(front).build().unwrap());
+        let mut parallel = Road::<5, 5, 40, 2, 14>::new(bikes, cars).unwrap();
+        parallel.reseed(11);
+        let mut sequential = parallel.clone();
+        sequential.set_parallel(false);
+
+        for _ in 0..50 {
+            parallel.update().unwrap();
+            sequential.update().unwrap();
+            assert_eq!(
+                parallel.vehicle_states_as_string(),
+                sequential.vehicle_states_as_string()
+            );
+        }
+        assert_eq!(parallel.parallel(), cfg!(feature = "parallel"));
+        assert!(!sequential.parallel());
     }
 
     #[test]
-    fn route_width_works_bike() {
-        /*
-        right ->
-          0 1 2¦3 4 5 ^ back
-        0
-        1
-        2     \ ^
-        3 . . < x
-        4
+    fn bus_dwells_at_stop_while_followers_queue() {
+        let dwell = 6;
+        let bus = CarBuilder::bus(dwell)
+            .unwrap()
+            .with_front_at(20)
+            .with_deceleration_prob(Probability::ZERO)
+            .build()
+            .unwrap();
+        let follower = CarBuilder::default()
+            .with_front_at(5)
+            .with_deceleration_prob(Probability::ZERO)
+            .build()
+            .unwrap();
+        let mut road = Road::<0, 2, 80, 2, 14>::new([], [bus, follower]).unwrap();
+        road.add_bus_stop(40).unwrap();
+        assert!(road.add_bus_stop(80).is_err());
 
-        long in [2, 3] => width = 2
-        otherwise => width = 6
-        */
+        while !road.get_car(0).is_dwelling() {
+            road.update().unwrap();
+            assert!(road.get_car(0).front() <= 40);
+        }
+        assert_eq!(road.get_car(0).front(), 40);
 
-        let long = 3;
-        let lat = 3;
-        let dimensions = (2, 2);
-        let bikes = [BikeBuilder::default()
-            .with_dimensions(dimensions)
-            .unwrap()
-            .with_front_at(long)
-            .with_right_at(lat)]
-        .map(|builder| builder.try_into().unwrap());
-        let road = Road::<1, 0, 20, 3, 3>::new(bikes, []).unwrap();
+        for _ in 0..dwell {
+            road.update().unwrap();
+            assert_eq!(road.get_car(0).front(), 40);
+            assert_eq!(road.get_car(0).speed(), 0);
+        }
+        // the follower has caught up and is stuck behind the bus
+        assert_eq!(road.get_car(1).speed(), 0);
+        assert_eq!(road.car_front_gap(1), Some(0));
+        assert!(!road.get_car(0).is_dwelling());
 
-        assert_eq!(road.route_width(0), 6);
-        assert_eq!(road.route_width(1), 6);
-        assert_eq!(road.route_width(2), 2);
-        assert_eq!(road.route_width(3), 2);
-        assert_eq!(road.route_width(4), 6);
-        assert_eq!(road.route_width(5), 6);
-        assert_eq!(road.route_width(21), 6);
-        assert_eq!(road.route_width(22), 2);
-        assert_eq!(road.route_width(23), 2);
-        assert_eq!(road.route_width(24), 6);
+        for _ in 0..3 {
+            road.update().unwrap();
+        }
+        assert!(40 < road.get_car(0).front());
+        assert!(0 < road.get_car(1).speed());
     }
 
     #[test]
-    fn route_width_works_car() {
-        /*
-        where a car is always has 0 route width
-        */
+    fn dwelling_bus_survives_save_load_and_modify() {
+        let bus = CarBuilder::bus(6)
+            .unwrap()
+            .with_front_at(36)
+            .with_speed(4)
+            .with_deceleration_prob(Probability::ZERO)
+            .build()
+            .unwrap();
+        let mut road = Road::<0, 1, 80, 2, 14>::new([], [bus]).unwrap();
+        road.add_bus_stop(40).unwrap();
+        road.update_until(10, |road| road.get_car(0).is_dwelling())
+            .unwrap();
+        road.update().unwrap();
+        let file = tempfile::NamedTempFile::new().unwrap();
 
-        let long = 3;
-        let cars =
-            [CarBuilder::default().with_front_at(long)].map(|builder| builder.try_into().unwrap());
-        const ROAD_LEN: usize = 20;
-        const ROAD_WID: usize = 14;
-        let road = Road::<0, 1, ROAD_LEN, 0, ROAD_WID>::new([], cars).unwrap();
+        road.save_json(file.path()).unwrap();
+        let mut loaded = Road::<0, 1, 80, 2, 14>::load_json(file.path()).unwrap();
+        road.modify_car(0, |builder| *builder = builder.with_group(1))
+            .unwrap();
 
-        println!("cells:\n{}", road.cells());
-        let car_longs: HashSet<isize> = road
-            .get_car(0)
-            .occupied_cells()
-            .map(|coord| coord.long.rem_euclid(ROAD_LEN as isize))
-            .collect();
-        println!("occupied longs: {:?}", car_longs);
-        for long in 0..ROAD_LEN as isize {
-            println!("long: {}", long);
-            let expected_width = match car_longs.contains(&long) {
-                true => {
-                    println!("expected 0 width as car is here");
-                    0
-                }
-                false => {
-                    println!("expected full width as car is not here");
-                    ROAD_WID
-                }
-            };
-            assert_eq!(road.route_width(long), expected_width);
+        assert!(loaded.physically_eq(&road));
+        for road in [&mut road, &mut loaded] {
+            let bus = road.get_car(0);
+            assert!(bus.is_dwelling());
+            assert!(bus.brake_light());
+            // still waiting out the rest of its dwell
+            road.update().unwrap();
+            assert_eq!(road.get_car(0).front(), 40);
+            assert!(road.get_car(0).is_dwelling());
         }
     }
 
     #[test]
-    fn cells_front_gap_works_no_space() {
-        /*
-        0 1 2 3 4 5 6 7 8 9 10
-              t   l
-            x x o o
-                >
-        length 0
-        */
-        let trailing_coord = Coord { lat: 3, long: 3 };
-        let leading_coord = Coord { lat: 3, long: 5 };
-        let dimensions = (2, 2);
-        let bikes = [
-            BikeBuilder::default()
-                .with_dimensions(dimensions)
-                .unwrap()
-                .with_front_right_at(trailing_coord),
-            BikeBuilder::default()
-                .with_dimensions(dimensions)
-                .unwrap()
-                .with_front_right_at(leading_coord),
-        ]
-        .map(|builder| builder.try_into().unwrap());
-        let road = Road::<2, 0, 20, 3, 3>::new(bikes, []).unwrap();
+    fn vehicle_slices_have_const_generic_lengths() {
+        let cars =
+            [5, 15, 25].map(|front| CarBuilder::default().with_front_at(front).build().unwrap());
+        let bike = BikeBuilder::deterministic_default()
+            .with_front_right_at(Coord { lat: 15, long: 3 })
+            .build()
+            .unwrap();
+        let road = Road::<1, 3, 40, 2, 14>::new([bike], cars).unwrap();
 
-        let front_gap = road.cells.front_gap(&trailing_coord, None);
+        assert_eq!(road.cars().len(), 3);
+        assert_eq!(road.bikes().len(), 1);
+        let car_fronts: Vec<isize> = road.cars().iter().map(|car| car.front()).collect();
+        assert_eq!(car_fronts, [5, 15, 25]);
+        assert_eq!(
+            road.bikes()[0].rectangle_occupation(),
+            bike.rectangle_occupation()
+        );
+    }
 
-        assert_eq!(front_gap, 0);
+    #[test]
+    fn oncoming_cars_pass_in_adjacent_lanes() {
+        let forward = CarBuilder::default()
+            .with_front_at(10)
+            .with_speed_max(3)
+            .unwrap();
+        let backward = CarBuilder::default()
+            .with_front_at(30)
+            .with_speed_max(3)
+            .unwrap()
+            .with_direction(Direction::Backward)
+            .with_left_at(7)
+            .unwrap();
+        let cars = [forward, backward].map(|builder| builder.build().unwrap());
+        let mut road = Road::<0, 2, 40, 0, 14>::new([], cars).unwrap();
+        assert_eq!(road.car_front_gap(1), Some(35));
+
+        let (mut forward_travelled, mut backward_travelled) = (0, 0);
+        for _ in 0..20 {
+            let fronts = road.cars.map(|car| car.front());
+            road.update().unwrap();
+            road.assert_safe_following();
+            forward_travelled += (road.get_car(0).front() - fronts[0]).rem_euclid(40);
+            backward_travelled += (fronts[1] - road.get_car(1).front()).rem_euclid(40);
+            assert_eq!(
+                forward_travelled % 40,
+                (road.get_car(0).front() - 10).rem_euclid(40)
+            );
+            assert_eq!(
+                backward_travelled % 40,
+                (30 - road.get_car(1).front()).rem_euclid(40)
+            );
+        }
+
+        // each has gone past the other's starting point
+        assert!(20 < forward_travelled && 20 < backward_travelled);
+        assert!(road.get_car(1).occupied_cells().all(|coord| 7 <= coord.lat));
     }
 
     #[test]
-    fn bike_is_where_expected() {
-        /*
-        0 1 2 3 4 5 6 7 8 9 10
-              t             l
-            x x           x x
-                - - - - >
-        length 5
-        */
-        let coord = Coord { lat: 3, long: 10 };
-        let dimensions = (2, 2);
-        let bikes = [BikeBuilder::default()
-            .with_dimensions(dimensions)
+    fn backward_car_gap_stops_at_lane_drop_and_incident() {
+        let cars = [CarBuilder::default()
+            .with_front_at(30)
+            .with_left_at(2)
             .unwrap()
-            .with_front_right_at(coord)]
-        .map(|builder| builder.try_into().unwrap());
-        let road = Road::<1, 0, 20, 3, 3>::new(bikes, []).unwrap();
+            .with_direction(Direction::Backward)
+            .build()
+            .unwrap()];
+        let mut road = Road::<0, 1, 60, 7, 7>::new([], cars).unwrap();
+        assert_eq!(road.car_front_gap(0), Some(55));
+
+        // behind the car to a forward one, so out of its way
+        road.add_lane_drop(LaneDrop {
+            long_range: 35..=40,
+            motor_lane_width: 3,
+        })
+        .unwrap();
+        road.add_lane_drop(LaneDrop {
+            long_range: 15..=20,
+            motor_lane_width: 5,
+        })
+        .unwrap();
+        assert_eq!(road.car_front_gap(0), Some(9));
 
-        let bike = road.get_bike(0);
-        let occupied_coords: Vec<Coord> = bike.rectangle_occupation().occupied_cells().collect();
+        road.add_timed_incident(25, 2, 10).unwrap();
+        assert_eq!(road.car_front_gap(0), Some(4));
+    }
 
-        println!("occupation: {:?}", bike.rectangle_occupation());
-        println!("occupied coords: {:?}", occupied_coords);
-        println!("length: {}", occupied_coords.len());
+    #[test]
+    fn backward_car_stops_behind_vehicle_ahead_of_it() {
+        let cars =
+            [(20, Direction::Forward), (35, Direction::Backward)].map(|(front, direction)| {
+                CarBuilder::default()
+                    .with_front_at(front)
+                    .with_direction(direction)
+                    .with_acceleration_prob(Probability::ONE)
+                    .build()
+                    .unwrap()
+            });
+        let mut road = Road::<0, 2, 40, 0, 14>::new([], cars).unwrap();
+        road.modify_car(1, |builder| {
+            *builder = builder.with_acceleration_prob(Probability::ZERO)
+        })
+        .unwrap();
+        assert_eq!(road.car_front_gap(1), Some(14));
 
-        let found_vehicle = road.cells.get(&coord).unwrap().unwrap();
+        for _ in 0..20 {
+            road.update().unwrap();
+        }
 
-        assert_eq!(*found_vehicle, Vehicle::Bike(0));
+        assert_eq!(road.get_car(0).front(), 20);
+        assert_eq!(road.get_car(1).front(), 21);
+        assert_eq!(road.car_front_gap(1), Some(0));
     }
 
     #[test]
-    fn rectangle_occupies_cells_correct_size_larger() {
-        let width = 3;
-        let length = 4;
-        let area = (width * length) as usize;
-        let occupier = RectangleOccupier {
-            front: 2,
-            right: 5,
-            width,
-            length,
-        };
-        println!("occupier: {:?}", occupier);
+    fn run_to_channel_sends_a_frame_per_update() {
+        let cars = [0, 20].map(|front| CarBuilder::default().with_front_at(front).build().unwrap());
+        let road = Road::<0, 2, 40, 2, 14>::new([], cars).unwrap();
+        let (tx, rx) = std::sync::mpsc::channel();
 
-        assert_eq!(occupier.occupied_cells().count(), area);
+        let worker = std::thread::spawn(move || road.run_to_channel(15, tx));
+        let frames: Vec<IterationRecord> = rx.iter().map(|frame| frame.unwrap()).collect();
+        let final_road = worker.join().unwrap().unwrap();
+
+        assert_eq!(frames.len(), 15);
+        assert_eq!(
+            frames.last(),
+            Some(&IterationRecord::from_road(&final_road))
+        );
     }
 
     #[test]
-    fn rectangle_occupier_correct_size() {
-        let width = 2;
-        let length = 2;
-        let area = width * length;
-        let occupier = RectangleOccupier {
-            front: 2,
-            right: 2,
-            width,
-            length,
-        };
+    fn vehicle_at_finds_bike_in_its_cells() {
+        let bikes = [3, 10].map(|front| {
+            BikeBuilder::deterministic_default()
+                .with_front_right_at(Coord {
+                    lat: 15,
+                    long: front,
+                })
+                .build()
+                .unwrap()
+        });
+        let road = Road::<2, 0, 20, 2, 14>::new(bikes, []).unwrap();
 
-        assert_eq!(occupier.occupied_cells().count(), area as usize)
+        assert_eq!(
+            road.vehicle_at(&Coord { lat: 15, long: 10 }).unwrap(),
+            Some(&Vehicle::Bike(1))
+        );
+        assert_eq!(
+            road.vehicle_at(&Coord { lat: 15, long: 23 }).unwrap(),
+            Some(&Vehicle::Bike(0))
+        );
+        assert_eq!(road.vehicle_at(&Coord { lat: 15, long: 11 }).unwrap(), None);
+        assert!(road.vehicle_at(&Coord { lat: 16, long: 10 }).is_err());
     }
 
-    proptest!(
-        #[test]
-        fn rectangle_occupier_correct_size_proptest(occupier in arb_rectangle_occupier(-10_000..10_000, -100..100, 10, 10)) {
-            let area = occupier.width * occupier.length;
-            println!("occupier: {:?}", occupier);
-            prop_assert_eq!(occupier.occupied_cells().count(), area)
+    #[test]
+    fn occupancy_grid_matches_cells() {
+        let car = CarBuilder::default().with_front_at(12).build().unwrap();
+        let road = Road::<0, 1, 20, 2, 14>::new([], [car]).unwrap();
+
+        let grid = road.occupancy_grid();
+
+        assert_eq!(grid.len(), 20);
+        assert!(grid.iter().all(|long| long.len() == 16));
+        for (long, lats) in grid.iter().enumerate() {
+            for (lat, cell) in lats.iter().enumerate() {
+                let in_car = (8..=12).contains(&long) && lat < car.width();
+                assert_eq!(
+                    *cell,
+                    in_car.then_some(Vehicle::Car(0)),
+                    "at {} {}",
+                    long,
+                    lat
+                );
+            }
         }
+    }
 
-        #[test]
-        fn rectangle_occupier_correct_width_proptest(occupier: RectangleOccupier) {
-            println!("occupier: {:?}", occupier);
-            prop_assert_eq!(occupier.width_iterator().count(), occupier.width);
-        }
-    );
+    #[test]
+    fn slowest_and_fastest_cars() {
+        let cars = [(5, 2), (20, 6), (35, 0)].map(|(front, speed)| {
+            CarBuilder::default()
+                .with_front_at(front)
+                .with_speed(speed)
+                .build()
+                .unwrap()
+        });
+        let road = Road::<0, 3, 60, 2, 14>::new([], cars).unwrap();
+
+        assert_eq!(
+            road.slowest_car()
+                .map(|(car_id, car)| (car_id, car.speed())),
+            Some((2, 0))
+        );
+        assert_eq!(
+            road.fastest_car()
+                .map(|(car_id, car)| (car_id, car.speed())),
+            Some((1, 6))
+        );
+        assert!(Road::<0, 0, 60, 2, 14>::new([], [])
+            .unwrap()
+            .slowest_car()
+            .is_none());
+        assert!(Road::<0, 0, 60, 2, 14>::new([], [])
+            .unwrap()
+            .fastest_car()
+            .is_none());
+    }
 
     #[test]
-    fn rectangle_occupier_correct_size_v2() {
-        let width = 2;
-        let length = 2;
-        let area = (width * length) as usize;
-        let occupier = RectangleOccupier {
-            front: 2,
-            right: 2,
-            width,
-            length,
-        };
+    fn extreme_car_ties_go_to_lowest_id() {
+        let cars = [5, 20, 35].map(|front| {
+            CarBuilder::default()
+                .with_front_at(front)
+                .with_speed(3)
+                .build()
+                .unwrap()
+        });
+        let road = Road::<0, 3, 60, 2, 14>::new([], cars).unwrap();
 
-        println!("occupier: {:?}", occupier);
-        assert_eq!(occupier.occupied_cells().count(), area)
+        assert_eq!(road.slowest_car().map(|(car_id, _)| car_id), Some(0));
+        assert_eq!(road.fastest_car().map(|(car_id, _)| car_id), Some(0));
     }
 
     #[test]
-    fn rectangle_width_correct() {
-        let width = 2;
-        let occupier = RectangleOccupier {
-            front: 2,
-            right: 2,
-            width,
-            length: 2,
-        };
+    fn leader_and_follower_wrap_around() {
+        let cars = [5, 40].map(|front| CarBuilder::default().with_front_at(front).build().unwrap());
+        let bikes = [BikeBuilder::default().with_front_right_at(Coord { long: 30, lat: 15 })]
+            .map(|builder| builder.build().unwrap());
+        let road = Road::<1, 2, 60, 2, 14>::new(bikes, cars).unwrap();
+
+        assert_eq!(road.leader_of(Vehicle::Car(0)), Some(Vehicle::Car(1)));
+        assert_eq!(road.leader_of(Vehicle::Car(1)), Some(Vehicle::Car(0)));
+        assert_eq!(road.follower_of(Vehicle::Car(0)), Some(Vehicle::Car(1)));
+        assert_eq!(road.follower_of(Vehicle::Car(1)), Some(Vehicle::Car(0)));
+    }
 
-        assert_eq!(occupier.width_iterator().count(), width as usize)
+    #[test]
+    fn leader_and_follower_of_lone_vehicle_are_none() {
+        let cars = [CarBuilder::default().with_front_at(5).build().unwrap()];
+        let bikes = [BikeBuilder::default().with_front_right_at(Coord { long: 30, lat: 15 })]
+            .map(|builder| builder.build().unwrap());
+        let road = Road::<1, 1, 60, 2, 14>::new(bikes, cars).unwrap();
+
+        assert_eq!(road.leader_of(Vehicle::Bike(0)), None);
+        assert_eq!(road.follower_of(Vehicle::Bike(0)), None);
+        assert_eq!(road.leader_of(Vehicle::Car(0)), None);
     }
 
     #[test]
-    fn rectangle_length_correct() {
-        let length = 2;
-        let occupier = RectangleOccupier {
-            front: 2,
-            right: 2,
-            width: 2,
-            length,
-        };
+    fn iter_vehicles_yields_bikes_then_cars() {
+        let cars = [5, 40].map(|front| {
+            CarBuilder::default()
+                .with_front_at(front)
+                .with_speed(2)
+                .build()
+                .unwrap()
+        });
+        let bikes = [BikeBuilder::default().with_front_right_at(Coord { long: 30, lat: 15 })]
+            .map(|builder| builder.build().unwrap());
+        let road = Road::<1, 2, 60, 2, 14>::new(bikes, cars).unwrap();
 
-        assert_eq!(occupier.length_iterator().count(), length as usize)
+        let vehicles: Vec<Vehicle> = road.iter_vehicles().map(|(vehicle, _)| vehicle).collect();
+        assert_eq!(
+            vehicles,
+            vec![Vehicle::Bike(0), Vehicle::Car(0), Vehicle::Car(1)]
+        );
+        for (vehicle, vehicle_ref) in road.iter_vehicles() {
+            let cells: HashSet<Coord> = vehicle_ref.occupied_cells().collect();
+            assert!(cells
+                .iter()
+                .all(|coord| road.vehicle_at(coord).unwrap() == Some(&vehicle)));
+            assert_eq!(vehicle_ref.speed(), if vehicle.is_car() { 2 } else { 0 });
+        }
     }
 
     #[test]
-    fn positions_on_nm_lane_higher_priority_than_m_lane() {
-        let road = Road::<1, 0, 100, 7, 7>::new([BikeBuilder::default().build().unwrap()], []);
+    fn groups_survive_build_and_update() {
+        let cars = [CarBuilder::default()
+            .with_front_at(5)
+            .with_group(2)
+            .build()
+            .unwrap()];
+        let bikes = [BikeBuilder::default()
+            .with_front_right_at(Coord { long: 30, lat: 15 })
+            .with_group(1)
+            .build()
+            .unwrap()];
+        let mut road = Road::<1, 1, 60, 2, 14>::new(bikes, cars).unwrap();
+
+        road.update().unwrap();
+
+        assert_eq!(road.get_car(0).group(), 2);
+        assert_eq!(road.get_bike(0).group(), 1);
+        assert_eq!(road.car_states()[0].group, 2);
+        assert!(road.vehicle_states_as_string().contains("\"group\":1"));
     }
 
     #[test]
-    fn medium_sized_example_road_builds() {
-        let _road: Road<10, 10, 100, 7, 7> = {
-            let bikes: Vec<Bike> = (0..10)
-                .map(|bike_id| {
-                    return BikeBuilder::default()
-                        .with_front_at(10 * bike_id)
-                        .with_right_at(8)
-                        .build()
-                        .unwrap();
-                })
-                .collect();
-            for bike in &bikes {
-                println!(
-                    "occupied_cells: {:?}",
-                    bike.occupied_cells().collect::<Vec<Coord>>()
-                )
-            }
-            let cars: Vec<Car> = (0..10)
-                .map(|car_id| {
-                    return CarBuilder::default()
-                        .with_front_at(10 * car_id)
-                        .build()
-                        .unwrap();
-                })
-                .collect();
-            for car in &cars {
-                println!(
-                    "occupied_cells: {:?}",
-                    car.occupied_cells().collect::<Vec<Coord>>()
-                )
-            }
-            Road::new(
-                bikes.try_into().expect("should be right number of bikes"),
-                cars.try_into().expect("should be right number of cars"),
-            )
-            .unwrap()
-        };
+    fn vehicles_by_position() {
+        // the last car beside the other two so that they don't overlap
+        let cars = [(10, 0), (2, 0), (7, 6)].map(|(front, left)| {
+            CarBuilder::default()
+                .with_front_at(front)
+                .with_left_at(left)
+                .unwrap()
+                .build()
+                .unwrap()
+        });
+        let bikes = [(30, 15), (61, 15), (30, 13)].map(|(long, lat)| {
+            BikeBuilder::default()
+                .with_front_right_at(Coord { long, lat })
+                .build()
+                .unwrap()
+        });
+        let road = Road::<3, 3, 60, 4, 12>::new(bikes, cars).unwrap();
+
+        assert_eq!(road.cars_by_position(), vec![1, 2, 0]);
+        // the second bike wraps around to long 1, the others tie
+        assert_eq!(road.bikes_by_position(), vec![1, 0, 2]);
     }
 
     #[test]
-    fn medium_sized_example_road_updates() {
-        let mut road: Road<10, 10, 100, 7, 7> = {
-            let bikes: Vec<Bike> = (0..10)
-                .map(|bike_id| {
-                    return BikeBuilder::default()
-                        .with_front_at(10 * bike_id)
-                        .with_right_at(8)
-                        .build()
-                        .unwrap();
-                })
-                .collect();
-            let cars: Vec<Car> = (0..10)
-                .map(|car_id| {
-                    return CarBuilder::default()
-                        .with_front_at(10 * car_id)
-                        .build()
-                        .unwrap();
-                })
-                .collect();
-            Road::new(
-                bikes.try_into().expect("should be right number of bikes"),
-                cars.try_into().expect("should be right number of cars"),
-            )
-            .unwrap()
-        };
+    fn car_passing_bike_one_cell_away_is_close_pass() {
+        // the car speeds up to 7, widening to lat 6, one cell from the bike
+        let cars = [CarBuilder::default()
+            .with_front_at(10)
+            .with_speed(5)
+            .with_deceleration_prob(Probability::ZERO)
+            .build()
+            .unwrap()];
+        let bikes = [BikeBuilder::default()
+            .with_front_right_at(Coord { long: 14, lat: 9 })
+            .with_lateral_ignorance(Probability::ONE)
+            .with_deceleration_prob(Probability::ZERO)
+            .build()
+            .unwrap()];
+        let mut road = Road::<1, 1, 60, 2, 14>::new(bikes, cars).unwrap();
 
-        for iter_num in 0u16..1000 {
-            println!("Iteration #{}", iter_num);
-            println!("{}", road.cells());
-            road.update().unwrap();
-        }
+        road.update().unwrap();
+
+        assert_eq!(road.get_car(0).front(), 17);
+        assert_eq!(road.close_pass_events(2), 1);
+        assert_eq!(road.close_pass_events(1), 0);
+
+        // already ahead of the bike, so not passing it again
+        road.update().unwrap();
+        assert_eq!(road.close_pass_events(2), 1);
     }
 
     #[test]
-    fn one_car_one_bike_updates() {
-        let mut road: Road<1, 1, 10, 4, 4> = Road::new(
-            [BikeBuilder::default()
-                .with_front_at(0)
-                .with_right_at(7)
-                .build()
-                .unwrap()],
-            [CarBuilder::default().with_front_at(0).build().unwrap()],
-        )
-        .unwrap();
+    fn trailing_car_front_gap_is_clear_distance() {
+        // the leading car's back is at 21
+        let cars =
+            [10, 25].map(|front| CarBuilder::default().with_front_at(front).build().unwrap());
+        let road = Road::<0, 2, 60, 0, 14>::new([], cars).unwrap();
 
-        for iter_num in 0u16..60000 {
-            println!("Iteration #{}", iter_num);
-            println!("{}\n", road.cells());
-            road.update().unwrap();
-        }
+        assert_eq!(road.car_front_gap(0), Some(10));
+        assert_eq!(
+            road.front_gap(&road.get_car(0).rectangle_occupation()),
+            Some(10)
+        );
+        assert_eq!(road.car_front_gap(2), None);
     }
 
     #[test]
-    fn one_car_one_bike_updates_v2() {
-        let mut road: Road<1, 1, 10, 4, 4> = Road::new(
-            [BikeBuilder::default()
-                .with_front_at(5)
-                .with_right_at(5)
-                .with_forward_speed(3)
+    fn next_best_fallback_shifts_losing_bike() {
+        // both bikes want lat 4, the only one clear ahead, the first bike's
+        // next best is lat 2
+        let bikes = [(3, 1), (6, 2)].map(|(right, rightward_speed_max)| {
+            BikeBuilder::deterministic_default()
+                .with_dimensions((1, 1))
+                .unwrap()
+                .with_rightward_speed_max(rightward_speed_max)
                 .unwrap()
+                .with_front_right_at(Coord {
+                    long: 5,
+                    lat: right,
+                })
                 .build()
-                .unwrap()],
-            [CarBuilder::default().with_front_at(0).build().unwrap()],
-        )
-        .unwrap();
+                .unwrap()
+        });
+        let mut hold = Road::<2, 0, 20, 6, 2>::new(bikes, []).unwrap();
+        for (long, lat) in [(8, 2), (6, 3), (6, 5), (6, 6), (6, 7)] {
+            hold.add_timed_incident(long, lat, 5).unwrap();
+        }
+        let mut next_best = hold.clone();
+        next_best.set_lateral_fallback(LateralFallback::NextBest);
+
+        // picking the first index every time shuffles the second bike first
+        for road in [&mut hold, &mut next_best] {
+            road.bikes_lateral_update_with(&mut ScriptedSource { decisions: vec![] })
+                .unwrap();
+            assert_eq!(road.get_bike(1).rectangle_occupation().right, 4);
+        }
 
-        println!("{}", road.cells());
-        road.update().unwrap();
+        assert_eq!(hold.get_bike(0).rectangle_occupation().right, 3);
+        assert_eq!(next_best.get_bike(0).rectangle_occupation().right, 2);
+        assert!(!next_best.vehicle_collides(Vehicle::Bike(0)));
     }
 
     #[test]
-    fn car_occupation_correct() {
-        let cars = [CarBuilder::default()].map(|builder| builder.try_into().unwrap());
-        let road = Road::<0, 1, 20, 3, 3>::new([], cars).unwrap();
+    fn next_best_fallback_keeps_clear_of_unplaced_bikes() {
+        // all three bikes want lat 4, the only one clear ahead. The second
+        // bike gets it, then the third falls back. It mustn't take the first
+        // bike's lat 3, as the first bike has nowhere else to go
+        let bikes =
+            [(3, 1, 2), (6, 2, 1), (2, 2, 1)].map(|(right, rightward_speed_max, length)| {
+                BikeBuilder::deterministic_default()
+                    .with_dimensions((1, length))
+                    .unwrap()
+                    .with_rightward_speed_max(rightward_speed_max)
+                    .unwrap()
+                    .with_front_right_at(Coord {
+                        long: 5,
+                        lat: right,
+                    })
+                    .build()
+                    .unwrap()
+            });
+        let mut road = Road::<3, 0, 20, 6, 2>::new(bikes, []).unwrap();
+        road.set_lateral_fallback(LateralFallback::NextBest);
+        for (long, lat) in [
+            (6, 0),
+            (6, 1),
+            (6, 2),
+            (4, 2),
+            (8, 3),
+            (6, 5),
+            (6, 6),
+            (6, 7),
+        ] {
+            road.add_timed_incident(long, lat, 5).unwrap();
+        }
 
-        let car_occupation: HashSet<Coord> = road.get_car(0).occupied_cells().collect();
-        let cells_occupation: HashSet<Coord> = road
-            .cells()
-            .cells()
-            .keys()
-            .map(|coord| coord.to_owned())
-            .collect();
+        // picking the first index every time places the bikes in the order
+        // 1, 2, 0
+        road.bikes_lateral_update_with(&mut ScriptedSource { decisions: vec![] })
+            .unwrap();
 
-        assert_eq!(car_occupation, cells_occupation);
+        assert_eq!(road.get_bike(1).rectangle_occupation().right, 4);
+        assert_eq!(road.get_bike(2).rectangle_occupation().right, 2);
+        assert_eq!(road.get_bike(0).rectangle_occupation().right, 3);
+        let fresh = RoadCells::<20, 6, 2>::try_from(&road).unwrap();
+        assert_eq!(road.cells.cells, fresh.cells);
     }
 }
@@ -0,0 +1,386 @@
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+
+use crate::{
+    bike::{Bike, BikeBuilder},
+    car::{Car, CarBuilder},
+    population::{evenly_spaced_bikes, evenly_spaced_cars},
+    road::{Road, VehicleState},
+    stats::Units,
+};
+
+/// What to run, on a road whose size is given by the const generics of
+/// `run_simulation`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct RunConfig {
+    pub num_iterations: usize,
+    /// Seeds the road's random number generator, otherwise chosen at random.
+    pub seed: Option<u64>,
+    /// Adds the speeds and densities in km/h and vehicles per km to the
+    /// output, which is otherwise in cells and steps.
+    pub units: Option<Units>,
+}
+
+impl RunConfig {
+    /// Overrides the defaults with any `--iterations <count>` and
+    /// `--seed <seed>` in the command line arguments, which shouldn't include
+    /// the program name. Either of `--cell-length <metres>` and
+    /// `--step-duration <seconds>` turns on real units, the other staying at
+    /// its `Units::NASCH` size.
+    pub fn from_args(
+        args: impl IntoIterator<Item = String>,
+        default_iterations: usize,
+        default_seed: Option<u64>,
+    ) -> Result<Self> {
+        let mut config = Self {
+            num_iterations: default_iterations,
+            seed: default_seed,
+            units: None,
+        };
+        let mut args = args.into_iter();
+        while let Some(flag) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| anyhow!("missing value after {}", flag))?;
+            match flag.as_str() {
+                "--iterations" => {
+                    config.num_iterations = value
+                        .parse()
+                        .with_context(|| format!("invalid iteration count {}", value))?;
+                }
+                "--seed" => {
+                    config.seed = Some(
+                        value
+                            .parse()
+                            .with_context(|| format!("invalid seed {}", value))?,
+                    );
+                }
+                "--cell-length" | "--step-duration" => {
+                    let size: f64 = value
+                        .parse()
+                        .with_context(|| format!("invalid {} {}", flag, value))?;
+                    let units = config.units.unwrap_or(Units::NASCH);
+                    config.units = Some(match flag.as_str() {
+                        "--cell-length" => Units::new(size, units.step_duration_s)?,
+                        _ => Units::new(units.cell_length_m, size)?,
+                    });
+                }
+                _ => return Err(anyhow!("unknown argument {}", flag)),
+            }
+        }
+        return Ok(config);
+    }
+}
+
+/// The builders the road's vehicles were built from.
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildInfo {
+    pub bikes: Vec<BikeBuilder>,
+    pub cars: Vec<CarBuilder>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RoadInfo {
+    pub num_bikes: usize,
+    pub num_cars: usize,
+    pub length: usize,
+    pub bl_width: usize,
+    pub ml_width: usize,
+    pub num_iterations: usize,
+    pub car_density: f64,
+    pub bike_density: f64,
+    pub seed: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub units: Option<Units>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub car_veh_per_km: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bike_veh_per_km: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VehicleFronts {
+    pub cars: Vec<isize>,
+    pub bikes: Vec<isize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VehicleStates {
+    pub cars: Vec<VehicleState>,
+    pub bikes: Vec<VehicleState>,
+}
+
+/// The state of the road at one iteration.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct IterationRecord {
+    pub vehicle_fronts: VehicleFronts,
+    pub vehicle_states: VehicleStates,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mean_car_speed: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mean_bike_speed: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mean_car_speed_kmh: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mean_bike_speed_kmh: Option<f64>,
+}
+
+impl IterationRecord {
+    pub fn from_road<
+        const B: usize,
+        const C: usize,
+        const L: usize,
+        const BLW: usize,
+        const MLW: usize,
+    >(
+        road: &Road<B, C, L, BLW, MLW>,
+    ) -> Self {
+        return Self::from_road_in_units(road, None);
+    }
+
+    /// The record with the mean speeds also in km/h if there are `units`.
+    pub fn from_road_in_units<
+        const B: usize,
+        const C: usize,
+        const L: usize,
+        const BLW: usize,
+        const MLW: usize,
+    >(
+        road: &Road<B, C, L, BLW, MLW>,
+        units: Option<&Units>,
+    ) -> Self {
+        return Self {
+            vehicle_fronts: VehicleFronts {
+                cars: road.cars().iter().map(|car| car.front()).collect(),
+                bikes: road.bikes().iter().map(|bike| bike.front()).collect(),
+            },
+            vehicle_states: VehicleStates {
+                cars: road.car_states(),
+                bikes: road.bike_states(),
+            },
+            mean_car_speed: road.mean_car_speed(),
+            mean_bike_speed: road.mean_bike_speed(),
+            mean_car_speed_kmh: units.and_then(|units| road.mean_car_speed_kmh(units)),
+            mean_bike_speed_kmh: units.and_then(|units| road.mean_bike_speed_kmh(units)),
+        };
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SimulationResult {
+    pub build_info: BuildInfo,
+    pub road_info: RoadInfo,
+    /// The road before the first update and after each one, so one more
+    /// record than there are iterations.
+    pub iterations: Vec<IterationRecord>,
+}
+
+/// Builds a road of default vehicles evenly spaced along it, bikes on the
+/// right hand edge and cars on the left, and records it over the configured
+/// number of updates.
+pub fn run_simulation<
+    const B: usize,
+    const C: usize,
+    const L: usize,
+    const BLW: usize,
+    const MLW: usize,
+>(
+    config: RunConfig,
+) -> Result<SimulationResult> {
+    let bikes = evenly_spaced_bikes::<B, L>((BLW + MLW) as isize - 1);
+    let cars = evenly_spaced_cars::<C, L>();
+    let built_bikes: Vec<Bike> = bikes
+        .iter()
+        .map(|builder| builder.build())
+        .collect::<Result<_>>()?;
+    let built_cars: Vec<Car> = cars
+        .iter()
+        .map(|builder| builder.build())
+        .collect::<Result<_>>()?;
+    check_fits_road_width(
+        "bikes",
+        built_bikes
+            .iter()
+            .map(|bike| bike.rectangle_occupation().width),
+        BLW + MLW,
+    )?;
+    check_fits_road_width("cars", built_cars.iter().map(Car::width), BLW + MLW)?;
+
+    let mut road = Road::<B, C, L, BLW, MLW>::new(
+        built_bikes
+            .try_into()
+            .expect("should be right number of bikes"),
+        built_cars
+            .try_into()
+            .expect("should be right number of cars"),
+    )?;
+    if let Some(seed) = config.seed {
+        road.reseed(seed);
+    }
+
+    let road_info = RoadInfo {
+        num_bikes: B,
+        num_cars: C,
+        length: L,
+        bl_width: BLW,
+        ml_width: MLW,
+        num_iterations: config.num_iterations,
+        car_density: road.car_density(),
+        bike_density: road.bike_density(),
+        seed: road.seed(),
+        units: config.units,
+        car_veh_per_km: config.units.map(|units| road.car_veh_per_km(&units)),
+        bike_veh_per_km: config.units.map(|units| road.bike_veh_per_km(&units)),
+    };
+    let units = config.units.as_ref();
+    let mut iterations = Vec::with_capacity(config.num_iterations + 1);
+    for _iter_num in 0..config.num_iterations {
+        iterations.push(IterationRecord::from_road_in_units(&road, units));
+        road.update()?;
+    }
+    iterations.push(IterationRecord::from_road_in_units(&road, units));
+
+    return Ok(SimulationResult {
+        build_info: BuildInfo {
+            bikes: bikes.into(),
+            cars: cars.into(),
+        },
+        road_info,
+        iterations,
+    });
+}
+
+/// Rejects vehicles wider than the road up front, rather than leaving it to
+/// the road to complain about cells off its edge.
+fn check_fits_road_width(
+    vehicles: &str,
+    widths: impl IntoIterator<Item = usize>,
+    road_width: usize,
+) -> Result<()> {
+    return match widths.into_iter().max() {
+        Some(widest) if road_width < widest => Err(anyhow!(
+            "{} are {} lats wide but the road (BL_WIDTH + ML_WIDTH) is only {}",
+            vehicles,
+            widest,
+            road_width
+        )),
+        _ => Ok(()),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        simulation::{run_simulation, RunConfig},
+        stats::Units,
+    };
+
+    fn args(args: &[&str]) -> Vec<String> {
+        return args.iter().map(|arg| arg.to_string()).collect();
+    }
+
+    #[test]
+    fn small_simulation_records_every_iteration() {
+        let config = RunConfig {
+            num_iterations: 20,
+            seed: Some(4),
+            units: None,
+        };
+
+        let result = run_simulation::<4, 2, 60, 3, 14>(config).unwrap();
+
+        assert_eq!(result.iterations.len(), 21);
+        assert_eq!(result.road_info.seed, 4);
+        assert_eq!(result.build_info.bikes.len(), 4);
+        assert!(result
+            .iterations
+            .iter()
+            .all(|record| record.vehicle_states.cars.len() == 2));
+
+        let rerun = run_simulation::<4, 2, 60, 3, 14>(config).unwrap();
+        assert_eq!(rerun.iterations, result.iterations);
+    }
+
+    #[test]
+    fn too_narrow_road_is_rejected() {
+        let config = RunConfig {
+            num_iterations: 1,
+            seed: None,
+            units: None,
+        };
+
+        let error = run_simulation::<4, 0, 60, 1, 0>(config).unwrap_err();
+        assert!(error.to_string().contains("bikes are 2 lats wide"));
+        let error = run_simulation::<0, 2, 60, 1, 1>(config).unwrap_err();
+        assert!(error.to_string().contains("cars are 5 lats wide"));
+        assert!(run_simulation::<4, 0, 60, 1, 1>(config).is_ok());
+    }
+
+    #[test]
+    fn args_override_defaults() {
+        let defaults = RunConfig::from_args(args(&[]), 1000, None).unwrap();
+        assert_eq!(
+            defaults,
+            RunConfig {
+                num_iterations: 1000,
+                seed: None,
+                units: None,
+            }
+        );
+
+        for iterations in [0, 3, 7] {
+            let config = RunConfig::from_args(
+                args(&["--seed", "9", "--iterations", &iterations.to_string()]),
+                1000,
+                Some(1),
+            )
+            .unwrap();
+            assert_eq!(config.seed, Some(9));
+
+            let result = run_simulation::<2, 1, 40, 3, 14>(config).unwrap();
+            assert_eq!(result.road_info.num_iterations, iterations);
+            assert_eq!(result.iterations.len(), iterations + 1);
+        }
+    }
+
+    #[test]
+    fn units_add_real_unit_metrics_to_the_output() {
+        let config = RunConfig::from_args(args(&["--step-duration", "0.5"]), 5, Some(2)).unwrap();
+        let units = Units {
+            step_duration_s: 0.5,
+            ..Units::NASCH
+        };
+        assert_eq!(config.units, Some(units));
+
+        let result = run_simulation::<2, 1, 40, 3, 14>(config).unwrap();
+
+        // 1 car in 40 cells of 7.5 m
+        assert_eq!(result.road_info.car_veh_per_km, Some(1000.0 / 300.0));
+        for record in &result.iterations {
+            let mean_car_speed = record.mean_car_speed.unwrap();
+            assert_eq!(
+                record.mean_car_speed_kmh,
+                Some(units.speed_to_kmh(mean_car_speed))
+            );
+        }
+        let json = serde_json::to_string(&result).unwrap();
+        assert!(json.contains("\"mean_bike_speed_kmh\""));
+        assert!(json.contains("\"bike_veh_per_km\""));
+
+        let unitless = run_simulation::<2, 1, 40, 3, 14>(RunConfig {
+            units: None,
+            ..config
+        })
+        .unwrap();
+        assert_eq!(unitless.road_info.car_veh_per_km, None);
+        assert!(!serde_json::to_string(&unitless).unwrap().contains("kmh"));
+    }
+
+    #[test]
+    fn bad_args_are_rejected() {
+        assert!(RunConfig::from_args(args(&["--iterations"]), 10, None).is_err());
+        assert!(RunConfig::from_args(args(&["--iterations", "-1"]), 10, None).is_err());
+        assert!(RunConfig::from_args(args(&["--speed", "3"]), 10, None).is_err());
+        assert!(RunConfig::from_args(args(&["--cell-length", "0"]), 10, None).is_err());
+    }
+}
@@ -0,0 +1,380 @@
+use std::collections::VecDeque;
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::road::Road;
+
+/// Physical sizes of a cell and a step, for reporting the unitless
+/// simulation quantities in real units.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Units {
+    pub cell_length_m: f64,
+    pub step_duration_s: f64,
+}
+
+impl Units {
+    /// The 7.5 m cells and 1 s steps of the Nagel-Schreckenberg model.
+    pub const NASCH: Self = Self {
+        cell_length_m: 7.5,
+        step_duration_s: 1.0,
+    };
+
+    /// Fails unless both sizes are finite and above 0.
+    pub fn new(cell_length_m: f64, step_duration_s: f64) -> Result<Self> {
+        for (name, size) in [
+            ("cell length", cell_length_m),
+            ("step duration", step_duration_s),
+        ] {
+            if !(size.is_finite() && 0.0 < size) {
+                return Err(anyhow!("{} must be above 0, instead {}", name, size));
+            }
+        }
+        return Ok(Self {
+            cell_length_m,
+            step_duration_s,
+        });
+    }
+
+    pub fn cells_to_m(&self, cells: f64) -> f64 {
+        return cells * self.cell_length_m;
+    }
+
+    pub fn steps_to_s(&self, steps: f64) -> f64 {
+        return steps * self.step_duration_s;
+    }
+
+    pub fn speed_to_m_per_s(&self, cells_per_step: f64) -> f64 {
+        return self.cells_to_m(cells_per_step) / self.step_duration_s;
+    }
+
+    pub fn speed_to_kmh(&self, cells_per_step: f64) -> f64 {
+        return self.speed_to_m_per_s(cells_per_step) * 3.6;
+    }
+
+    /// Converts a number of vehicles per cell of road length to vehicles per
+    /// kilometre.
+    pub fn density_to_veh_per_km(&self, vehicles_per_cell: f64) -> f64 {
+        return vehicles_per_cell * 1000.0 / self.cell_length_m;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SpeedSummary {
+    pub min: isize,
+    pub median: f64,
+    pub max: isize,
+    pub mean: f64,
+}
+
+impl SpeedSummary {
+    /// Summarises the speeds, or `None` if there are none. The median of an
+    /// even number of speeds is the mean of the middle two.
+    pub fn from_speeds(speeds: impl IntoIterator<Item = isize>) -> Option<Self> {
+        let mut sorted: Vec<isize> = Vec::new();
+        let mut min = isize::MAX;
+        let mut max = isize::MIN;
+        let mut sum = 0;
+        for speed in speeds {
+            min = min.min(speed);
+            max = max.max(speed);
+            sum += speed;
+            sorted.push(speed);
+        }
+        if sorted.is_empty() {
+            return None;
+        }
+        sorted.sort_unstable();
+
+        let middle = sorted.len() / 2;
+        let median = match sorted.len() % 2 {
+            0 => (sorted[middle - 1] + sorted[middle]) as f64 / 2.0,
+            _ => sorted[middle] as f64,
+        };
+        return Some(Self {
+            min,
+            median,
+            max,
+            mean: sum as f64 / sorted.len() as f64,
+        });
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SpeedStats {
+    pub mean: f64,
+    /// Population standard deviation.
+    pub std_dev: f64,
+    pub p15: f64,
+    pub p50: f64,
+    pub p85: f64,
+}
+
+impl SpeedStats {
+    /// Computes the stats, or `None` if there are no speeds. Percentiles are
+    /// linearly interpolated between the closest ranks.
+    pub fn from_speeds(speeds: impl IntoIterator<Item = isize>) -> Option<Self> {
+        let mut sorted: Vec<f64> = speeds.into_iter().map(|speed| speed as f64).collect();
+        if sorted.is_empty() {
+            return None;
+        }
+        sorted.sort_unstable_by(f64::total_cmp);
+
+        let count = sorted.len() as f64;
+        let mean = sorted.iter().sum::<f64>() / count;
+        let variance = sorted
+            .iter()
+            .map(|speed| (speed - mean).powi(2))
+            .sum::<f64>()
+            / count;
+        return Some(Self {
+            mean,
+            std_dev: variance.sqrt(),
+            p15: percentile(&sorted, 15.0),
+            p50: percentile(&sorted, 50.0),
+            p85: percentile(&sorted, 85.0),
+        });
+    }
+}
+
+fn percentile(sorted: &[f64], percent: f64) -> f64 {
+    let rank = percent / 100.0 * (sorted.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    return sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64);
+}
+
+/// Mean of the per-step mean speeds over the last `window` steps, for
+/// smoothing out the step to step noise of a run.
+#[derive(Debug, Clone)]
+pub struct RollingMean {
+    window: usize,
+    // steps without a mean speed (no vehicles) are kept so that they still
+    // push older values out of the window
+    values: VecDeque<Option<f64>>,
+}
+
+impl RollingMean {
+    pub fn new(window: usize) -> Result<Self> {
+        if window == 0 {
+            return Err(anyhow!("rolling mean window must be at least one step"));
+        }
+        return Ok(Self {
+            window,
+            values: VecDeque::with_capacity(window),
+        });
+    }
+
+    pub fn window(&self) -> usize {
+        return self.window;
+    }
+
+    pub fn push(&mut self, value: Option<f64>) {
+        if self.values.len() == self.window {
+            self.values.pop_front();
+        }
+        self.values.push_back(value);
+    }
+
+    /// The mean of the values in the window, `None` if none were `Some`.
+    pub fn current(&self) -> Option<f64> {
+        let present: Vec<f64> = self.values.iter().flatten().copied().collect();
+        return match present.len() {
+            0 => None,
+            count => Some(present.iter().sum::<f64>() / count as f64),
+        };
+    }
+}
+
+/// Running totals of the mean car speed of every recorded step, for the time
+/// mean speed of a long run without keeping each step's state around.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FlowAccumulator {
+    speed_sum: f64,
+    // steps without cars have no mean speed to add to the sum
+    speed_count: usize,
+    record_count: usize,
+}
+
+impl FlowAccumulator {
+    pub fn record<
+        const B: usize,
+        const C: usize,
+        const L: usize,
+        const BLW: usize,
+        const MLW: usize,
+    >(
+        &mut self,
+        road: &Road<B, C, L, BLW, MLW>,
+    ) {
+        if let Some(mean_speed) = road.mean_car_speed() {
+            self.speed_sum += mean_speed;
+            self.speed_count += 1;
+        }
+        self.record_count += 1;
+    }
+
+    /// The mean over the recorded steps of their mean car speeds, `None` if
+    /// none of them had any cars.
+    pub fn time_mean_speed(&self) -> Option<f64> {
+        return match self.speed_count {
+            0 => None,
+            count => Some(self.speed_sum / count as f64),
+        };
+    }
+
+    /// The number of steps recorded, including any without cars.
+    pub fn record_count(&self) -> usize {
+        return self.record_count;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        car::CarBuilder,
+        road::Road,
+        stats::{FlowAccumulator, RollingMean, SpeedStats, SpeedSummary, Units},
+    };
+
+    const NASCH_UNITS: Units = Units {
+        cell_length_m: 7.5,
+        step_duration_s: 1.0,
+    };
+
+    #[test]
+    fn speed_converts_to_kmh() {
+        assert_eq!(NASCH_UNITS.speed_to_m_per_s(5.0), 37.5);
+        assert_eq!(NASCH_UNITS.speed_to_kmh(5.0), 135.0);
+    }
+
+    #[test]
+    fn speed_conversion_accounts_for_step_duration() {
+        let half_second_steps = Units {
+            step_duration_s: 0.5,
+            ..NASCH_UNITS
+        };
+
+        assert_eq!(half_second_steps.speed_to_kmh(5.0), 270.0);
+        assert_eq!(half_second_steps.steps_to_s(10.0), 5.0);
+    }
+
+    #[test]
+    fn density_converts_to_veh_per_km() {
+        // a jam of 7.5m cars bumper to bumper
+        assert!((NASCH_UNITS.density_to_veh_per_km(1.0) - 133.333).abs() < 1e-3);
+        assert_eq!(NASCH_UNITS.density_to_veh_per_km(0.15), 20.0);
+    }
+
+    #[test]
+    fn units_must_be_positive_and_finite() {
+        assert_eq!(Units::new(7.5, 1.0).unwrap(), NASCH_UNITS);
+        assert_eq!(Units::NASCH, NASCH_UNITS);
+        for (cell_length_m, step_duration_s) in [
+            (0.0, 1.0),
+            (7.5, -1.0),
+            (f64::NAN, 1.0),
+            (7.5, f64::INFINITY),
+        ] {
+            assert!(Units::new(cell_length_m, step_duration_s).is_err());
+        }
+    }
+
+    #[test]
+    fn summary_of_no_speeds_is_none() {
+        assert_eq!(SpeedSummary::from_speeds([]), None);
+    }
+
+    #[test]
+    fn summary_of_odd_number_of_speeds() {
+        let summary = SpeedSummary::from_speeds([7, 1, 3]).unwrap();
+
+        assert_eq!(
+            summary,
+            SpeedSummary {
+                min: 1,
+                median: 3.0,
+                max: 7,
+                mean: 11.0 / 3.0,
+            }
+        );
+    }
+
+    #[test]
+    fn stats_of_no_speeds_is_none() {
+        assert_eq!(SpeedStats::from_speeds([]), None);
+    }
+
+    #[test]
+    fn stats_of_known_speeds() {
+        let stats = SpeedStats::from_speeds([9, 0, 4, 2, 5, 8, 7, 1, 3, 6, 10]).unwrap();
+
+        assert_eq!(stats.mean, 5.0);
+        assert_eq!(stats.std_dev, 10.0f64.sqrt());
+        assert_eq!(stats.p15, 1.5);
+        assert_eq!(stats.p50, 5.0);
+        assert_eq!(stats.p85, 8.5);
+    }
+
+    #[test]
+    fn stats_of_single_speed() {
+        let stats = SpeedStats::from_speeds([4]).unwrap();
+
+        assert_eq!(
+            stats,
+            SpeedStats {
+                mean: 4.0,
+                std_dev: 0.0,
+                p15: 4.0,
+                p50: 4.0,
+                p85: 4.0,
+            }
+        );
+    }
+
+    #[test]
+    fn rolling_mean_ages_out_old_values() {
+        let mut rolling = RollingMean::new(3).unwrap();
+        assert_eq!(rolling.current(), None);
+
+        rolling.push(Some(3.0));
+        rolling.push(Some(6.0));
+        assert_eq!(rolling.current(), Some(4.5));
+        rolling.push(Some(9.0));
+        assert_eq!(rolling.current(), Some(6.0));
+        rolling.push(Some(0.0));
+        assert_eq!(rolling.current(), Some(5.0));
+        rolling.push(None);
+        assert_eq!(rolling.current(), Some(4.5));
+        rolling.push(None);
+        rolling.push(None);
+        assert_eq!(rolling.current(), None);
+    }
+
+    #[test]
+    fn flow_accumulator_averages_step_means() {
+        let mut accumulator = FlowAccumulator::default();
+        assert_eq!(accumulator.time_mean_speed(), None);
+
+        // steps with mean speeds of 3, 5 and 1
+        for speeds in [[2, 4], [5, 5], [0, 2]] {
+            let cars = [(5, speeds[0]), (30, speeds[1])].map(|(front, speed)| {
+                CarBuilder::default()
+                    .with_front_at(front)
+                    .with_speed(speed)
+                    .build()
+                    .unwrap()
+            });
+            accumulator.record(&Road::<0, 2, 60, 0, 14>::new([], cars).unwrap());
+        }
+        accumulator.record(&Road::<0, 0, 60, 0, 14>::new([], []).unwrap());
+
+        assert_eq!(accumulator.record_count(), 4);
+        assert_eq!(accumulator.time_mean_speed(), Some(3.0));
+    }
+
+    #[test]
+    fn rolling_mean_needs_a_window() {
+        assert!(RollingMean::new(0).is_err());
+    }
+}
@@ -0,0 +1,140 @@
+use std::fmt::Write;
+
+use crate::road::Road;
+
+// pixels per cell along either axis
+const CELL_SIZE: isize = 10;
+const CAR_COLOUR: &str = "#d62728";
+const BIKE_COLOUR: &str = "#1f77b4";
+const PEDESTRIAN_COLOUR: &str = "#2ca02c";
+
+fn push_rect(
+    svg: &mut String,
+    colour: &str,
+    top: isize,
+    right: isize,
+    width: usize,
+    length: usize,
+) {
+    let left = right - width as isize + 1;
+    writeln!(
+        svg,
+        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+        left * CELL_SIZE,
+        top * CELL_SIZE,
+        width as isize * CELL_SIZE,
+        length as isize * CELL_SIZE,
+        colour
+    )
+    .expect("writing to a string shouldn't fail");
+}
+
+impl<const B: usize, const C: usize, const L: usize, const BLW: usize, const MLW: usize>
+    Road<B, C, L, BLW, MLW>
+{
+    /// Draws the road as an SVG, lats across and longs downwards as in the
+    /// `Display` of the cells, with a line between the motor and bike lanes
+    /// and a rectangle per vehicle. A vehicle wrapping around the end of the
+    /// road is drawn from its front, so the wrapped part hangs off the top.
+    pub fn to_svg(&self) -> String {
+        let width = Self::total_width() * CELL_SIZE;
+        let height = L as isize * CELL_SIZE;
+        let mut svg = String::new();
+        writeln!(
+            svg,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">",
+            width, height, width, height
+        )
+        .expect("writing to a string shouldn't fail");
+        writeln!(
+            svg,
+            "<line x1=\"{}\" y1=\"0\" x2=\"{}\" y2=\"{}\" stroke=\"black\" stroke-dasharray=\"4\"/>",
+            MLW as isize * CELL_SIZE,
+            MLW as isize * CELL_SIZE,
+            height
+        )
+        .expect("writing to a string shouldn't fail");
+
+        for car in self.cars() {
+            let occupation = car.rectangle_occupation();
+            push_rect(
+                &mut svg,
+                CAR_COLOUR,
+                occupation.back(),
+                occupation.right,
+                occupation.width,
+                occupation.length,
+            );
+        }
+        for continuous_car in self.continuous_cars() {
+            let occupation = continuous_car.rectangle_occupation();
+            push_rect(
+                &mut svg,
+                CAR_COLOUR,
+                occupation.back(),
+                occupation.right,
+                occupation.width,
+                occupation.length,
+            );
+        }
+        for bike in self.bikes() {
+            let occupation = bike.rectangle_occupation();
+            push_rect(
+                &mut svg,
+                BIKE_COLOUR,
+                occupation.back(),
+                occupation.right,
+                occupation.width,
+                occupation.length,
+            );
+        }
+        for pedestrian in self.pedestrians() {
+            if !pedestrian.is_finished() {
+                push_rect(
+                    &mut svg,
+                    PEDESTRIAN_COLOUR,
+                    pedestrian.long(),
+                    pedestrian.lat(),
+                    1,
+                    1,
+                );
+            }
+        }
+
+        svg.push_str("</svg>\n");
+        return svg;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        bike::BikeBuilder,
+        car::CarBuilder,
+        road::{Coord, Road},
+    };
+
+    #[test]
+    fn svg_has_a_rect_per_vehicle_and_the_lane_divider() {
+        let bikes = [3, 12].map(|front| {
+            BikeBuilder::deterministic_default()
+                .with_front_right_at(Coord {
+                    lat: 15,
+                    long: front,
+                })
+                .build()
+                .unwrap()
+        });
+        let car = CarBuilder::default().with_front_at(20).build().unwrap();
+        let road = Road::<2, 1, 30, 2, 14>::new(bikes, [car]).unwrap();
+
+        let svg = road.to_svg();
+
+        assert!(svg.starts_with("<svg"));
+        assert_eq!(svg.matches("<rect").count(), 3);
+        assert_eq!(svg.matches("<line").count(), 1);
+        assert!(svg.contains("<line x1=\"140\""));
+        // the car's back is at long 16
+        assert!(svg.contains("<rect x=\"0\" y=\"160\" width=\"50\" height=\"50\""));
+    }
+}